@@ -0,0 +1,304 @@
+use std::collections::HashSet;
+use std::io::Read;
+
+use indexmap::IndexMap;
+
+use crate::{
+    hourly, invalid_value, is_missing, parse_weather_code, DataPoint, Date, HourlyData, ParseDiagnostic, ParseError, ParseMode,
+    ParseReport, Problems, ResamplePolicy, WeatherData, WeatherDataMap,
+};
+
+#[derive(Clone, Copy, Debug)]
+/// Options for [`DataOps::from_csv`](crate::DataOps::from_csv)
+pub struct CsvOptions{
+    /// Byte separating the cells of a row, `b','` by default
+    pub delimiter: u8,
+    pub mode: ParseMode,
+}
+
+impl Default for CsvOptions{
+    fn default() -> CsvOptions{
+        CsvOptions{
+            delimiter: b',',
+            mode: ParseMode::Strict,
+        }
+    }
+}
+
+impl CsvOptions{
+    /// Options for a file based on its extension: tab separated for `.tsv`, comma separated otherwise
+    pub fn for_path(path: &str) -> CsvOptions{
+        let delimiter = if path.to_ascii_lowercase().ends_with(".tsv") { b'\t' } else { b',' };
+        CsvOptions{
+            delimiter,
+            ..CsvOptions::default()
+        }
+    }
+}
+
+/// Returns true if the file at the path should be read as CSV rather than the column text format
+pub fn is_csv_path(path: &str) -> bool{
+    let path = path.to_ascii_lowercase();
+    path.ends_with(".csv") || path.ends_with(".tsv")
+}
+
+fn diagnostic(error: ParseError, line: Option<usize>, field: Option<DataPoint>, token: Option<&str>, date: Option<Date>) -> ParseDiagnostic{
+    ParseDiagnostic{
+        error,
+        line,
+        field,
+        index: None,
+        token: token.map(str::to_string),
+        date,
+    }
+}
+
+/// Header of the column marking days made up by [`DataOps::fill_gaps`](crate::DataOps::fill_gaps)
+const SYNTHETIC_COLUMN: &str = "synthetic";
+
+pub(crate) fn write(map: &WeatherDataMap, points: &HashSet<DataPoint>, delimiter: u8) -> String{
+    let points: Vec<DataPoint> = if points.is_empty(){
+        DataPoint::ALL.to_vec()
+    } else {
+        DataPoint::ALL.into_iter().filter(|point| points.contains(point)).collect()
+    };
+    let mut writer = csv::WriterBuilder::new().delimiter(delimiter).from_writer(vec![]);
+
+    // Filled in days are marked in an extra column, left out when every day was recorded
+    let synthetic = map.values().any(|data| data.synthetic);
+    let header = std::iter::once(DataPoint::Date).chain(points.iter().copied()).map(|point| point.name());
+    let header: Vec<&str> = header.chain(synthetic.then_some(SYNTHETIC_COLUMN)).collect();
+    writer.write_record(header).expect("writing to memory can't fail");
+    for (date, data) in map.iter(){
+        let mut row = vec![date.to_string()];
+        for point in points.iter(){
+            row.push(match data.value(*point){
+                Some(value) => value.to_string(),
+                None => String::new(),
+            });
+        }
+        if synthetic{
+            row.push(if data.synthetic { "true".to_string() } else { String::new() });
+        }
+        writer.write_record(&row).expect("writing to memory can't fail");
+    }
+
+    let bytes = writer.into_inner().expect("writing to memory can't fail");
+    String::from_utf8(bytes).expect("dates and numbers are always valid utf-8")
+}
+
+pub(crate) fn read<R: Read>(reader: R, options: CsvOptions) -> Result<ParseReport, ParseDiagnostic>{
+    let mut problems = Problems{
+        mode: options.mode,
+        found: vec![],
+    };
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(options.delimiter)
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_reader(reader);
+
+    let header = match reader.headers(){
+        Ok(header) => header.clone(),
+        Err(e) => return Err(diagnostic(ParseError::Io(e.to_string()), Some(1), None, None, None)),
+    };
+    if hourly::is_hourly_header(&header){
+        let hours = hourly::read_rows(&mut reader, &header, &mut problems)?;
+        return Ok(ParseReport{
+            data: HourlyData::daily(&hours, ResamplePolicy::default()),
+            problems: problems.found,
+            units: None,
+        });
+    }
+    // The field read from each column, None for columns that are ignored
+    let mut columns: Vec<Option<DataPoint>> = Vec::with_capacity(header.len());
+    let mut synthetic_column = None;
+    for name in header.iter(){
+        match DataPoint::from_name(name){
+            None if name == SYNTHETIC_COLUMN && synthetic_column.is_none() => {
+                synthetic_column = Some(columns.len());
+                columns.push(None);
+            }
+            Some(field) if columns.contains(&Some(field)) => {
+                problems.report(diagnostic(ParseError::DuplicateField(field), Some(1), Some(field), Some(name), None))?;
+                columns.push(None);
+            }
+            Some(field) => columns.push(Some(field)),
+            None => {
+                problems.report(diagnostic(ParseError::UnknownField(name.to_string()), Some(1), None, Some(name), None))?;
+                columns.push(None);
+            }
+        }
+    }
+    let date_column = match columns.iter().position(|column| *column == Some(DataPoint::Date)){
+        Some(column) => column,
+        None => {
+            // Without dates there are no rows to keep
+            problems.report(diagnostic(ParseError::MissingField(DataPoint::Date), Some(1), None, None, None))?;
+            return Ok(ParseReport{
+                data: IndexMap::new(),
+                problems: problems.found,
+                units: None,
+            });
+        }
+    };
+
+    let mut dates: HashSet<Date> = HashSet::new();
+    let mut weather_data: Vec<WeatherData> = vec![];
+    for record in reader.records(){
+        let record = match record{
+            Ok(record) => record,
+            Err(e) => {
+                let line = e.position().map(|position| position.line() as usize);
+                problems.report(diagnostic(ParseError::Io(e.to_string()), line, None, None, None))?;
+                break;
+            }
+        };
+        let line = record.position().map(|position| position.line() as usize);
+        if record.len() == 1 && record[0].is_empty(){
+            continue;
+        }
+
+        let date_token = record.get(date_column).unwrap_or("");
+        let date = match Date::from_string(date_token){
+            Ok(date) if dates.contains(&date) => {
+                problems.report(diagnostic(ParseError::DuplicateDate(date), line, Some(DataPoint::Date), Some(date_token), Some(date)))?;
+                continue;
+            }
+            Ok(date) => date,
+            Err(e) => {
+                problems.report(diagnostic(e, line, Some(DataPoint::Date), Some(date_token), None))?;
+                continue;
+            }
+        };
+
+        let mut valid = true;
+        if record.len() > columns.len(){
+            problems.report(diagnostic(ParseError::TooManyValues, line, None, record.get(columns.len()), Some(date)))?;
+        }
+        if record.len() < columns.len(){
+            problems.report(diagnostic(ParseError::TooFewValues, line, None, None, Some(date)))?;
+            valid = false;
+        }
+        let mut data = WeatherData::empty(date);
+        data.synthetic = synthetic_column.and_then(|column| record.get(column)) == Some("true");
+        for (token, column) in record.iter().zip(columns.iter()){
+            let field = match column{
+                Some(DataPoint::Date) | None => continue,
+                Some(field) => *field,
+            };
+            if token.is_empty() || is_missing(token){
+                continue;
+            }
+            let value = match field{
+                DataPoint::WeatherCode => parse_weather_code(token).map(f32::from),
+                _ => token.parse::<f32>().ok(),
+            };
+            match value{
+                Some(value) => data.set_value(field, Some(value)),
+                None => {
+                    problems.report(diagnostic(invalid_value(field)(token.to_string()), line, Some(field), Some(token), Some(date)))?;
+                    valid = false;
+                }
+            }
+        }
+        if valid{
+            dates.insert(date);
+            weather_data.push(data);
+        }
+    }
+
+    weather_data.sort_by_key(|data| data.date);
+    Ok(ParseReport{
+        data: weather_data.into_iter().map(|data| (data.date, data)).collect(),
+        problems: problems.found,
+        units: None,
+    })
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    const SAMPLE: &str = "date,weather_code,temperature_max,temperature_min,precipitation_sum,wind_speed_max,precipitation_probability_max
+2024-04-25,3,54.9,44.2,0,9.3,45
+2024-04-24,61,52.6,47.1,0.22,10.1,100
+";
+
+    fn parse(csv: &str, delimiter: u8, mode: ParseMode) -> Result<ParseReport, ParseDiagnostic>{
+        read(csv.as_bytes(), CsvOptions{ delimiter, mode })
+    }
+
+    fn date(date: &str) -> Date{
+        Date::from_string(date).unwrap()
+    }
+
+    #[test]
+    fn round_trip(){
+        let map = parse(SAMPLE, b',', ParseMode::Strict).unwrap().data;
+        assert_eq!(map.keys().copied().collect::<Vec<Date>>(), vec![date("2024-04-24"), date("2024-04-25")]);
+        let written = write(&map, &HashSet::new(), b',');
+        assert!(written.starts_with("date,weather_code,temperature_max,temperature_min,precipitation_sum,wind_speed_max,precipitation_probability_max\n2024-04-24,61,52.6,47.1,0.22,10.1,100\n"));
+        let back = parse(&written, b',', ParseMode::Strict).unwrap().data;
+        assert_eq!(write(&back, &HashSet::new(), b','), written);
+    }
+
+    #[test]
+    fn other_delimiters(){
+        let tsv = SAMPLE.replace(',', "\t");
+        let map = parse(&tsv, b'\t', ParseMode::Strict).unwrap().data;
+        assert_eq!(map[&date("2024-04-24")].temp_max, Some(52.6));
+        let points = HashSet::from([DataPoint::TemperatureMax]);
+        assert_eq!(write(&map, &points, b';'), "date;temperature_max\n2024-04-24;52.6\n2024-04-25;54.9\n");
+        assert_eq!(CsvOptions::for_path("data.TSV").delimiter, b'\t');
+        assert!(is_csv_path("data.csv") && !is_csv_path("data.txt"));
+    }
+
+    #[test]
+    fn empty_and_missing_cells(){
+        let map = parse("date,temperature_max,wind_speed_max\n2024-04-24,,-\n2024-04-25,NaN,9.3\n", b',', ParseMode::Strict).unwrap().data;
+        let day = &map[&date("2024-04-24")];
+        assert_eq!((day.temp_max, day.max_wind, day.weather_code), (None, None, None));
+        assert_eq!(map[&date("2024-04-25")].max_wind, Some(9.3));
+        let points = HashSet::from([DataPoint::TemperatureMax, DataPoint::WindSpeedMax]);
+        assert_eq!(write(&map, &points, b','), "date,temperature_max,wind_speed_max\n2024-04-24,,\n2024-04-25,,9.3\n");
+    }
+
+    #[test]
+    fn bad_token_is_reported_with_its_line(){
+        let csv = "date,temperature_max\n2024-04-24,52.6\n2024-04-25,abc\n2024-04-26,54.9\n2024-04-26,50\n";
+        let e = parse(csv, b',', ParseMode::Strict).unwrap_err();
+        assert_eq!(e.error, ParseError::InvalidTemperature("abc".to_string()));
+        assert_eq!((e.line, e.field, e.date), (Some(3), Some(DataPoint::TemperatureMax), Some(date("2024-04-25"))));
+        let report = parse(csv, b',', ParseMode::Lenient).unwrap();
+        assert_eq!(report.data.keys().copied().collect::<Vec<Date>>(), vec![date("2024-04-24"), date("2024-04-26")]);
+        let problems: Vec<(Option<usize>, &ParseError)> = report.problems.iter().map(|problem| (problem.line, &problem.error)).collect();
+        assert_eq!(problems, vec![
+            (Some(3), &ParseError::InvalidTemperature("abc".to_string())),
+            (Some(5), &ParseError::DuplicateDate(date("2024-04-26"))),
+        ]);
+    }
+
+    #[test]
+    fn synthetic_column(){
+        let mut map = parse(SAMPLE, b',', ParseMode::Strict).unwrap().data;
+        let points = HashSet::from([DataPoint::TemperatureMax]);
+        // Left out while every day was recorded
+        assert_eq!(write(&map, &points, b','), "date,temperature_max\n2024-04-24,52.6\n2024-04-25,54.9\n");
+        map[1].synthetic = true;
+        let written = write(&map, &points, b',');
+        assert_eq!(written, "date,temperature_max,synthetic\n2024-04-24,52.6,\n2024-04-25,54.9,true\n");
+        let back = parse(&written, b',', ParseMode::Strict).unwrap().data;
+        assert!(!back[0].synthetic && back[1].synthetic);
+    }
+
+    #[test]
+    fn hourly_header_is_recognised(){
+        let csv = "time,temperature,precipitation\n2024-04-24T00:00,50,0.1\n2024-04-24T01:00,52,0.2\n";
+        let report = parse(csv, b',', ParseMode::Strict).unwrap();
+        let day = &report.data[&date("2024-04-24")];
+        assert_eq!((day.temp_max, day.temp_min), (Some(52.0), Some(50.0)));
+        // A date column makes it daily, whatever else is there
+        assert!(parse("date,time\n2024-04-24,x\n", b',', ParseMode::Lenient).unwrap().problems.iter().any(|problem| problem.error == ParseError::UnknownField("time".to_string())));
+    }
+}
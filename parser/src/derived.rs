@@ -0,0 +1,140 @@
+use crate::stats::Selector;
+use crate::{DataPoint, UnitSystem, WeatherData};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+/// Base temperatures for degree days, in the units the data is recorded in
+pub struct DegreeDayBases{
+    /// Days with a mean temperature below this need heating
+    pub heating: f32,
+    /// Days with a mean temperature above this need cooling
+    pub cooling: f32,
+    /// Crops don't grow below this temperature
+    pub growing: f32,
+    /// Crops don't grow any faster above this temperature, None for no cap
+    pub growing_cap: Option<f32>,
+}
+
+impl DegreeDayBases{
+    /// The usual bases: 65°F (18.3°C) for heating and cooling, 50°F (10°C) for growing with a cap at 86°F (30°C)
+    pub fn for_units(units: UnitSystem) -> DegreeDayBases{
+        match units{
+            UnitSystem::Imperial => DegreeDayBases{
+                heating: 65.0,
+                cooling: 65.0,
+                growing: 50.0,
+                growing_cap: Some(86.0),
+            },
+            UnitSystem::Metric => DegreeDayBases{
+                heating: 18.3,
+                cooling: 18.3,
+                growing: 10.0,
+                growing_cap: Some(30.0),
+            },
+        }
+    }
+}
+
+impl Default for DegreeDayBases{
+    fn default() -> DegreeDayBases{
+        DegreeDayBases::for_units(UnitSystem::default())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+/// A value computed from the high and low temperatures of a day, missing if either of them is
+/// # Examples
+/// ```
+/// use parser::{Date, DegreeDayBases, Derived, WeatherCode, WeatherData};
+/// let day = WeatherData::new(Date::new(2024, 7, 1).unwrap(), WeatherCode::new(0).unwrap(), 90.0, 70.0, 0.0, 5.0, 0.0);
+/// let bases = DegreeDayBases::default();
+/// assert_eq!(Derived::MeanTemperature.value(&day), Some(80.0));
+/// assert_eq!(Derived::DiurnalRange.value(&day), Some(20.0));
+/// assert_eq!(Derived::cooling_degree_days(&bases).value(&day), Some(15.0));
+/// assert_eq!(Derived::heating_degree_days(&bases).value(&day), Some(0.0));
+/// // The high is capped at 86°F: (86 + 70) / 2 - 50
+/// assert_eq!(Derived::growing_degree_days(&bases).value(&day), Some(28.0));
+/// ```
+pub enum Derived{
+    /// Mean of the high and low temperatures
+    MeanTemperature,
+    /// Difference between the high and low temperatures
+    DiurnalRange,
+    /// How far the mean temperature is below the base, 0 on warmer days
+    HeatingDegreeDays{ base: f32 },
+    /// How far the mean temperature is above the base, 0 on colder days
+    CoolingDegreeDays{ base: f32 },
+    /// How far the mean temperature is above the base, with the high capped and the low raised to the base first
+    GrowingDegreeDays{ base: f32, cap: Option<f32> },
+}
+
+impl Derived{
+    pub fn heating_degree_days(bases: &DegreeDayBases) -> Derived{
+        Derived::HeatingDegreeDays{ base: bases.heating }
+    }
+    pub fn cooling_degree_days(bases: &DegreeDayBases) -> Derived{
+        Derived::CoolingDegreeDays{ base: bases.cooling }
+    }
+    pub fn growing_degree_days(bases: &DegreeDayBases) -> Derived{
+        Derived::GrowingDegreeDays{ base: bases.growing, cap: bases.growing_cap }
+    }
+    /// Reads a derived value from its name (see [`Derived::name`]), with the given bases for degree days
+    pub fn from_name(name: &str, bases: &DegreeDayBases) -> Option<Derived>{
+        match name{
+            "temperature_mean" => Some(Derived::MeanTemperature),
+            "temperature_range" => Some(Derived::DiurnalRange),
+            "heating_degree_days" => Some(Derived::heating_degree_days(bases)),
+            "cooling_degree_days" => Some(Derived::cooling_degree_days(bases)),
+            "growing_degree_days" => Some(Derived::growing_degree_days(bases)),
+            _ => None,
+        }
+    }
+    pub fn name(&self) -> &'static str{
+        match self{
+            Derived::MeanTemperature => "temperature_mean",
+            Derived::DiurnalRange => "temperature_range",
+            Derived::HeatingDegreeDays{ .. } => "heating_degree_days",
+            Derived::CoolingDegreeDays{ .. } => "cooling_degree_days",
+            Derived::GrowingDegreeDays{ .. } => "growing_degree_days",
+        }
+    }
+    /// True for the degree days, which are usually added up over a season (see [`stats::cumulative`](crate::stats::cumulative))
+    pub fn is_degree_days(&self) -> bool{
+        !matches!(self, Derived::MeanTemperature | Derived::DiurnalRange)
+    }
+    /// True if the value is a temperature difference rather than a temperature, so converting it between
+    /// units only scales it (a range of 9°F is 5°C, not -12.8°C)
+    pub fn is_difference(&self) -> bool{
+        !matches!(self, Derived::MeanTemperature)
+    }
+    /// Converts a value from one unit system to another
+    pub fn convert(&self, value: f32, from: UnitSystem, to: UnitSystem) -> f32{
+        let converted = from.convert(value, DataPoint::TemperatureMax, to);
+        if self.is_difference(){
+            converted - from.convert(0.0, DataPoint::TemperatureMax, to)
+        } else {
+            converted
+        }
+    }
+    pub fn value(&self, data: &WeatherData) -> Option<f32>{
+        let (high, low) = (data.temp_max?, data.temp_min?);
+        let mean = (high + low) / 2.0;
+        let value = match self{
+            Derived::MeanTemperature => mean,
+            Derived::DiurnalRange => high - low,
+            Derived::HeatingDegreeDays{ base } => (base - mean).max(0.0),
+            Derived::CoolingDegreeDays{ base } => (mean - base).max(0.0),
+            Derived::GrowingDegreeDays{ base, cap } => {
+                let high = cap.map_or(high, |cap| high.min(cap)).max(*base);
+                let low = low.max(*base);
+                ((high + low) / 2.0 - base).max(0.0)
+            }
+        };
+        Some(value)
+    }
+}
+
+impl Selector for Derived{
+    fn select(&self, data: &WeatherData) -> Option<f32>{
+        self.value(data)
+    }
+}
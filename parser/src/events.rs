@@ -0,0 +1,202 @@
+use serde::Serialize;
+
+use crate::{Date, UnitSystem, WeatherData, WeatherDataMap};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+/// Kind of weather event [`DataOps::events`](crate::DataOps::events) looks for, each one a run of consecutive days
+/// meeting a threshold from [`Thresholds`]
+pub enum EventKind{
+    /// Days with less precipitation than the wet day threshold
+    DrySpell,
+    /// Days with at least the wet day threshold of precipitation
+    RainStreak,
+    /// Enough days in a row with a high temperature at or above the heat threshold
+    HeatWave,
+    /// Days with a low temperature at or below the frost threshold
+    Frost,
+    /// Days with a maximum wind speed at or above the wind threshold
+    HighWind,
+}
+
+impl EventKind{
+    pub const ALL: [EventKind; 5] = [EventKind::DrySpell, EventKind::RainStreak, EventKind::HeatWave, EventKind::Frost, EventKind::HighWind];
+
+    /// Reads an event kind from its name (see [`EventKind::name`]), ignoring case
+    pub fn from_name(name: &str) -> Option<EventKind>{
+        match name.to_ascii_lowercase().as_str(){
+            "dry_spell" => Some(EventKind::DrySpell),
+            "rain_streak" => Some(EventKind::RainStreak),
+            "heat_wave" => Some(EventKind::HeatWave),
+            "frost" => Some(EventKind::Frost),
+            "high_wind" => Some(EventKind::HighWind),
+            _ => None,
+        }
+    }
+    pub fn name(&self) -> &'static str{
+        match self{
+            EventKind::DrySpell => "dry_spell",
+            EventKind::RainStreak => "rain_streak",
+            EventKind::HeatWave => "heat_wave",
+            EventKind::Frost => "frost",
+            EventKind::HighWind => "high_wind",
+        }
+    }
+    /// Whether a day counts towards the event, None if the value it depends on is missing
+    fn matches(&self, data: &WeatherData, thresholds: &Thresholds) -> Option<bool>{
+        let matches = match self{
+            EventKind::DrySpell => data.precip_sum? < thresholds.wet,
+            EventKind::RainStreak => data.precip_sum? >= thresholds.wet,
+            EventKind::HeatWave => data.temp_max? >= thresholds.heat,
+            EventKind::Frost => data.temp_min? <= thresholds.frost,
+            EventKind::HighWind => data.max_wind? >= thresholds.wind,
+        };
+        Some(matches)
+    }
+    /// Fewest days in a row that make an event, only heat waves need more than one
+    fn min_days(&self, thresholds: &Thresholds) -> u32{
+        match self{
+            EventKind::HeatWave => thresholds.heat_wave_days.max(1) as u32,
+            _ => 1,
+        }
+    }
+}
+
+impl std::fmt::Display for EventKind{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result{
+        write!(f, "{}", self.name())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+/// Thresholds for the kinds of events, in the units the data is recorded in
+pub struct Thresholds{
+    /// Precipitation that makes a day wet rather than dry
+    pub wet: f32,
+    /// High temperature of a hot day
+    pub heat: f32,
+    /// Low temperature of a frost day
+    pub frost: f32,
+    /// Maximum wind speed of a windy day
+    pub wind: f32,
+    /// Hot days in a row that make a heat wave
+    pub heat_wave_days: u16,
+}
+
+impl Thresholds{
+    /// The usual thresholds: 0.01 in (0.25 mm) of precipitation, 90°F (32.2°C), 32°F (0°C), 25 mph (40 km/h)
+    /// and three days for a heat wave
+    pub fn for_units(units: UnitSystem) -> Thresholds{
+        match units{
+            UnitSystem::Imperial => Thresholds{ wet: 0.01, heat: 90.0, frost: 32.0, wind: 25.0, heat_wave_days: 3 },
+            UnitSystem::Metric => Thresholds{ wet: 0.25, heat: 32.2, frost: 0.0, wind: 40.0, heat_wave_days: 3 },
+        }
+    }
+}
+
+impl Default for Thresholds{
+    fn default() -> Thresholds{
+        Thresholds::for_units(UnitSystem::default())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+/// A run of consecutive days meeting the threshold of an event
+pub struct Event{
+    pub kind: EventKind,
+    /// First day of the event
+    pub start: Date,
+    /// Last day of the event, the same as the start for one day events
+    pub end: Date,
+    /// Number of days from the start to the end
+    pub days: u32,
+}
+
+pub(crate) fn find(map: &WeatherDataMap, kind: EventKind, thresholds: &Thresholds) -> Vec<Event>{
+    let mut days: Vec<(&Date, &WeatherData)> = map.iter().collect();
+    days.sort_by_key(|(date, _)| **date);
+
+    let mut events = vec![];
+    let mut current: Option<Event> = None;
+    for (date, data) in days{
+        let matches = kind.matches(data, thresholds) == Some(true);
+        // A day that doesn't match, has a missing value or comes after a gap in the dates ends the run
+        current = match current{
+            Some(event) if matches && event.end.add_days(1) == Some(*date) => Some(Event{ end: *date, days: event.days + 1, ..event }),
+            _ => {
+                events.extend(current);
+                matches.then_some(Event{ kind, start: *date, end: *date, days: 1 })
+            }
+        };
+    }
+    events.extend(current);
+    events.retain(|event| event.days >= kind.min_days(thresholds));
+    events
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn date(date: &str) -> Date{
+        Date::from_string(date).unwrap()
+    }
+
+    /// Days with the given high temperatures from `first` on, skipping the dates with no entry
+    fn highs(first: &str, temps: &[Option<Option<f32>>]) -> WeatherDataMap{
+        let first = date(first);
+        temps.iter().enumerate().filter_map(|(i, temp)| {
+            let date = first.add_days(i as i64).unwrap();
+            temp.map(|temp| (date, WeatherData{ temp_max: temp, ..WeatherData::empty(date) }))
+        }).collect()
+    }
+
+    fn spans(events: &[Event]) -> Vec<(Date, Date, u32)>{
+        events.iter().map(|event| (event.start, event.end, event.days)).collect()
+    }
+
+    const THRESHOLDS: Thresholds = Thresholds{ wet: 0.01, heat: 90.0, frost: 32.0, wind: 25.0, heat_wave_days: 2 };
+
+    #[test]
+    fn streaks_start_and_end_on_the_threshold(){
+        let map = highs("2024-07-01", &[Some(Some(89.9)), Some(Some(90.0)), Some(Some(95.0)), Some(Some(89.0)), Some(Some(91.0)), Some(Some(92.0))]);
+        let waves = find(&map, EventKind::HeatWave, &THRESHOLDS);
+        assert_eq!(spans(&waves), vec![
+            (date("2024-07-02"), date("2024-07-03"), 2),
+            (date("2024-07-05"), date("2024-07-06"), 2),
+        ]);
+        assert!(waves.iter().all(|event| event.kind == EventKind::HeatWave));
+
+        // Entries out of order are read by date
+        let mut reversed = map.clone();
+        reversed.reverse();
+        assert_eq!(find(&reversed, EventKind::HeatWave, &THRESHOLDS), waves);
+    }
+
+    #[test]
+    fn missing_days_and_gaps_end_a_streak(){
+        let missing = highs("2024-07-01", &[Some(Some(95.0)), Some(Some(95.0)), Some(None), Some(Some(95.0)), Some(Some(95.0))]);
+        assert_eq!(spans(&find(&missing, EventKind::HeatWave, &THRESHOLDS)), vec![
+            (date("2024-07-01"), date("2024-07-02"), 2),
+            (date("2024-07-04"), date("2024-07-05"), 2),
+        ]);
+        let gap = highs("2024-07-01", &[Some(Some(95.0)), Some(Some(95.0)), None, Some(Some(95.0)), Some(Some(95.0))]);
+        assert_eq!(spans(&find(&gap, EventKind::HeatWave, &THRESHOLDS)), spans(&find(&missing, EventKind::HeatWave, &THRESHOLDS)));
+        assert!(find(&missing, EventKind::DrySpell, &THRESHOLDS).is_empty());
+    }
+
+    #[test]
+    fn heat_waves_need_the_minimum_days(){
+        let map = highs("2024-07-01", &[Some(Some(95.0)), Some(Some(80.0)), Some(Some(95.0)), Some(Some(95.0)), Some(Some(95.0))]);
+        let three = Thresholds{ heat_wave_days: 3, ..THRESHOLDS };
+        assert_eq!(spans(&find(&map, EventKind::HeatWave, &three)), vec![(date("2024-07-03"), date("2024-07-05"), 3)]);
+        let four = Thresholds{ heat_wave_days: 4, ..THRESHOLDS };
+        assert!(find(&map, EventKind::HeatWave, &four).is_empty());
+        // Zero days is read as one, and other events are never held to the minimum
+        let zero = Thresholds{ heat_wave_days: 0, ..THRESHOLDS };
+        assert_eq!(find(&map, EventKind::HeatWave, &zero).len(), 2);
+        let frost = date("2024-01-01");
+        let frosts: WeatherDataMap = [(frost, WeatherData{ temp_min: Some(20.0), ..WeatherData::empty(frost) })].into_iter().collect();
+        assert_eq!(spans(&find(&frosts, EventKind::Frost, &four)), vec![(date("2024-01-01"), date("2024-01-01"), 1)]);
+    }
+}
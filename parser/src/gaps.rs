@@ -0,0 +1,160 @@
+use serde::Serialize;
+
+use crate::{DataPoint, Date, WeatherData, WeatherDataMap};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+/// A run of consecutive days missing from the data, between two days that are there
+pub struct Gap{
+    /// First missing day
+    pub start: Date,
+    /// Last missing day
+    pub end: Date,
+    /// Number of missing days
+    pub days: u32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+/// How [`DataOps::fill_gaps`](crate::DataOps::fill_gaps) makes up the values of a missing day
+pub enum FillMethod{
+    /// On a straight line between the days on either side of the gap
+    Linear,
+    /// Zero, for amounts that are usually nothing
+    Zero,
+    /// The value of the day before the gap
+    CarryForward,
+}
+
+impl FillMethod{
+    /// The way a field is filled: linearly for temperatures, wind speed and precipitation chance,
+    /// with zero for precipitation and carried forward for the weather code
+    pub fn for_field(point: DataPoint) -> FillMethod{
+        match point{
+            DataPoint::PrecipitationSum => FillMethod::Zero,
+            DataPoint::WeatherCode | DataPoint::Date => FillMethod::CarryForward,
+            DataPoint::TemperatureMax | DataPoint::TemperatureMin | DataPoint::WindSpeedMax | DataPoint::PrecipitationProbabilityMax => FillMethod::Linear,
+        }
+    }
+}
+
+/// Dates of the map in order
+fn sorted_dates(map: &WeatherDataMap) -> Vec<Date>{
+    let mut dates: Vec<Date> = map.keys().copied().collect();
+    dates.sort();
+    dates
+}
+
+pub(crate) fn find(map: &WeatherDataMap) -> Vec<Gap>{
+    sorted_dates(map)
+        .windows(2)
+        .filter_map(|pair| {
+            let missing = pair[0].days_between(&pair[1]) - 1;
+            if missing <= 0{
+                return None;
+            }
+            Some(Gap{
+                start: pair[0].add_days(1)?,
+                end: pair[1].add_days(-1)?,
+                days: missing as u32,
+            })
+        })
+        .collect()
+}
+
+pub(crate) fn fill(map: &WeatherDataMap) -> WeatherDataMap{
+    let mut filled: WeatherDataMap = sorted_dates(map).into_iter().map(|date| (date, map[&date].clone())).collect();
+    for gap in find(map){
+        let before = &map[&gap.start.add_days(-1).expect("the day before a gap is in the data")];
+        let after = &map[&gap.end.add_days(1).expect("the day after a gap is in the data")];
+        for day in 1..=gap.days{
+            let date = match gap.start.add_days(day as i64 - 1){
+                Some(date) => date,
+                None => continue,
+            };
+            let mut data = WeatherData::empty(date);
+            data.synthetic = true;
+            for point in DataPoint::ALL{
+                let value = match FillMethod::for_field(point){
+                    FillMethod::Zero => Some(0.0),
+                    FillMethod::CarryForward => before.value(point),
+                    FillMethod::Linear => match (before.value(point), after.value(point)){
+                        (Some(first), Some(last)) => Some(first + (last - first) * day as f32 / (gap.days + 1) as f32),
+                        _ => None,
+                    },
+                };
+                data.set_value(point, value);
+            }
+            filled.insert(date, data);
+        }
+    }
+    filled.sort_keys();
+    filled
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::WeatherCode;
+
+    fn date(date: &str) -> Date{
+        Date::from_string(date).unwrap()
+    }
+
+    /// Days on the given dates, out of order, with a three day gap and a one day gap
+    fn sample() -> WeatherDataMap{
+        [
+            WeatherData::new(date("2024-04-05"), WeatherCode::new(61).unwrap(), 58.0, 44.0, 1.0, 14.0, 100.0),
+            WeatherData::new(date("2024-04-01"), WeatherCode::new(3).unwrap(), 50.0, 40.0, 0.5, 10.0, 20.0),
+            WeatherData::new(date("2024-04-08"), WeatherCode::new(0).unwrap(), 60.0, 45.0, 0.0, 8.0, 0.0),
+            WeatherData{ temp_max: None, ..WeatherData::new(date("2024-04-06"), WeatherCode::new(0).unwrap(), 0.0, 45.0, 0.0, 8.0, 0.0) },
+        ].into_iter().map(|data| (data.date, data)).collect()
+    }
+
+    fn row(data: &WeatherData) -> [Option<f32>; 6]{
+        DataPoint::ALL.map(|point| data.value(point))
+    }
+
+    #[test]
+    fn find_single_and_multi_day_gaps(){
+        assert_eq!(find(&sample()), vec![
+            Gap{ start: date("2024-04-02"), end: date("2024-04-04"), days: 3 },
+            Gap{ start: date("2024-04-07"), end: date("2024-04-07"), days: 1 },
+        ]);
+        let whole: WeatherDataMap = sample().into_iter().filter(|(date, _)| *date >= self::date("2024-04-05")).collect();
+        assert_eq!(find(&whole).len(), 1);
+        assert!(find(&WeatherDataMap::new()).is_empty());
+    }
+
+    #[test]
+    fn fill_interpolates_the_missing_days(){
+        let map = sample();
+        let filled = fill(&map);
+        assert_eq!(filled.len(), 8);
+        assert_eq!(filled.keys().copied().collect::<Vec<Date>>(), (1..=8).map(|day| Date::new(2024, 4, day).unwrap()).collect::<Vec<Date>>());
+
+        let middle: Vec<[Option<f32>; 4]> = ["2024-04-02", "2024-04-03", "2024-04-04"]
+            .iter()
+            .map(|day| {
+                let data = &filled[&date(day)];
+                [data.temp_max, data.temp_min, data.max_wind, data.precip_prob_max]
+            })
+            .collect();
+        assert_eq!(middle, vec![
+            [Some(52.0), Some(41.0), Some(11.0), Some(40.0)],
+            [Some(54.0), Some(42.0), Some(12.0), Some(60.0)],
+            [Some(56.0), Some(43.0), Some(13.0), Some(80.0)],
+        ]);
+        for day in ["2024-04-02", "2024-04-03", "2024-04-04", "2024-04-07"]{
+            let data = &filled[&date(day)];
+            assert!(data.synthetic);
+            assert_eq!(data.precip_sum, Some(0.0));
+        }
+        assert_eq!(filled[&date("2024-04-03")].weather_code.map(|code| code.code()), Some(3));
+        // A value missing on one side of the gap can't be interpolated
+        assert_eq!((filled[&date("2024-04-07")].temp_max, filled[&date("2024-04-07")].temp_min), (None, Some(45.0)));
+
+        // The days that were there are kept as they are
+        assert!(map.iter().all(|(date, data)| row(&filled[date]) == row(data) && !filled[date].synthetic));
+        let again = fill(&filled);
+        assert!(again.iter().all(|(date, data)| row(data) == row(&filled[date])) && again.len() == filled.len());
+    }
+}
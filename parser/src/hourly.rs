@@ -0,0 +1,422 @@
+use std::collections::HashSet;
+use std::io::Read;
+
+use indexmap::IndexMap;
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{
+    is_missing, parse_weather_code, CsvOptions, Date, ParseDiagnostic, ParseError, ParseResult, Problems, ResamplePolicy, WeatherCode,
+    WeatherData, WeatherDataMap,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+/// A time of day on a date, to the minute, in the local time of wherever the data was recorded
+pub struct Timestamp{
+    date: Date,
+    hour: u8,
+    minute: u8,
+}
+
+impl Timestamp{
+    /// Creates a timestamp from its parts, checking that the time exists
+    /// # Errors
+    /// Returns an error if the hour is over 23 or the minute over 59
+    pub fn new(date: Date, hour: u8, minute: u8) -> ParseResult<Timestamp>{
+        if hour > 23 || minute > 59{
+            return Err(ParseError::InvalidTimestamp(format!("{}T{:02}:{:02}", date, hour, minute)));
+        }
+        Ok(Timestamp{ date, hour, minute })
+    }
+
+    /// Reads a timestamp formatted as "YYYY-MM-DDTHH:MM" (the way Open-Meteo writes them) or with a space instead of
+    /// the `T`. Seconds are allowed but dropped
+    /// # Errors
+    /// Returns an error if the timestamp isn't formatted correctly or the date or time doesn't exist
+    /// # Examples
+    /// ```
+    /// use parser::Timestamp;
+    /// let time = Timestamp::from_string("2024-04-24T13:30").unwrap();
+    /// assert_eq!((time.hour(), time.minute()), (13, 30));
+    /// assert_eq!(Timestamp::from_string("2024-04-24 13:30:00").unwrap(), time);
+    /// assert!(Timestamp::from_string("2024-04-24T24:00").is_err());
+    /// assert!(Timestamp::from_string("2024-04-24").is_err());
+    /// ```
+    pub fn from_string(timestamp: &str) -> ParseResult<Timestamp>{
+        let invalid = || ParseError::InvalidTimestamp(timestamp.to_string());
+        let (date, time) = timestamp.split_once(['T', ' ']).ok_or_else(invalid)?;
+        let date = Date::from_string(date).map_err(|_| invalid())?;
+        let parts: Vec<&str> = time.split(':').collect();
+        if !(2..=3).contains(&parts.len()) || parts.iter().any(|part| part.len() != 2){
+            return Err(invalid());
+        }
+        let (hour, minute) = match (parts[0].parse(), parts[1].parse()){
+            (Ok(hour), Ok(minute)) => (hour, minute),
+            _ => return Err(invalid()),
+        };
+        if parts.len() == 3 && !matches!(parts[2].parse::<u8>(), Ok(0..=59)){
+            return Err(invalid());
+        }
+        Timestamp::new(date, hour, minute).map_err(|_| invalid())
+    }
+    pub fn date(&self) -> Date{
+        self.date
+    }
+    pub fn hour(&self) -> u8{
+        self.hour
+    }
+    pub fn minute(&self) -> u8{
+        self.minute
+    }
+}
+
+impl std::fmt::Display for Timestamp{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result{
+        write!(f, "{}T{:02}:{:02}", self.date, self.hour, self.minute)
+    }
+}
+
+impl Serialize for Timestamp{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error>{
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Timestamp{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error>{
+        let timestamp = <std::borrow::Cow<str>>::deserialize(deserializer)?;
+        Timestamp::from_string(&timestamp).map_err(D::Error::custom)
+    }
+}
+
+pub type HourlyDataMap = IndexMap<Timestamp, HourlyData>;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+/// A single observation from an hourly (or more frequent) station export, with every value possibly missing
+///
+/// Values are in the same units as the daily fields they're summarized into, see [`HourlyData::daily`]
+pub struct HourlyData{
+    pub time: Timestamp,
+    pub weather_code: Option<WeatherCode>,
+    pub temperature: Option<f32>,
+    /// Precipitation since the previous observation
+    pub precipitation: Option<f32>,
+    pub precipitation_probability: Option<f32>,
+    pub wind_speed: Option<f32>,
+    /// Degrees clockwise from north the wind is blowing from
+    pub wind_direction: Option<f32>,
+    /// Relative humidity in percent
+    pub humidity: Option<f32>,
+    /// Air pressure in hPa
+    pub pressure: Option<f32>,
+}
+
+#[derive(Debug, Clone)]
+/// Observations that were parsed successfully, along with every problem that was skipped over to get them
+pub struct HourlyReport{
+    pub data: HourlyDataMap,
+    pub problems: Vec<ParseDiagnostic>,
+}
+
+/// A column of an hourly export
+#[derive(Clone, Copy, PartialEq)]
+enum Column{
+    Time,
+    WeatherCode,
+    Temperature,
+    Precipitation,
+    PrecipitationProbability,
+    WindSpeed,
+    WindDirection,
+    Humidity,
+    Pressure,
+}
+
+impl Column{
+    /// Looks up a column from our own name for it or the name Open-Meteo's hourly variables have
+    fn from_name(name: &str) -> Option<Column>{
+        match name.to_ascii_lowercase().as_str(){
+            "time" | "timestamp" => Some(Column::Time),
+            "weather_code" | "weathercode" => Some(Column::WeatherCode),
+            "temperature" | "temperature_2m" => Some(Column::Temperature),
+            "precipitation" => Some(Column::Precipitation),
+            "precipitation_probability" => Some(Column::PrecipitationProbability),
+            "wind_speed" | "wind_speed_10m" | "windspeed_10m" => Some(Column::WindSpeed),
+            "wind_direction" | "wind_direction_10m" | "winddirection_10m" => Some(Column::WindDirection),
+            "humidity" | "relative_humidity" | "relative_humidity_2m" => Some(Column::Humidity),
+            "pressure" | "pressure_msl" | "surface_pressure" => Some(Column::Pressure),
+            _ => None,
+        }
+    }
+    fn invalid_value(&self) -> fn(String) -> ParseError{
+        match self{
+            Column::Time => ParseError::InvalidTimestamp,
+            Column::WeatherCode => ParseError::InvalidWeatherCode,
+            Column::Temperature => ParseError::InvalidTemperature,
+            Column::Precipitation => ParseError::InvalidPrecipitation,
+            Column::PrecipitationProbability => ParseError::InvalidPrecipitationProbability,
+            Column::WindSpeed => ParseError::InvalidWind,
+            Column::WindDirection | Column::Humidity | Column::Pressure => ParseError::InvalidValue,
+        }
+    }
+    fn set(&self, data: &mut HourlyData, value: f32){
+        match self{
+            Column::WeatherCode => data.weather_code = WeatherCode::from_value(value),
+            Column::Temperature => data.temperature = Some(value),
+            Column::Precipitation => data.precipitation = Some(value),
+            Column::PrecipitationProbability => data.precipitation_probability = Some(value),
+            Column::WindSpeed => data.wind_speed = Some(value),
+            Column::WindDirection => data.wind_direction = Some(value),
+            Column::Humidity => data.humidity = Some(value),
+            Column::Pressure => data.pressure = Some(value),
+            Column::Time => (),
+        }
+    }
+}
+
+fn diagnostic(error: ParseError, line: Option<usize>, token: Option<&str>, date: Option<Date>) -> ParseDiagnostic{
+    ParseDiagnostic{
+        error,
+        line,
+        field: None,
+        index: None,
+        token: token.map(str::to_string),
+        date,
+    }
+}
+
+/// Returns true if a CSV header has a time column and no date column, the way hourly exports are laid out
+pub(crate) fn is_hourly_header(header: &csv::StringRecord) -> bool{
+    header.iter().any(|name| Column::from_name(name) == Some(Column::Time)) && !header.iter().any(|name| name == "date")
+}
+
+/// Reads the rows of an hourly export whose header has already been read
+pub(crate) fn read_rows<R: Read>(reader: &mut csv::Reader<R>, header: &csv::StringRecord, problems: &mut Problems) -> Result<HourlyDataMap, ParseDiagnostic>{
+    // The column read from each position, None for columns that are ignored
+    let mut columns: Vec<Option<Column>> = Vec::with_capacity(header.len());
+    for name in header.iter(){
+        match Column::from_name(name){
+            Some(column) if columns.contains(&Some(column)) => {
+                problems.report(diagnostic(ParseError::DuplicateColumn(name.to_string()), Some(1), Some(name), None))?;
+                columns.push(None);
+            }
+            Some(column) => columns.push(Some(column)),
+            None => {
+                problems.report(diagnostic(ParseError::UnknownField(name.to_string()), Some(1), Some(name), None))?;
+                columns.push(None);
+            }
+        }
+    }
+    let time_column = columns.iter().position(|column| *column == Some(Column::Time)).expect("hourly exports have a time column");
+
+    let mut hours: Vec<HourlyData> = vec![];
+    let mut times: HashSet<Timestamp> = HashSet::new();
+    for record in reader.records(){
+        let record = match record{
+            Ok(record) => record,
+            Err(e) => {
+                let line = e.position().map(|position| position.line() as usize);
+                problems.report(diagnostic(ParseError::Io(e.to_string()), line, None, None))?;
+                break;
+            }
+        };
+        let line = record.position().map(|position| position.line() as usize);
+        if record.len() == 1 && record[0].is_empty(){
+            continue;
+        }
+
+        let time_token = record.get(time_column).unwrap_or("");
+        let time = match Timestamp::from_string(time_token){
+            Ok(time) if times.contains(&time) => {
+                problems.report(diagnostic(ParseError::DuplicateTimestamp(time), line, Some(time_token), Some(time.date)))?;
+                continue;
+            }
+            Ok(time) => time,
+            Err(e) => {
+                problems.report(diagnostic(e, line, Some(time_token), None))?;
+                continue;
+            }
+        };
+
+        let mut valid = true;
+        if record.len() > columns.len(){
+            problems.report(diagnostic(ParseError::TooManyValues, line, record.get(columns.len()), Some(time.date)))?;
+        }
+        if record.len() < columns.len(){
+            problems.report(diagnostic(ParseError::TooFewValues, line, None, Some(time.date)))?;
+            valid = false;
+        }
+        let mut data = HourlyData::empty(time);
+        for (token, column) in record.iter().zip(columns.iter()){
+            let column = match column{
+                Some(Column::Time) | None => continue,
+                Some(column) => *column,
+            };
+            if token.is_empty() || is_missing(token){
+                continue;
+            }
+            let value = match column{
+                Column::WeatherCode => parse_weather_code(token).map(f32::from),
+                _ => token.parse::<f32>().ok(),
+            };
+            match value{
+                Some(value) => column.set(&mut data, value),
+                None => {
+                    problems.report(diagnostic(column.invalid_value()(token.to_string()), line, Some(token), Some(time.date)))?;
+                    valid = false;
+                }
+            }
+        }
+        if valid{
+            times.insert(time);
+            hours.push(data);
+        }
+    }
+
+    hours.sort_by_key(|data| data.time);
+    Ok(hours.into_iter().map(|data| (data.time, data)).collect())
+}
+
+/// Hours of a day in the data that have a value, for telling complete days from partial ones
+fn hours_with(hours: &[&HourlyData], value: fn(&HourlyData) -> bool) -> usize{
+    hours.iter().filter(|data| value(data)).map(|data| data.time.hour).collect::<HashSet<u8>>().len()
+}
+
+impl HourlyData{
+    /// Creates an observation for the given time with every value missing
+    pub fn empty(time: Timestamp) -> HourlyData{
+        HourlyData{
+            time,
+            weather_code: None,
+            temperature: None,
+            precipitation: None,
+            precipitation_probability: None,
+            wind_speed: None,
+            wind_direction: None,
+            humidity: None,
+            pressure: None,
+        }
+    }
+
+    /// Reads an hourly export as CSV, with a header row naming the columns in any order and one row per observation
+    ///
+    /// Only the `time` column is required. Columns can use our own names (`temperature`, `wind_speed`, `humidity`...)
+    /// or those of Open-Meteo's hourly variables (`temperature_2m`, `wind_speed_10m`, `relative_humidity_2m`...).
+    /// Missing values are read the same way as by [`DataOps::from_csv`](crate::DataOps::from_csv), which reads
+    /// hourly exports too, summarizing them into days
+    /// # Errors
+    /// In [`ParseMode::Strict`](crate::ParseMode::Strict), returns the first problem found in the data
+    pub fn from_csv<R: Read>(reader: R, options: CsvOptions) -> Result<HourlyReport, ParseDiagnostic>{
+        let mut problems = Problems{
+            mode: options.mode,
+            found: vec![],
+        };
+        let mut reader = csv::ReaderBuilder::new()
+            .delimiter(options.delimiter)
+            .flexible(true)
+            .trim(csv::Trim::All)
+            .from_reader(reader);
+        let header = match reader.headers(){
+            Ok(header) => header.clone(),
+            Err(e) => return Err(diagnostic(ParseError::Io(e.to_string()), Some(1), None, None)),
+        };
+        if !header.iter().any(|name| Column::from_name(name) == Some(Column::Time)){
+            // Without times there are no rows to keep
+            problems.report(diagnostic(ParseError::MissingColumn("time".to_string()), Some(1), None, None))?;
+            return Ok(HourlyReport{
+                data: IndexMap::new(),
+                problems: problems.found,
+            });
+        }
+        let data = read_rows(&mut reader, &header, &mut problems)?;
+        Ok(HourlyReport{
+            data,
+            problems: problems.found,
+        })
+    }
+
+    /// Reads a JSON array of observations, sorted by time
+    /// # Errors
+    /// Returns an error if the JSON isn't an array of observations or two of them are at the same time
+    pub fn from_json(json: &str) -> Result<HourlyDataMap, ParseDiagnostic>{
+        match serde_json::from_str(json){
+            Ok(hours) => HourlyData::from_records(hours),
+            Err(e) => Err(ParseDiagnostic::from(ParseError::InvalidJson(e.to_string()))),
+        }
+    }
+
+    /// Keys observations read some other way (from an already parsed JSON document, say) by their time, in order
+    /// # Errors
+    /// Returns an error if two of them are at the same time
+    pub fn from_records(mut hours: Vec<HourlyData>) -> Result<HourlyDataMap, ParseDiagnostic>{
+        hours.sort_by_key(|data| data.time);
+        let mut map: HourlyDataMap = IndexMap::with_capacity(hours.len());
+        for data in hours{
+            if map.contains_key(&data.time){
+                return Err(diagnostic(ParseError::DuplicateTimestamp(data.time), None, None, Some(data.time.date)));
+            }
+            map.insert(data.time, data);
+        }
+        Ok(map)
+    }
+
+    /// Summarizes observations into one entry per day: the highest and lowest temperature, the total precipitation,
+    /// the highest wind speed and precipitation chance, and the most severe weather code (the highest WMO code)
+    ///
+    /// With [`ResamplePolicy::RequireComplete`], a value is missing unless all 24 hours of the day have it.
+    /// Direction, humidity and pressure have no daily field and are left out
+    /// # Examples
+    /// ```
+    /// use parser::{CsvOptions, Date, HourlyData, ResamplePolicy};
+    /// let csv = "time,temperature_2m,precipitation,wind_speed_10m,weather_code
+    /// 2024-04-24T00:00,48.2,0,5.1,3
+    /// 2024-04-24T12:00,61.0,0.25,9.3,61
+    /// 2024-04-24T18:00,57.4,0.5,7.0,3
+    /// 2024-04-25T00:00,50.0,,4.2,2";
+    ///
+    /// let hourly = HourlyData::from_csv(csv.as_bytes(), CsvOptions::default()).unwrap().data;
+    /// let daily = HourlyData::daily(&hourly, ResamplePolicy::SkipMissing);
+    /// let day = &daily[&Date::from_string("2024-04-24").unwrap()];
+    /// assert_eq!((day.temp_max, day.temp_min), (Some(61.0), Some(48.2)));
+    /// assert_eq!(day.precip_sum, Some(0.75));
+    /// assert_eq!(day.weather_code.unwrap().code(), 61);
+    /// assert_eq!(daily[1].precip_sum, None);
+    /// // Only 3 of the 24 hours were recorded
+    /// let complete = HourlyData::daily(&hourly, ResamplePolicy::RequireComplete);
+    /// assert_eq!(complete[0].temp_max, None);
+    /// ```
+    pub fn daily(hourly: &HourlyDataMap, policy: ResamplePolicy) -> WeatherDataMap{
+        let mut days: IndexMap<Date, Vec<&HourlyData>> = IndexMap::new();
+        for data in hourly.values(){
+            days.entry(data.time.date).or_default().push(data);
+        }
+        days.sort_keys();
+
+        let mut daily: WeatherDataMap = IndexMap::with_capacity(days.len());
+        for (date, hours) in days{
+            // A value that isn't there for every hour only counts as missing with RequireComplete
+            let complete = |value: fn(&HourlyData) -> bool| policy == ResamplePolicy::SkipMissing || hours_with(&hours, value) == 24;
+            let values = |value: fn(&HourlyData) -> Option<f32>| hours.iter().filter_map(move |data| value(data));
+
+            let mut data = WeatherData::empty(date);
+            if complete(|data| data.weather_code.is_some()){
+                data.weather_code = hours.iter().filter_map(|data| data.weather_code).max();
+            }
+            if complete(|data| data.temperature.is_some()){
+                data.temp_max = values(|data| data.temperature).reduce(f32::max);
+                data.temp_min = values(|data| data.temperature).reduce(f32::min);
+            }
+            if complete(|data| data.precipitation.is_some()){
+                data.precip_sum = values(|data| data.precipitation).reduce(|sum, value| sum + value);
+            }
+            if complete(|data| data.wind_speed.is_some()){
+                data.max_wind = values(|data| data.wind_speed).reduce(f32::max);
+            }
+            if complete(|data| data.precipitation_probability.is_some()){
+                data.precip_prob_max = values(|data| data.precipitation_probability).reduce(f32::max);
+            }
+            daily.insert(date, data);
+        }
+        daily
+    }
+}
@@ -1,8 +1,39 @@
 use std::collections::HashSet;
+use std::io::BufRead;
 
 use indexmap::IndexMap;
+use serde::de::Error as _;
+use serde::ser::SerializeMap;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
-#[derive(Debug)]
+mod csv_format;
+mod derived;
+mod events;
+mod gaps;
+mod hourly;
+mod merge;
+mod open_meteo;
+mod quality;
+mod resample;
+mod stations;
+pub mod stats;
+mod units;
+mod weather_code;
+
+pub use csv_format::{is_csv_path, CsvOptions};
+pub use derived::{DegreeDayBases, Derived};
+pub use events::{Event, EventKind, Thresholds};
+pub use gaps::{FillMethod, Gap};
+pub use hourly::{HourlyData, HourlyDataMap, HourlyReport, Timestamp};
+pub use merge::{Conflict, FieldConflict, MergeReport, MergeStrategy};
+pub use open_meteo::{from_open_meteo, read_open_meteo, OpenMeteoImport};
+pub use quality::{Implausible, Issue, OutlierTest, QualityFlag};
+pub use resample::{Period, ResamplePolicy};
+pub use stations::{Dataset, DatasetReport, Station, DEFAULT_STATION};
+pub use units::{celsius_to_fahrenheit, fahrenheit_to_celsius, inches_to_millimeters, kmh_to_mph, millimeters_to_inches, mph_to_kmh, UnitSystem};
+pub use weather_code::{WeatherCategory, WeatherCode};
+
+#[derive(Debug, Clone, PartialEq)]
 /// Represents an error that can occur during parsing
 pub enum ParseError{
     InvalidDate(String),
@@ -12,11 +43,158 @@ pub enum ParseError{
     InvalidWind(String),
     InvalidPrecipitationProbability(String),
     InvalidLine(String),
+    /// A line has more values than there are dates
     TooManyValues,
+    /// A line has fewer values than there are dates
+    TooFewValues,
     DuplicateDate(Date),
+    /// A field appears on more than one line
+    DuplicateField(DataPoint),
+    /// A column or field name that isn't one of the known fields
+    UnknownField(String),
+    /// A field has no line (or column) at all
+    MissingField(DataPoint),
+    /// The input couldn't be read
+    Io(String),
+    /// A JSON document that isn't valid or doesn't have the expected structure
+    InvalidJson(String),
+    /// The `units:` line names something other than metric or imperial
+    InvalidUnits(String),
+    /// A station id appears on more than one `station:` line
+    DuplicateStation(String),
+    /// A latitude, longitude or elevation that isn't a number or is off the globe
+    InvalidCoordinate(String),
+    /// A time that isn't formatted as YYYY-MM-DDTHH:MM or doesn't exist
+    InvalidTimestamp(String),
+    DuplicateTimestamp(Timestamp),
+    /// A wind direction, humidity or pressure that isn't a number
+    InvalidValue(String),
+    /// A column of an hourly export appears more than once
+    DuplicateColumn(String),
+    /// An hourly export has no column for a value it can't do without
+    MissingColumn(String),
+}
+
+impl std::fmt::Display for ParseError{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result{
+        match self{
+            ParseError::InvalidDate(msg) => write!(f, "{}", msg),
+            ParseError::InvalidWeatherCode(code) => write!(f, "'{}' is not a WMO weather code", code),
+            ParseError::InvalidTemperature(value)
+            | ParseError::InvalidPrecipitation(value)
+            | ParseError::InvalidWind(value)
+            | ParseError::InvalidPrecipitationProbability(value)
+            | ParseError::InvalidValue(value) => write!(f, "'{}' is not a number", value),
+            ParseError::InvalidLine(line) => write!(f, "invalid line '{}', expected 'field_name: values...'", line),
+            ParseError::TooManyValues => write!(f, "more values than there are dates"),
+            ParseError::TooFewValues => write!(f, "missing value, there are fewer values than dates"),
+            ParseError::DuplicateDate(date) => write!(f, "duplicate date {}", date),
+            ParseError::DuplicateField(field) => write!(f, "field {} appears more than once", field.name()),
+            ParseError::UnknownField(name) => write!(f, "unknown field '{}'", name),
+            ParseError::MissingField(field) => write!(f, "missing field {}", field.name()),
+            ParseError::Io(msg) => write!(f, "could not read data: {}", msg),
+            ParseError::InvalidJson(msg) => write!(f, "invalid JSON document: {}", msg),
+            ParseError::InvalidUnits(units) => write!(f, "'{}' is not a unit system, expected metric or imperial", units),
+            ParseError::DuplicateStation(id) => write!(f, "station {} appears more than once", id),
+            ParseError::InvalidCoordinate(value) => write!(f, "'{}' is not a valid latitude, longitude or elevation", value),
+            ParseError::InvalidTimestamp(time) => write!(f, "invalid time '{}', expected YYYY-MM-DDTHH:MM", time),
+            ParseError::DuplicateTimestamp(time) => write!(f, "duplicate time {}", time),
+            ParseError::DuplicateColumn(name) => write!(f, "column {} appears more than once", name),
+            ParseError::MissingColumn(name) => write!(f, "missing column {}", name),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+#[derive(Debug, Clone, PartialEq)]
+/// A [`ParseError`] together with where it occurred in the column text format
+/// 
+/// Displays as e.g. "line 3 (temperature_max), value #5 for 2024-04-28: 'abc' is not a number",
+/// leaving out whatever parts of the location aren't known
+pub struct ParseDiagnostic{
+    pub error: ParseError,
+    /// Line number in the input, starting at 1
+    pub line: Option<usize>,
+    /// Field of the line the error is on
+    pub field: Option<DataPoint>,
+    /// Position of the value on its line, starting at 1
+    pub index: Option<usize>,
+    /// The token that couldn't be parsed
+    pub token: Option<String>,
+    /// Date the offending value belongs to
+    pub date: Option<Date>,
+}
+
+impl ParseDiagnostic{
+    fn on_line(error: ParseError, line: usize, field: Option<DataPoint>) -> ParseDiagnostic{
+        ParseDiagnostic{
+            line: Some(line),
+            field,
+            ..ParseDiagnostic::from(error)
+        }
+    }
+    fn at_value(error: ParseError, line: &RawLine, index: usize, token: Option<&str>, date: Option<Date>) -> ParseDiagnostic{
+        ParseDiagnostic{
+            error,
+            line: Some(line.number),
+            field: Some(line.field),
+            index: Some(index + 1),
+            token: token.map(str::to_string),
+            date,
+        }
+    }
+}
+
+impl From<ParseError> for ParseDiagnostic{
+    fn from(error: ParseError) -> ParseDiagnostic{
+        ParseDiagnostic{
+            error,
+            line: None,
+            field: None,
+            index: None,
+            token: None,
+            date: None,
+        }
+    }
+}
+
+impl std::fmt::Display for ParseDiagnostic{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result{
+        let mut location = String::new();
+        match (self.line, self.field){
+            (Some(line), Some(field)) => location.push_str(&format!("line {} ({})", line, field.name())),
+            (Some(line), None) => location.push_str(&format!("line {}", line)),
+            (None, Some(field)) => location.push_str(field.name()),
+            (None, None) => (),
+        }
+        if let Some(index) = self.index{
+            if !location.is_empty(){
+                location.push_str(", ");
+            }
+            location.push_str(&format!("value #{}", index));
+        }
+        if let Some(date) = self.date{
+            if !location.is_empty(){
+                location.push(' ');
+            }
+            location.push_str(&format!("for {}", date));
+        }
+        if location.is_empty(){
+            write!(f, "{}", self.error)
+        } else {
+            write!(f, "{}: {}", location, self.error)
+        }
+    }
 }
 
-#[derive(PartialEq, Eq, Hash)]
+impl std::error::Error for ParseDiagnostic{
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)>{
+        Some(&self.error)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum DataPoint{
     WeatherCode,
     TemperatureMax,
@@ -27,47 +205,233 @@ pub enum DataPoint{
     Date
 }
 
+impl DataPoint{
+    /// Every value field of a WeatherData entry, in the order they're written out
+    pub const ALL: [DataPoint; 6] = [
+        DataPoint::WeatherCode,
+        DataPoint::TemperatureMax,
+        DataPoint::TemperatureMin,
+        DataPoint::PrecipitationSum,
+        DataPoint::WindSpeedMax,
+        DataPoint::PrecipitationProbabilityMax,
+    ];
+
+    /// Looks up a field from the name used in the text format and in JSON
+    pub fn from_name(name: &str) -> Option<DataPoint>{
+        match name{
+            "weather_code" => Some(DataPoint::WeatherCode),
+            "temperature_max" => Some(DataPoint::TemperatureMax),
+            "temperature_min" => Some(DataPoint::TemperatureMin),
+            "precipitation_sum" => Some(DataPoint::PrecipitationSum),
+            "wind_speed_max" => Some(DataPoint::WindSpeedMax),
+            "precipitation_probability_max" => Some(DataPoint::PrecipitationProbabilityMax),
+            "date" => Some(DataPoint::Date),
+            _ => None,
+        }
+    }
+
+    /// Name of the field as used in the text format and in JSON
+    pub fn name(&self) -> &'static str{
+        match self{
+            DataPoint::WeatherCode => "weather_code",
+            DataPoint::TemperatureMax => "temperature_max",
+            DataPoint::TemperatureMin => "temperature_min",
+            DataPoint::PrecipitationSum => "precipitation_sum",
+            DataPoint::WindSpeedMax => "wind_speed_max",
+            DataPoint::PrecipitationProbabilityMax => "precipitation_probability_max",
+            DataPoint::Date => "date",
+        }
+    }
+}
+
+impl Serialize for DataPoint{
+    /// Serializes as the name of the field
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error>{
+        serializer.serialize_str(self.name())
+    }
+}
+
 pub type ParseResult<T> = Result<T, ParseError>;
 pub type WeatherDataMap = IndexMap<Date, WeatherData>;
 
 pub trait DataOps{
     fn take_range(&self, begin: &Date, end: &Date) -> WeatherDataMap;
+    /// Writes the data as a JSON array of records with only the selected fields (all of them if empty)
+    /// A selected weather code comes with its `weather_description`
     fn json(self, points: HashSet<DataPoint>) -> String;
     fn to_file(&self) -> String;
+    /// Same as [`DataOps::to_file`], starting with a `units:` line recording the units the values are in
+    fn to_file_in(&self, units: UnitSystem) -> String;
+    /// Returns a copy of the data with every value converted from one unit system to another
+    fn convert_units(&self, from: UnitSystem, to: UnitSystem) -> WeatherDataMap;
+    /// Groups the days by calendar period, with one entry per period dated on its first day (the Monday of an ISO week,
+    /// the first of the month or January 1st)
+    /// 
+    /// Each entry holds the mean of the high temperatures, the lowest low temperature, the total precipitation, the highest
+    /// wind speed and precipitation chance, and the most common weather code of the period
+    /// # Examples
+    /// ```
+    /// use parser::{DataOps, Date, Period, ResamplePolicy, WeatherData};
+    /// let data = WeatherData::from_data("date: 2024-04-28 2024-04-29 2024-04-30
+    /// weather_code: 3 61 61
+    /// temperature_max: 60 50 52
+    /// temperature_min: 44.2 47.1 40.0
+    /// precipitation_sum: 0 0.2 0.1
+    /// wind_speed_max: 9.3 10.1 8.2
+    /// precipitation_probability_max: 45 100 20".to_string()).unwrap();
+    /// 
+    /// let weeks = data.resample(Period::Week, ResamplePolicy::SkipMissing);
+    /// assert_eq!(weeks.len(), 2);
+    /// let week = &weeks[&Date::from_string("2024-04-29").unwrap()];
+    /// assert_eq!(week.temp_max, Some(51.0));
+    /// assert_eq!(week.temp_min, Some(40.0));
+    /// // Only 2 of the 7 days of the week are there
+    /// let complete = data.resample(Period::Week, ResamplePolicy::RequireComplete);
+    /// assert_eq!(complete[1].temp_max, None);
+    /// ```
+    fn resample(&self, period: Period, policy: ResamplePolicy) -> WeatherDataMap;
+    /// Smooths every field over windows of `days` calendar days ending on each date, the way
+    /// [`stats::RollingOp::for_field`] says it's usually done (see [`stats::rolling`]). The weather code is left missing
+    fn rolling(&self, days: u16) -> WeatherDataMap;
+    /// Finds every run of consecutive calendar days meeting the threshold of an event, in date order
+    ///
+    /// A missing value or a gap in the dates ends a run, and heat waves shorter than
+    /// [`Thresholds::heat_wave_days`] are left out
+    /// # Examples
+    /// ```
+    /// use parser::{DataOps, Date, EventKind, Thresholds, WeatherData};
+    /// let data = WeatherData::from_data("date: 2024-07-01 2024-07-02 2024-07-03 2024-07-04 2024-07-05
+    /// weather_code: 0 0 0 61 0
+    /// temperature_max: 91 93 95 88 92
+    /// temperature_min: 70 72 74 70 71
+    /// precipitation_sum: 0 0 0 0.4 0
+    /// wind_speed_max: 9.3 10.1 8.2 30 12
+    /// precipitation_probability_max: 0 5 10 90 0".to_string()).unwrap();
+    ///
+    /// let waves = data.events(EventKind::HeatWave, &Thresholds::default());
+    /// assert_eq!(waves.len(), 1);
+    /// assert_eq!(waves[0].start, Date::from_string("2024-07-01").unwrap());
+    /// assert_eq!(waves[0].days, 3);
+    /// let dry: Vec<u32> = data.events(EventKind::DrySpell, &Thresholds::default()).iter().map(|event| event.days).collect();
+    /// assert_eq!(dry, vec![3, 1]);
+    /// ```
+    fn events(&self, kind: EventKind, thresholds: &Thresholds) -> Vec<Event>;
+    /// Flags the values of each day that are physically impossible for data recorded in the given units (a low above
+    /// the high, negative precipitation, a precipitation chance over 100%...) and, unless the test is None, the values
+    /// that are outliers among the rest of their field
+    ///
+    /// Only days with flags are returned, in the order of the map. Impossible values aren't tested for outliers,
+    /// and fields with fewer than 5 values aren't tested at all
+    /// # Examples
+    /// ```
+    /// use parser::{DataOps, DataPoint, Date, Implausible, Issue, OutlierTest, UnitSystem, WeatherData};
+    /// let data = WeatherData::from_data("date: 2024-04-24 2024-04-25 2024-04-26 2024-04-27 2024-04-28 2024-04-29
+    /// weather_code: 3 61 3 3 3 3
+    /// temperature_max: 61.9 52.6 54.9 53.1 56.0 55.2
+    /// temperature_min: 47.1 44.2 60.0 45.3 46.0 44.9
+    /// precipitation_sum: 0 0.22 0.1 -0.5 0 0
+    /// wind_speed_max: 9.3 10.1 8.2 9.0 9.5 60.0
+    /// precipitation_probability_max: 45 100 20 30 120 10".to_string()).unwrap();
+    ///
+    /// let flags = data.check_quality(UnitSystem::Imperial, Some(OutlierTest::Iqr{ k: 1.5 }));
+    /// let day = &flags[&Date::from_string("2024-04-26").unwrap()];
+    /// assert_eq!(day[0].field, DataPoint::TemperatureMin);
+    /// assert_eq!(day[0].issue, Issue::Implausible{ reason: Implausible::AboveTemperatureMax });
+    /// assert_eq!(flags[&Date::from_string("2024-04-27").unwrap()][0].issue, Issue::Implausible{ reason: Implausible::Negative });
+    /// assert_eq!(flags[&Date::from_string("2024-04-28").unwrap()][0].issue, Issue::Implausible{ reason: Implausible::TooHigh });
+    /// // Much windier than the other days
+    /// assert!(matches!(flags[&Date::from_string("2024-04-29").unwrap()][0].issue, Issue::Outlier{ test: "iqr", .. }));
+    /// ```
+    fn check_quality(&self, units: UnitSystem, test: Option<OutlierTest>) -> IndexMap<Date, Vec<QualityFlag>>;
+    /// Lists the runs of days missing between the first and last date of the data, in date order
+    fn gaps(&self) -> Vec<Gap>;
+    /// Returns a copy of the data sorted by date with every gap filled in, the way [`FillMethod::for_field`] says
+    /// each field is filled. Filled days are marked as [`synthetic`](WeatherData::synthetic)
+    /// # Examples
+    /// ```
+    /// use parser::{DataOps, Date, WeatherData};
+    /// let data = WeatherData::from_data("date: 2024-04-24 2024-04-27
+    /// weather_code: 3 61
+    /// temperature_max: 60 51
+    /// temperature_min: 47.1 44.2
+    /// precipitation_sum: 0.1 0.22
+    /// wind_speed_max: 9.3 10.1
+    /// precipitation_probability_max: 45 100".to_string()).unwrap();
+    ///
+    /// let gaps = data.gaps();
+    /// assert_eq!((gaps[0].start, gaps[0].days), (Date::from_string("2024-04-25").unwrap(), 2));
+    /// let filled = data.fill_gaps();
+    /// let day = &filled[&Date::from_string("2024-04-25").unwrap()];
+    /// assert!(day.synthetic);
+    /// assert_eq!(day.temp_max, Some(57.0));
+    /// assert_eq!(day.precip_sum, Some(0.0));
+    /// assert_eq!(day.weather_code.unwrap().code(), 3);
+    /// ```
+    fn fill_gaps(&self) -> WeatherDataMap;
+    /// Combines the days of another map with these ones, settling the values both have for the same date with the strategy
+    /// # Errors
+    /// With [`MergeStrategy::FailOnConflict`], returns every conflicting date if any value differs
+    /// # Examples
+    /// ```
+    /// use parser::{DataOps, DataPoint, Date, MergeStrategy, WeatherData};
+    /// let left = WeatherData::from_data("date: 2024-04-24 2024-04-25
+    /// weather_code: 3 61
+    /// temperature_max: 60 51
+    /// temperature_min: 47.1 44.2
+    /// precipitation_sum: 0.1 0.22
+    /// wind_speed_max: 9.3 10.1
+    /// precipitation_probability_max: 45 100".to_string()).unwrap();
+    /// let right = WeatherData::from_data("date: 2024-04-25 2024-04-26
+    /// weather_code: 61 3
+    /// temperature_max: 53 55
+    /// temperature_min: 44.2 40.0
+    /// precipitation_sum: 0.22 0
+    /// wind_speed_max: 10.1 8.2
+    /// precipitation_probability_max: 100 20".to_string()).unwrap();
+    ///
+    /// let conflicts = left.merge(&right, MergeStrategy::FailOnConflict).unwrap_err();
+    /// assert_eq!(conflicts[0].date, Date::from_string("2024-04-25").unwrap());
+    /// assert_eq!(conflicts[0].fields[0].field, DataPoint::TemperatureMax);
+    /// let merged = left.merge(&right, MergeStrategy::Average).unwrap();
+    /// assert_eq!((merged.added, merged.overlapping), (1, 1));
+    /// assert_eq!(merged.data[&Date::from_string("2024-04-25").unwrap()].temp_max, Some(52.0));
+    /// ```
+    fn merge(&self, other: &WeatherDataMap, strategy: MergeStrategy) -> Result<MergeReport, Vec<Conflict>>;
+    /// Writes the data as CSV with a header row, one row per date, and only the selected fields (all of them if empty)
+    /// Missing values are written as empty cells
+    fn to_csv(&self, points: &HashSet<DataPoint>, delimiter: u8) -> String;
+    /// Reads CSV with a header row naming the fields, in any order, and one row per date
+    /// Only the date column is required, empty cells, `-`, `null`, `NaN` and columns left out are read as missing values
+    ///
+    /// A file with a `time` column and no `date` column is an hourly export, read with [`HourlyData::from_csv`]
+    /// and summarized into days with [`HourlyData::daily`]
+    fn from_csv<R: std::io::Read>(reader: R, options: CsvOptions) -> Result<ParseReport, ParseDiagnostic> where Self: Sized;
 }
 
 impl DataOps for WeatherDataMap{
     fn take_range(&self, begin: &Date, end: &Date) -> WeatherDataMap{
-        let begin_index = match self.get_index_of(begin){
-            Some(index) => index,
-            None => {
-                let closest = self.keys().min_by(|a, b| Date::distance(a, begin).cmp(&Date::distance(b, begin))).unwrap();
-                self.get_index_of(closest).unwrap()
-            },
+        // Snaps both ends of the range to the closest date present in the map
+        let closest = |target: &Date| -> Option<Date>{
+            self.keys().copied().min_by_key(|date| (target.days_between(date).unsigned_abs(), *date))
         };
-        let end_index = match self.get_index_of(end){
-            Some(index) => index,
-            None => {
-                let closest = self.keys().min_by(|a, b| Date::distance(a, end).cmp(&Date::distance(b, end))).unwrap();
-                self.get_index_of(closest).unwrap()
-            },
+        let (begin, end) = match (closest(begin), closest(end)){
+            (Some(begin), Some(end)) => (begin, end),
+            _ => return IndexMap::new(),
         };
 
-        let mut map: WeatherDataMap = IndexMap::with_capacity(begin_index.abs_diff(end_index) as usize);
-        self.get_range(begin_index..=end_index).unwrap().iter().for_each(|(date, data)|{
-            map.insert(*date, data.clone());
-        });
-        map
+        self.iter()
+            .filter(|(date, _)| **date >= begin && **date <= end)
+            .map(|(date, data)| (*date, data.clone()))
+            .collect()
     }
-    fn json(self, mut options: HashSet<DataPoint>) -> String{
-        let mut json = String::from("[");
-        for (_, data) in self.iter(){
-            json.push_str(&data.json(&mut options));
-            json.push_str(",");
-        }
-        json.remove(json.rfind(',').unwrap());
-        json.push_str("]");
-        json
+    fn json(self, options: HashSet<DataPoint>) -> String{
+        let points: Vec<DataPoint> = if options.is_empty(){
+            DataPoint::ALL.to_vec()
+        } else {
+            DataPoint::ALL.into_iter().filter(|point| options.contains(point)).collect()
+        };
+        let views: Vec<PointsView> = self.values().map(|data| PointsView::new(data, &points)).collect();
+        serde_json::to_string(&views).expect("weather data is always serializable to JSON")
     }
     fn to_file(&self) -> String{
         let mut data_str = String::new();
@@ -76,60 +440,164 @@ impl DataOps for WeatherDataMap{
             data_str.push_str(&date.to_string());
             data_str.push(' ');
         }
-        data_str.push_str("\nweather_code:");
-        for (_, data) in self.iter(){
-            data_str.push_str(&data.weather_code.to_string());
-            data_str.push(' ');
-        }
-        data_str.push_str("\ntemperature_max:");
-        for (_, data) in self.iter(){
-            data_str.push_str(&data.temp_max.to_string());
-            data_str.push(' ');
-        }
-        data_str.push_str("\ntemperature_min:");
-        for (_, data) in self.iter(){
-            data_str.push_str(&data.temp_min.to_string());
-            data_str.push(' ');
-        }
-        data_str.push_str("\nprecipitation_sum:");
-        for (_, data) in self.iter(){
-            data_str.push_str(&data.precip_sum.to_string());
-            data_str.push(' ');
-        }
-        data_str.push_str("\nwind_speed_max:");
-        for (_, data) in self.iter(){
-            data_str.push_str(&data.max_wind.to_string());
-            data_str.push(' ');
-        }
-        data_str.push_str("\nprecipitation_probability_max:");
-        for (_, data) in self.iter(){
-            data_str.push_str(&data.precip_prob_max.to_string());
-            data_str.push(' ');
+        for point in DataPoint::ALL{
+            data_str.push('\n');
+            data_str.push_str(point.name());
+            data_str.push(':');
+            for (_, data) in self.iter(){
+                match data.value(point){
+                    Some(value) => data_str.push_str(&value.to_string()),
+                    None => data_str.push_str(MISSING_VALUE),
+                }
+                data_str.push(' ');
+            }
         }
         data_str
 
     }
+    fn to_file_in(&self, units: UnitSystem) -> String{
+        format!("units: {}\n{}", units, self.to_file())
+    }
+    fn convert_units(&self, from: UnitSystem, to: UnitSystem) -> WeatherDataMap{
+        units::convert_map(self, from, to)
+    }
+    fn resample(&self, period: Period, policy: ResamplePolicy) -> WeatherDataMap{
+        resample::resample(self, period, policy)
+    }
+    fn rolling(&self, days: u16) -> WeatherDataMap{
+        let mut smoothed: WeatherDataMap = self.keys().map(|date| (*date, WeatherData::empty(*date))).collect();
+        for point in DataPoint::ALL{
+            let op = match stats::RollingOp::for_field(point){
+                Some(op) => op,
+                None => continue,
+            };
+            for (date, value) in stats::rolling(self, point, days, op){
+                smoothed[&date].set_value(point, value);
+            }
+        }
+        smoothed
+    }
+    fn events(&self, kind: EventKind, thresholds: &Thresholds) -> Vec<Event>{
+        events::find(self, kind, thresholds)
+    }
+    fn check_quality(&self, units: UnitSystem, test: Option<OutlierTest>) -> IndexMap<Date, Vec<QualityFlag>>{
+        quality::check(self, units, test)
+    }
+    fn gaps(&self) -> Vec<Gap>{
+        gaps::find(self)
+    }
+    fn fill_gaps(&self) -> WeatherDataMap{
+        gaps::fill(self)
+    }
+    fn merge(&self, other: &WeatherDataMap, strategy: MergeStrategy) -> Result<MergeReport, Vec<Conflict>>{
+        merge::merge(self, other, strategy)
+    }
+    fn to_csv(&self, points: &HashSet<DataPoint>, delimiter: u8) -> String{
+        csv_format::write(self, points, delimiter)
+    }
+    fn from_csv<R: std::io::Read>(reader: R, options: CsvOptions) -> Result<ParseReport, ParseDiagnostic>{
+        csv_format::read(reader, options)
+    }
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Hash, Eq, PartialOrd, Ord)]
-/// Struct representing a date 
+/// Struct representing a date in the proleptic Gregorian calendar
+/// 
+/// A `Date` can only be constructed through [`Date::new`] or [`Date::from_string`], both of
+/// which reject dates that don't exist on the calendar (e.g. "2023-02-29" or "2024-13-45")
 pub struct Date{
     year: u32,
     month: u8,
     day: u8,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+/// Day of the week, starting on Monday as in ISO 8601
+pub enum Weekday{
+    Monday,
+    Tuesday,
+    Wednesday,
+    Thursday,
+    Friday,
+    Saturday,
+    Sunday,
+}
+
+impl Weekday{
+    /// Returns the ISO 8601 number of the weekday (Monday = 1, Sunday = 7)
+    pub fn number_from_monday(&self) -> u8{
+        *self as u8 + 1
+    }
+}
+
+impl std::fmt::Display for Weekday{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result{
+        let name = match self{
+            Weekday::Monday => "Monday",
+            Weekday::Tuesday => "Tuesday",
+            Weekday::Wednesday => "Wednesday",
+            Weekday::Thursday => "Thursday",
+            Weekday::Friday => "Friday",
+            Weekday::Saturday => "Saturday",
+            Weekday::Sunday => "Sunday",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Returns true if the given year is a leap year in the Gregorian calendar
+pub fn is_leap_year(year: u32) -> bool{
+    (year.is_multiple_of(4) && !year.is_multiple_of(100)) || year.is_multiple_of(400)
+}
+
+/// Returns the number of days in the given month (1-12) of the given year, or 0 if the month is invalid
+pub fn days_in_month(year: u32, month: u8) -> u8{
+    match month{
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+        4 | 6 | 9 | 11 => 30,
+        2 => if is_leap_year(year) { 29 } else { 28 },
+        _ => 0,
+    }
+}
+
 impl Date{
+    /// Creates a new Date object from its parts, checking that the date exists
+    /// # Errors
+    /// Returns an error if the year is not between 1 and 9999, the month is not between 1 and 12, or the day
+    /// is not a valid day of that month (leap years are accounted for)
+    /// # Examples
+    /// ```
+    /// use parser::Date;
+    /// assert!(Date::new(2024, 2, 29).is_ok());
+    /// assert!(Date::new(2023, 2, 29).is_err());
+    /// ```
+    pub fn new(year: u32, month: u8, day: u8) -> ParseResult<Date>{
+        if !(1..=9999).contains(&year){
+            return Err(ParseError::InvalidDate(format!("Invalid year: {}", year)));
+        }
+        if !(1..=12).contains(&month){
+            return Err(ParseError::InvalidDate(format!("Invalid month: {}", month)));
+        }
+        if day == 0 || day > days_in_month(year, month){
+            return Err(ParseError::InvalidDate(format!("Invalid day: {} (month {} of {} has {} days)", day, month, year, days_in_month(year, month))));
+        }
+        Ok(Date{
+            year,
+            month,
+            day,
+        })
+    }
+
     /// Creates a new Date object from string formatted as "YYYY-MM-DD"
     /// # Errors
-    /// Returns an error if the date is not formatted correctly
+    /// Returns an error if the date is not formatted correctly or does not exist
     /// # Examples
     /// ```
     /// use parser::Date;
     /// let date = Date::from_string("2021-01-01").unwrap();
-    /// assert_eq!(date.year, 2021);
-    /// assert_eq!(date.month, 1);
-    /// assert_eq!(date.day, 1);
+    /// assert_eq!(date.year(), 2021);
+    /// assert_eq!(date.month(), 1);
+    /// assert_eq!(date.day(), 1);
     ///
     /// ```
     /// 
@@ -138,10 +606,12 @@ impl Date{
     /// use parser::Date;
     /// let date = Date::from_string("01-01-21");
     /// assert!(date.is_err());
+    /// assert!(Date::from_string("2024-13-45").is_err());
+    /// assert!(Date::from_string("0-0-0").is_err());
     /// ```
     pub fn from_string(date: &str) -> ParseResult<Date>{
         let parts: Vec<&str> = date.split('-').collect();
-        if parts.len() != 3{
+        if parts.len() != 3 || parts[0].len() != 4 || parts[1].len() > 2 || parts[2].len() > 2{
             return Err(ParseError::InvalidDate(format!("Invalid date: {}", date))); 
         }
         let year = match parts[0].parse(){
@@ -156,239 +626,489 @@ impl Date{
             Ok(day) => day,
             Err(_) => return Err(ParseError::InvalidDate(format!("Invalid day: {}", parts[2]))),
         };
-        Ok(Date{
-            year,
-            month,
-            day,
+        Date::new(year, month, day)
+    }
+    pub fn year(&self) -> u32{
+        self.year
+    }
+    pub fn month(&self) -> u8{
+        self.month
+    }
+    pub fn day(&self) -> u8{
+        self.day
+    }
+
+    /// Number of days since 1970-01-01 (negative for earlier dates)
+    fn days_since_epoch(&self) -> i64{
+        // Shifts the year to start in March so the leap day is the last day of the year
+        let year = if self.month <= 2 { self.year as i64 - 1 } else { self.year as i64 };
+        let era = year.div_euclid(400);
+        let year_of_era = year.rem_euclid(400);
+        let month = (self.month as i64 + 9) % 12;
+        let day_of_year = (153 * month + 2) / 5 + self.day as i64 - 1;
+        let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+        era * 146097 + day_of_era - 719468
+    }
+
+    /// Inverse of `days_since_epoch`, returns None if the resulting year is out of range
+    fn from_days_since_epoch(days: i64) -> Option<Date>{
+        let days = days.checked_add(719468)?;
+        let era = days.div_euclid(146097);
+        let day_of_era = days.rem_euclid(146097);
+        let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let month = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * month + 2) / 5 + 1;
+        let month = if month < 10 { month + 3 } else { month - 9 };
+        let year = era * 400 + year_of_era + if month <= 2 { 1 } else { 0 };
+        if !(1..=9999).contains(&year){
+            return None;
+        }
+        Some(Date{
+            year: year as u32,
+            month: month as u8,
+            day: day as u8,
         })
     }
-    pub fn to_string(&self) -> String{
-        format!("{}-{}-{}", self.year, self.month, self.day)
+
+    /// Returns the signed number of days from `self` to `other` (positive if `other` is later)
+    /// # Examples
+    /// ```
+    /// use parser::Date;
+    /// let begin = Date::from_string("2024-02-28").unwrap();
+    /// let end = Date::from_string("2024-03-01").unwrap();
+    /// assert_eq!(begin.days_between(&end), 2);
+    /// assert_eq!(end.days_between(&begin), -2);
+    /// ```
+    pub fn days_between(&self, other: &Date) -> i64{
+        other.days_since_epoch() - self.days_since_epoch()
+    }
+
+    /// Returns the date `days` days after this one (or before, if negative)
+    /// Returns None if the result would fall outside of years 1 to 9999
+    pub fn add_days(&self, days: i64) -> Option<Date>{
+        Date::from_days_since_epoch(self.days_since_epoch().checked_add(days)?)
+    }
+
+    /// Returns the following day
+    pub fn succ(&self) -> Option<Date>{
+        self.add_days(1)
+    }
+
+    /// Returns the previous day
+    pub fn pred(&self) -> Option<Date>{
+        self.add_days(-1)
+    }
+
+    /// Returns the day of the week
+    pub fn weekday(&self) -> Weekday{
+        // 1970-01-01 was a Thursday
+        match (self.days_since_epoch() + 3).rem_euclid(7){
+            0 => Weekday::Monday,
+            1 => Weekday::Tuesday,
+            2 => Weekday::Wednesday,
+            3 => Weekday::Thursday,
+            4 => Weekday::Friday,
+            5 => Weekday::Saturday,
+            _ => Weekday::Sunday,
+        }
+    }
+
+    /// Returns the ordinal day of the year, starting at 1 for January 1st
+    pub fn day_of_year(&self) -> u16{
+        (1..self.month).map(|month| days_in_month(self.year, month) as u16).sum::<u16>() + self.day as u16
     }
+
+    /// Returns the ISO 8601 week-numbering year and week (1 to 53) the date falls in
+    /// # Examples
+    /// ```
+    /// use parser::Date;
+    /// assert_eq!(Date::new(2024, 4, 24).unwrap().iso_week(), (2024, 17));
+    /// // Belongs to the last week of the previous year
+    /// assert_eq!(Date::new(2021, 1, 3).unwrap().iso_week(), (2020, 53));
+    /// ```
+    pub fn iso_week(&self) -> (u32, u8){
+        // The week belongs to the year its Thursday falls in
+        let thursday = self.add_days(4 - self.weekday().number_from_monday() as i64).unwrap_or(*self);
+        (thursday.year, ((thursday.day_of_year() - 1) / 7 + 1) as u8)
+    }
+
+    /// Returns the absolute number of days between two dates
     pub fn distance(begin: &Date, end: &Date) -> u32{
-        let tot_days_1 = begin.year * 365 + begin.month as u32 * 30 + begin.day as u32;
-        let tot_days_2 = end.year * 365 + end.month as u32 * 30 + end.day as u32;
-        tot_days_2.abs_diff(tot_days_1)
-        
+        u32::try_from(begin.days_between(end).unsigned_abs()).unwrap_or(u32::MAX)
     }
 }
 
-#[inline]
-fn parse_date(data: &str) -> ParseResult<Vec<Date>>{
-    let split: Vec<&str> = data.split_whitespace().collect();
-    let mut dates: Vec<Date> = Vec::with_capacity(split.len());
-    for date in split{
-        match Date::from_string(date){
-            Ok(date) => if dates.contains(&date) {
-                return Err(ParseError::DuplicateDate(date));
-            } else {dates.push(date);},
-            Err(e) => return Err(e),
-        }
+impl std::fmt::Display for Date{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result{
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)
     }
-    Ok(dates)
 }
 
-#[inline]
-fn parse_weather_code(data: &str) -> ParseResult<Vec<u8>> {
-    let split: Vec<&str> = data.split_whitespace().collect();
-    let mut codes: Vec<u8> = Vec::with_capacity(split.len());
-    for code in split {
-        codes.push(match code.parse::<f64>() {
-            Ok(code) => {
-                if code < 0.0 || code > u8::MAX as f64 {
-                    return Err(ParseError::InvalidWeatherCode(code.to_string()));
-                }
-                code as u8 // Truncate the decimal part
-            },
-            Err(_) => return Err(ParseError::InvalidWeatherCode(code.to_string())),
-        });
+impl Serialize for Date{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error>{
+        serializer.collect_str(self)
     }
-    Ok(codes)
 }
 
-#[inline]
-fn parse_temp_max(data: &str) -> ParseResult<Vec<f32>>{
-    let split: Vec<&str> = data.split_whitespace().collect();
-    let mut temps: Vec<f32> = Vec::with_capacity(split.len());
-    for temp in split{
-        temps.push(match temp.parse(){
-            Ok(temp) => temp,
-            Err(_) => return Err(ParseError::InvalidTemperature(temp.to_string())),
-        });
+impl<'de> Deserialize<'de> for Date{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error>{
+        let date = <std::borrow::Cow<str>>::deserialize(deserializer)?;
+        Date::from_string(&date).map_err(D::Error::custom)
     }
-    Ok(temps)
 }
 
-#[inline]
-fn parse_temp_min(data: &str) -> ParseResult<Vec<f32>>{
-    let split: Vec<&str> = data.split_whitespace().collect();
-    let mut temps: Vec<f32> = Vec::with_capacity(split.len());
-    for temp in split{
-        temps.push(match temp.parse(){
-            Ok(temp) => temp,
-            Err(_) => return Err(ParseError::InvalidTemperature(temp.to_string())),
-        });
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+/// How the text format parser reacts to problems in the data
+pub enum ParseMode{
+    /// Stops at the first problem and returns it as an error
+    #[default]
+    Strict,
+    /// Skips whatever can't be parsed, keeping every day whose values could all be read and collecting all problems found
+    /// A field with no line at all is reported, and read as missing for every day
+    Lenient,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+/// Options for [`WeatherData::from_data_with`] and [`WeatherData::from_reader_with`]
+pub struct ParseOptions{
+    pub mode: ParseMode,
+}
+
+#[derive(Debug, Clone)]
+/// Days that were parsed successfully, along with every problem that was skipped over to get them
+pub struct ParseReport{
+    pub data: WeatherDataMap,
+    pub problems: Vec<ParseDiagnostic>,
+    /// Units the data says it's recorded in, None if it doesn't say
+    pub units: Option<UnitSystem>,
+}
+
+/// Collects problems found while parsing, returning the first one as an error in strict mode
+struct Problems{
+    mode: ParseMode,
+    found: Vec<ParseDiagnostic>,
+}
+
+impl Problems{
+    fn report(&mut self, problem: ParseDiagnostic) -> Result<(), ParseDiagnostic>{
+        match self.mode{
+            ParseMode::Strict => Err(problem),
+            ParseMode::Lenient => {
+                self.found.push(problem);
+                Ok(())
+            }
+        }
     }
-    Ok(temps)
 }
 
-#[inline]
-fn parse_precip_sum(data: &str) -> ParseResult<Vec<f32>>{
-    let split: Vec<&str> = data.split_whitespace().collect();
-    let mut sums: Vec<f32> = Vec::with_capacity(split.len());
-    for sum in split{
-        sums.push(match sum.parse(){
-            Ok(sum) => sum,
-            Err(_) => return Err(ParseError::InvalidPrecipitation(sum.to_string())),
+/// A line of the column text format that has been split into its field and values, but not parsed yet
+struct RawLine{
+    number: usize,
+    field: DataPoint,
+    values: String,
+}
+
+/// Splits the input into its lines, checking that every field appears exactly once, along with the optional `units:` line
+/// In lenient mode, invalid lines are skipped and only the first line of a repeated field is kept
+fn read_lines<R: BufRead>(reader: R, problems: &mut Problems) -> Result<(Vec<RawLine>, Option<UnitSystem>), ParseDiagnostic>{
+    let mut lines: Vec<RawLine> = Vec::with_capacity(7);
+    let mut units: Option<UnitSystem> = None;
+    for (i, line) in reader.lines().enumerate(){
+        let number = i + 1;
+        let line = match line{
+            Ok(line) => line,
+            Err(e) => {
+                // Nothing after a read error can be trusted, so this always stops reading
+                problems.report(ParseDiagnostic::on_line(ParseError::Io(e.to_string()), number, None))?;
+                break;
+            }
+        };
+        if line.trim().is_empty(){
+            continue;
+        }
+        if let Some(("units", name)) = line.split_once(':').map(|(field, name)| (field.trim(), name.trim())){
+            match UnitSystem::from_name(name){
+                Some(_) if units.is_some() => problems.report(ParseDiagnostic::on_line(ParseError::InvalidLine(line.clone()), number, None))?,
+                Some(system) => units = Some(system),
+                None => problems.report(ParseDiagnostic::on_line(ParseError::InvalidUnits(name.to_string()), number, None))?,
+            }
+            continue;
+        }
+        let parsed = line.split_once(':').and_then(|(name, values)| Some((DataPoint::from_name(name.trim())?, values)));
+        let (field, values) = match parsed{
+            Some(parsed) => parsed,
+            None => {
+                problems.report(ParseDiagnostic::on_line(ParseError::InvalidLine(line.clone()), number, None))?;
+                continue;
+            }
+        };
+        if lines.iter().any(|line| line.field == field){
+            problems.report(ParseDiagnostic::on_line(ParseError::DuplicateField(field), number, Some(field)))?;
+            continue;
+        }
+        lines.push(RawLine{
+            number,
+            field,
+            values: values.to_string(),
         });
     }
-    Ok(sums)
-    
+    for field in [DataPoint::Date].iter().chain(DataPoint::ALL.iter()){
+        if !lines.iter().any(|line| line.field == *field){
+            problems.report(ParseDiagnostic::from(ParseError::MissingField(*field)))?;
+        }
+    }
+    Ok((lines, units))
 }
 
+/// Parses the dates on the date line, one per column
+/// Columns whose date is invalid or repeats an earlier date are left as None
 #[inline]
-fn parse_wind_max(data: &str) -> ParseResult<Vec<f32>>{
-    let split: Vec<&str> = data.split_whitespace().collect();
-    let mut winds: Vec<f32> = Vec::with_capacity(split.len());
-    for wind in split{
-        winds.push(match wind.parse(){
-            Ok(wind) => wind,
-            Err(_) => return Err(ParseError::InvalidWind(wind.to_string())),
-        });
+fn parse_date(line: Option<&RawLine>, problems: &mut Problems) -> Result<Vec<Option<Date>>, ParseDiagnostic>{
+    let line = match line{
+        Some(line) => line,
+        None => return Ok(vec![]),
+    };
+    let split: Vec<&str> = line.values.split_whitespace().collect();
+    let mut dates: Vec<Option<Date>> = Vec::with_capacity(split.len());
+    for (i, token) in split.into_iter().enumerate(){
+        match Date::from_string(token){
+            Ok(date) => if dates.contains(&Some(date)) {
+                problems.report(ParseDiagnostic::at_value(ParseError::DuplicateDate(date), line, i, Some(token), Some(date)))?;
+                dates.push(None);
+            } else {dates.push(Some(date));},
+            Err(e) => {
+                problems.report(ParseDiagnostic::at_value(e, line, i, Some(token), None))?;
+                dates.push(None);
+            }
+        }
     }
-    Ok(winds)
+    Ok(dates)
+}
 
+#[inline]
+fn parse_weather_code(code: &str) -> Option<WeatherCode>{
+    code.parse::<f32>().ok().and_then(WeatherCode::from_value)
 }
 
+/// Token written for a missing value in the text format
+pub const MISSING_VALUE: &str = "-";
+
+/// Returns true if a token in the text format marks a missing value (`-`, `null` or `NaN`)
 #[inline]
-fn parse_prob_max(data: &str) -> ParseResult<Vec<f32>>{
-    let split: Vec<&str> = data.split_whitespace().collect();
-    let mut probs: Vec<f32> = Vec::with_capacity(split.len());
-    for prob in split{
-        probs.push(match prob.parse(){
-            Ok(prob) => prob,
-            Err(_) => return Err(ParseError::InvalidPrecipitationProbability(prob.to_string())),
-        });
+fn is_missing(token: &str) -> bool{
+    token == MISSING_VALUE || token.eq_ignore_ascii_case("null") || token.eq_ignore_ascii_case("nan")
+}
+
+/// Returns the error for a value of the given field that can't be parsed
+fn invalid_value(field: DataPoint) -> fn(String) -> ParseError{
+    match field{
+        DataPoint::WeatherCode => ParseError::InvalidWeatherCode,
+        DataPoint::TemperatureMax | DataPoint::TemperatureMin => ParseError::InvalidTemperature,
+        DataPoint::PrecipitationSum => ParseError::InvalidPrecipitation,
+        DataPoint::WindSpeedMax => ParseError::InvalidWind,
+        DataPoint::PrecipitationProbabilityMax => ParseError::InvalidPrecipitationProbability,
+        DataPoint::Date => ParseError::InvalidDate,
     }
-    Ok(probs)
 }
 
-#[derive(Debug, Clone)]
+/// Parses every value on a line, checking that there is exactly one value per date column
+/// Each value is None if it couldn't be parsed, or Some(None) if it's marked as missing
+/// A field without a line is read as missing for every date
+fn parse_values<T>(line: Option<&RawLine>, dates: &[Option<Date>], parse: fn(&str) -> Option<T>, problems: &mut Problems) -> Result<Vec<Option<Option<T>>>, ParseDiagnostic>{
+    let line = match line{
+        Some(line) => line,
+        None => return Ok(dates.iter().map(|_| Some(None)).collect()),
+    };
+    let invalid = invalid_value(line.field);
+    let mut values: Vec<Option<Option<T>>> = Vec::with_capacity(dates.len());
+    for (i, token) in line.values.split_whitespace().enumerate(){
+        let date = match dates.get(i){
+            Some(date) => *date,
+            None => {
+                // Only the first extra value is reported, the rest of the line is ignored
+                problems.report(ParseDiagnostic::at_value(ParseError::TooManyValues, line, i, Some(token), None))?;
+                break;
+            }
+        };
+        if is_missing(token){
+            values.push(Some(None));
+            continue;
+        }
+        match parse(token){
+            Some(value) => values.push(Some(Some(value))),
+            None => {
+                problems.report(ParseDiagnostic::at_value(invalid(token.to_string()), line, i, Some(token), date))?;
+                values.push(None);
+            }
+        }
+    }
+    if values.len() < dates.len(){
+        problems.report(ParseDiagnostic::at_value(ParseError::TooFewValues, line, values.len(), None, dates[values.len()]))?;
+        values.resize_with(dates.len(), || None);
+    }
+    Ok(values)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 /// Struct representing a single weather data entry
+/// 
+/// Serializes with the same field names used by the text format (`temperature_max`, `wind_speed_max`, ...)
+/// 
+/// Every value can be missing (`None`), which is written as `-` in the text format and `null` in JSON.
+/// A field left out of a record is read as missing as well.
 pub struct WeatherData{
     pub date: Date,
-    pub weather_code: u8,
-    pub temp_max: f32,
-    pub temp_min: f32,
-    pub precip_sum: f32,
-    pub max_wind: f32,
-    pub precip_prob_max: f32,
+    pub weather_code: Option<WeatherCode>,
+    #[serde(rename = "temperature_max")]
+    pub temp_max: Option<f32>,
+    #[serde(rename = "temperature_min")]
+    pub temp_min: Option<f32>,
+    #[serde(rename = "precipitation_sum")]
+    pub precip_sum: Option<f32>,
+    #[serde(rename = "wind_speed_max")]
+    pub max_wind: Option<f32>,
+    #[serde(rename = "precipitation_probability_max")]
+    pub precip_prob_max: Option<f32>,
+    /// True for days made up by [`DataOps::fill_gaps`] rather than recorded, only serialized when true
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub synthetic: bool,
 }
 impl WeatherData{
     
-    /// Creates a new WeatherData object from given parameters
-    pub fn new(date: Date, weather_code: u8, temp_max: f32, temp_min: f32, precip_sum: f32, max_wind: f32, precip_prob_max: f32) -> WeatherData{
+    /// Creates a new WeatherData object from given parameters, with every value present
+    pub fn new(date: Date, weather_code: WeatherCode, temp_max: f32, temp_min: f32, precip_sum: f32, max_wind: f32, precip_prob_max: f32) -> WeatherData{
         WeatherData{
             date,
-            weather_code,
-            temp_max,
-            temp_min,
-            precip_sum,
-            max_wind,
-            precip_prob_max,
+            weather_code: Some(weather_code),
+            temp_max: Some(temp_max),
+            temp_min: Some(temp_min),
+            precip_sum: Some(precip_sum),
+            max_wind: Some(max_wind),
+            precip_prob_max: Some(precip_prob_max),
+            synthetic: false,
         }
     }
-    
-    
-    pub fn from_data(data: String) -> ParseResult<WeatherDataMap>{
-
-        let mut dates: Vec<Date> = vec![];
-        let mut weather_codes: Vec<u8> = vec![];
-        let mut temp_maxs: Vec<f32> = vec![];
-        let mut temp_mins: Vec<f32> = vec![];
-        let mut precip_sums: Vec<f32> = vec![];
-        let mut wind_maxs: Vec<f32> = vec![];
-        let mut prob_maxs: Vec<f32> = vec![];
-
-        let mut line_count: u8 = 0;
-        let mut lines = data.lines();
-        while let Some(line) = lines.next(){
-            let parts: Vec<&str> = line.split(':').collect();
-            if parts.len() != 2{
-                return Err(ParseError::InvalidLine(line.to_string()));
-            }
-            match parts[0].trim(){
-                "date" => dates = match parse_date(parts[1]){
-                    Ok(dates) => dates,
-                    Err(e) => return Err(e),
-                },
-                "weather_code" => weather_codes = match parse_weather_code(parts[1]){
-                    Ok(codes) => codes,
-                    Err(e) => return Err(e),
-                },
-                "temperature_max" => temp_maxs = match parse_temp_max(parts[1]){
-                    Ok(temps) => temps,
-                    Err(e) => return Err(e),
-                },
-                "temperature_min" => temp_mins = match parse_temp_min(parts[1]){
-                    Ok(temps) => temps,
-                    Err(e) => return Err(e),
-                },
-                "precipitation_sum" => precip_sums = match parse_precip_sum(parts[1]){
-                    Ok(sums) => sums,
-                    Err(e) => return Err(e),
-                },
-                "wind_speed_max" => wind_maxs = match parse_wind_max(parts[1]){
-                    Ok(winds) => winds,
-                    Err(e) => return Err(e),
-                },
-                "precipitation_probability_max" => prob_maxs = match parse_prob_max(parts[1]){
-                    Ok(probs) => probs,
-                    Err(e) => return Err(e),
-                },
-                _ => return Err(ParseError::InvalidLine(line.to_string())),
 
-            }
+    /// Creates an entry for the given date with every value missing
+    pub fn empty(date: Date) -> WeatherData{
+        WeatherData{
+            date,
+            weather_code: None,
+            temp_max: None,
+            temp_min: None,
+            precip_sum: None,
+            max_wind: None,
+            precip_prob_max: None,
+            synthetic: false,
+        }
+    }
 
-            line_count += 1;
+    /// Sets the value of a field from a number, weather codes are truncated to a whole number
+    /// and set as missing if they aren't defined (see [`WeatherCode::from_value`]). Setting the date does nothing
+    pub fn set_value(&mut self, point: DataPoint, value: Option<f32>){
+        match point{
+            DataPoint::WeatherCode => self.weather_code = value.and_then(WeatherCode::from_value),
+            DataPoint::TemperatureMax => self.temp_max = value,
+            DataPoint::TemperatureMin => self.temp_min = value,
+            DataPoint::PrecipitationSum => self.precip_sum = value,
+            DataPoint::WindSpeedMax => self.max_wind = value,
+            DataPoint::PrecipitationProbabilityMax => self.precip_prob_max = value,
+            DataPoint::Date => (),
         }
+    }
 
-        if line_count != 7 {
-            return Err(ParseError::InvalidLine("Invalid number of lines".to_string()));
+    /// Returns the value of a field as a number, or None if it's missing (or the field is the date)
+    pub fn value(&self, point: DataPoint) -> Option<f32>{
+        match point{
+            DataPoint::WeatherCode => self.weather_code.map(f32::from),
+            DataPoint::TemperatureMax => self.temp_max,
+            DataPoint::TemperatureMin => self.temp_min,
+            DataPoint::PrecipitationSum => self.precip_sum,
+            DataPoint::WindSpeedMax => self.max_wind,
+            DataPoint::PrecipitationProbabilityMax => self.precip_prob_max,
+            DataPoint::Date => None,
         }
+    }
+    
+    
+    /// Parses weather data in the column text format, where each line is a field name followed by
+    /// one value per date (see [`DataOps::to_file`]). Missing values can be written as `-`, `null` or `NaN`
+    /// 
+    /// An optional `units: metric` or `units: imperial` line records the units the values are in, see [`ParseReport::units`]
+    /// # Errors
+    /// Returns a [`ParseDiagnostic`] pointing at the first problem found in the data
+    pub fn from_data(data: String) -> Result<WeatherDataMap, ParseDiagnostic>{
+        WeatherData::from_reader(data.as_bytes())
+    }
+
+    /// Same as [`WeatherData::from_data`], but reads the data line by line from any buffered reader
+    pub fn from_reader<R: BufRead>(reader: R) -> Result<WeatherDataMap, ParseDiagnostic>{
+        Ok(WeatherData::from_reader_with(reader, ParseOptions::default())?.data)
+    }
+
+    /// Parses weather data in the column text format with the given options
+    /// 
+    /// In [`ParseMode::Lenient`] this never fails: every day with a valid date and no invalid values is kept,
+    /// and everything else is listed in [`ParseReport::problems`]
+    /// # Errors
+    /// In [`ParseMode::Strict`], returns the first problem found in the data
+    /// # Examples
+    /// ```
+    /// use parser::{ParseMode, ParseOptions, WeatherData};
+    /// let data = "date: 2024-04-25 2024-04-24 2024-04-24
+    /// weather_code: 3 61 3
+    /// temperature_max: 54.9 abc 50.1
+    /// temperature_min: 44.2 47.1 40.0
+    /// precipitation_sum: 0 0.22 0
+    /// wind_speed_max: 9.3 10.1 8.2
+    /// precipitation_probability_max: 45 100 20".to_string();
+    /// 
+    /// assert!(WeatherData::from_data(data.clone()).is_err());
+    /// let report = WeatherData::from_data_with(data, ParseOptions{ mode: ParseMode::Lenient }).unwrap();
+    /// assert_eq!(report.data.len(), 1);
+    /// assert_eq!(report.problems.len(), 2);
+    /// ```
+    pub fn from_data_with(data: String, options: ParseOptions) -> Result<ParseReport, ParseDiagnostic>{
+        WeatherData::from_reader_with(data.as_bytes(), options)
+    }
+
+    /// Same as [`WeatherData::from_data_with`], but reads the data line by line from any buffered reader
+    pub fn from_reader_with<R: BufRead>(reader: R, options: ParseOptions) -> Result<ParseReport, ParseDiagnostic>{
+        let mut problems = Problems{
+            mode: options.mode,
+            found: vec![],
+        };
+        let (lines, units) = read_lines(reader, &mut problems)?;
+        let line = |field: DataPoint| lines.iter().find(|line| line.field == field);
+
+        let dates = parse_date(line(DataPoint::Date), &mut problems)?;
+        let weather_codes = parse_values(line(DataPoint::WeatherCode), &dates, parse_weather_code, &mut problems)?;
+        let temp_maxs = parse_values(line(DataPoint::TemperatureMax), &dates, |value| value.parse().ok(), &mut problems)?;
+        let temp_mins = parse_values(line(DataPoint::TemperatureMin), &dates, |value| value.parse().ok(), &mut problems)?;
+        let precip_sums = parse_values(line(DataPoint::PrecipitationSum), &dates, |value| value.parse().ok(), &mut problems)?;
+        let wind_maxs = parse_values(line(DataPoint::WindSpeedMax), &dates, |value| value.parse().ok(), &mut problems)?;
+        let prob_maxs = parse_values(line(DataPoint::PrecipitationProbabilityMax), &dates, |value| value.parse().ok(), &mut problems)?;
 
-        let mut i: usize = 0;
         let mut weather_data: Vec<WeatherData> = Vec::with_capacity(dates.len());
-        while let Some(date) = dates.get(i){
-            let weather_code = match weather_codes.get(i){
-                Some(code) => code,
-                None => return Err(ParseError::TooManyValues),
-            };
-            let temp_max = match temp_maxs.get(i){
-                Some(temp) => temp,
-                None => return Err(ParseError::TooManyValues),
-            };
-            let temp_min = match temp_mins.get(i){
-                Some(temp) => temp,
-                None => return Err(ParseError::TooManyValues),
-            };
-            let precip_sum = match precip_sums.get(i){
-                Some(sum) => sum,
-                None => return Err(ParseError::TooManyValues),
-            };
-            let wind_max = match wind_maxs.get(i){
-                Some(wind) => wind,
-                None => return Err(ParseError::TooManyValues),
-            };
-            let precip_prob_max = match prob_maxs.get(i){
-                Some(prob) => prob,
-                None => return Err(ParseError::TooManyValues),
-            };
-            weather_data.push(WeatherData::new(*date, *weather_code, *temp_max, *temp_min, *precip_sum, *wind_max, *precip_prob_max));
-            i += 1;
+        for (i, date) in dates.into_iter().enumerate(){
+            // Only days without invalid values are kept, strict mode has already returned if there are any
+            if let (Some(date), Some(weather_code), Some(temp_max), Some(temp_min), Some(precip_sum), Some(max_wind), Some(precip_prob_max)) =
+                (date, weather_codes[i], temp_maxs[i], temp_mins[i], precip_sums[i], wind_maxs[i], prob_maxs[i]){
+                weather_data.push(WeatherData{
+                    date,
+                    weather_code,
+                    temp_max,
+                    temp_min,
+                    precip_sum,
+                    max_wind,
+                    precip_prob_max,
+                    synthetic: false,
+                });
+            }
         }
 
-        weather_data.sort_by(|a, b|comp_date(a, b));
+        weather_data.sort_by_key(|data| data.date);
 
         let mut weather_data_map: IndexMap<Date, WeatherData> = IndexMap::with_capacity(weather_data.len());
 
@@ -396,48 +1116,226 @@ impl WeatherData{
             weather_data_map.insert(data.date, data);
         }
         
-        Ok(weather_data_map)
+        Ok(ParseReport{
+            data: weather_data_map,
+            problems: problems.found,
+            units,
+        })
     }
 
-    fn json(&self, points: &mut HashSet<DataPoint>) -> String{
-        if points.is_empty(){
-            *points = vec![DataPoint::WeatherCode, DataPoint::TemperatureMax, DataPoint::TemperatureMin, DataPoint::PrecipitationSum, DataPoint::WindSpeedMax, DataPoint::PrecipitationProbabilityMax].into_iter().collect();
-        }
-        let mut json = String::from("{");
-        json.push_str(&format!("\"date\":\"{}\",", self.date.to_string()));
-        for point in points.iter(){
+}
+
+/// Serializes a WeatherData entry with only the selected fields (the date is always included), the same record
+/// [`DataOps::json`] writes for each day
+/// The weather code is followed by its description
+pub struct PointsView<'a>{
+    data: &'a WeatherData,
+    points: &'a [DataPoint],
+}
+
+impl<'a> PointsView<'a>{
+    /// The fields are written in the order of `points`, which [`DataOps::json`] takes from [`DataPoint::ALL`]
+    pub fn new(data: &'a WeatherData, points: &'a [DataPoint]) -> PointsView<'a>{
+        PointsView{ data, points }
+    }
+}
+
+impl Serialize for PointsView<'_>{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error>{
+        let mut map = serializer.serialize_map(None)?;
+        map.serialize_entry("date", &self.data.date)?;
+        for point in self.points{
             match point{
-                DataPoint::WeatherCode => json.push_str(&format!("\"weather_code\":{},", self.weather_code)),
-                DataPoint::TemperatureMax => json.push_str(&format!("\"temperature_max\":{},", self.temp_max)),
-                DataPoint::TemperatureMin => json.push_str(&format!("\"temperature_min\":{},", self.temp_min)),
-                DataPoint::PrecipitationSum => json.push_str(&format!("\"precipitation_sum\":{},", self.precip_sum)),
-                DataPoint::WindSpeedMax => json.push_str(&format!("\"wind_speed_max\":{},", self.max_wind)),
-                DataPoint::PrecipitationProbabilityMax => json.push_str(&format!("\"precipitation_probability_max\":{},", self.precip_prob_max)),
+                DataPoint::WeatherCode => {
+                    map.serialize_entry(point.name(), &self.data.weather_code)?;
+                    map.serialize_entry("weather_description", &self.data.weather_code.map(|code| code.description()))?;
+                }
                 DataPoint::Date => (),
-                
+                _ => map.serialize_entry(point.name(), &self.data.value(*point))?,
             }
         }
-        // Removes trailing comma
-        json.remove(json.rfind(',').unwrap());
-        json.push_str("}");
-        json
+        if self.data.synthetic{
+            map.serialize_entry("synthetic", &true)?;
+        }
+        map.end()
     }
+}
+
+/// Serde helpers for (de)serializing a WeatherDataMap as a list of records rather than a map keyed by date
+/// 
+/// Use with `#[serde(with = "parser::records")]`, or call the functions directly. Deserializing sorts the
+/// records by date and rejects duplicate dates, the same way [`WeatherData::from_data`] does.
+/// # Examples
+/// ```
+/// use parser::{records, WeatherData};
+/// let data = WeatherData::from_data("date: 2024-04-25 2024-04-24
+/// weather_code: 3 61
+/// temperature_max: 54.9 52.6
+/// temperature_min: 44.2 47.1
+/// precipitation_sum: 0 0.22
+/// wind_speed_max: 9.3 10.1
+/// precipitation_probability_max: 45 100".to_string()).unwrap();
+/// 
+/// let json = records::serialize(&data, serde_json::value::Serializer).unwrap();
+/// assert_eq!(json[0]["date"], "2024-04-24");
+/// let back = records::deserialize(json).unwrap();
+/// assert_eq!(back.len(), 2);
+/// ```
+pub mod records{
+    use super::*;
 
+    pub fn serialize<S: Serializer>(map: &WeatherDataMap, serializer: S) -> Result<S::Ok, S::Error>{
+        serializer.collect_seq(map.values())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<WeatherDataMap, D::Error>{
+        let mut records = Vec::<WeatherData>::deserialize(deserializer)?;
+        records.sort_by_key(|data| data.date);
+        let mut map: WeatherDataMap = IndexMap::with_capacity(records.len());
+        for data in records{
+            if map.contains_key(&data.date){
+                return Err(D::Error::custom(format!("duplicate date: {}", data.date)));
+            }
+            map.insert(data.date, data);
+        }
+        Ok(map)
+    }
 }
 
+#[cfg(test)]
+mod tests{
+    use super::*;
 
-fn comp_date(a: &WeatherData, b: &WeatherData) -> std::cmp::Ordering{
-    let a = a.date;
-    let b = b.date;
-    if a.year == b.year{
-        if a.month == b.month{
-            a.day.cmp(&b.day)
-        }else{
-            a.month.cmp(&b.month)
+    fn date(date: &str) -> Date{
+        Date::from_string(date).unwrap()
+    }
+
+    /// Two days in the column text format, out of order
+    const SAMPLE: &str = "date: 2024-04-25 2024-04-24\nweather_code: 3 61\ntemperature_max: 54.9 52.6\ntemperature_min: 44.2 47.1\n\
+                          precipitation_sum: 0 0.22\nwind_speed_max: 9.3 10.1\nprecipitation_probability_max: 45 100";
+
+    #[test]
+    fn rejects_bad_dates(){
+        for bad in ["2024-13-45", "0-0-0", "2023-02-29", "1900-02-29", "2024-04-31", "2024-00-10", "24-04-24", "2024-04", "2024-04-24-01", "2024-4a-24", ""]{
+            assert!(matches!(Date::from_string(bad), Err(ParseError::InvalidDate(_))), "{} was accepted", bad);
         }
+        assert_eq!(date("2024-02-29"), Date::new(2024, 2, 29).unwrap());
+        assert_eq!(date("2000-2-9"), Date::new(2000, 2, 9).unwrap());
     }
-    else{
-        a.year.cmp(&b.year)
+
+    #[test]
+    fn bad_date_in_the_text_format(){
+        let data = "date: 2024-04-24 2024-02-30\nweather_code: 3 61\ntemperature_max: 61.9 52.6\ntemperature_min: 47.1 44.2\n\
+                    precipitation_sum: 0 0.22\nwind_speed_max: 9.3 10.1\nprecipitation_probability_max: 45 100";
+        let e = WeatherData::from_data(data.to_string()).unwrap_err();
+        assert!(matches!(e.error, ParseError::InvalidDate(_)));
+        assert_eq!((e.line, e.field, e.index), (Some(1), Some(DataPoint::Date), Some(2)));
+        assert_eq!(e.token.as_deref(), Some("2024-02-30"));
     }
 
-}
\ No newline at end of file
+    #[test]
+    fn calendar_arithmetic(){
+        assert_eq!(date("2023-12-31").days_between(&date("2025-01-01")), 367);
+        assert_eq!(date("2024-03-01").pred(), Some(date("2024-02-29")));
+        assert_eq!(date("2023-12-31").succ(), Some(date("2024-01-01")));
+        assert_eq!(date("2024-04-24").add_days(-366), Some(date("2023-04-24")));
+        assert_eq!(date("9999-12-31").succ(), None);
+        assert_eq!(date("0001-01-01").pred(), None);
+        assert_eq!(date("2024-04-24").weekday(), Weekday::Wednesday);
+        assert_eq!(date("2024-12-31").day_of_year(), 366);
+        assert_eq!(Date::distance(&date("2024-04-24"), &date("2023-04-24")), 366);
+    }
+
+    #[test]
+    fn dates_serialize_as_strings(){
+        assert_eq!(serde_json::to_string(&date("2024-04-24")).unwrap(), "\"2024-04-24\"");
+        assert_eq!(serde_json::from_str::<Date>("\"2024-04-24\"").unwrap(), date("2024-04-24"));
+        assert!(serde_json::from_str::<Date>("\"2024-02-30\"").is_err());
+        assert!(serde_json::from_str::<Date>("20240424").is_err());
+    }
+
+    #[test]
+    fn records_round_trip_through_json(){
+        let data = WeatherData::from_data(SAMPLE.to_string()).unwrap();
+        let mut json = vec![];
+        records::serialize(&data, &mut serde_json::Serializer::new(&mut json)).unwrap();
+        let json = String::from_utf8(json).unwrap();
+        assert!(json.starts_with(
+            "[{\"date\":\"2024-04-24\",\"weather_code\":61,\"temperature_max\":52.6,\"temperature_min\":47.1,\"precipitation_sum\":0.22,\
+             \"wind_speed_max\":10.1,\"precipitation_probability_max\":100.0},{\"date\":\"2024-04-25\""
+        ));
+        let back = records::deserialize(&mut serde_json::Deserializer::from_str(&json)).unwrap();
+        assert_eq!(back.to_file(), data.to_file());
+    }
+
+    #[test]
+    fn records_reject_duplicate_dates(){
+        let json = serde_json::json!([{"date": "2024-04-25"}, {"date": "2024-04-24", "temperature_max": 52.6}, {"date": "2024-04-25"}]);
+        let e = records::deserialize(json).unwrap_err();
+        assert_eq!(e.to_string(), "duplicate date: 2024-04-25");
+        // Fields left out are missing, and the records are sorted by date
+        let back = records::deserialize(serde_json::json!([{"date": "2024-04-25"}, {"date": "2024-04-24"}])).unwrap();
+        assert_eq!(back.keys().copied().collect::<Vec<Date>>(), vec![date("2024-04-24"), date("2024-04-25")]);
+        assert!(back[0].temp_max.is_none());
+    }
+
+    #[test]
+    fn short_row_is_reported_at_the_first_missing_value(){
+        let data = SAMPLE.replace("wind_speed_max: 9.3 10.1", "wind_speed_max: 9.3");
+        let e = WeatherData::from_data(data).unwrap_err();
+        assert_eq!(e.error, ParseError::TooFewValues);
+        assert_eq!((e.line, e.field, e.index, e.date), (Some(6), Some(DataPoint::WindSpeedMax), Some(2), Some(date("2024-04-24"))));
+        assert_eq!(e.to_string(), "line 6 (wind_speed_max), value #2 for 2024-04-24: missing value, there are fewer values than dates");
+        let e = WeatherData::from_data(SAMPLE.replace("temperature_min: 44.2 47.1", "temperature_min: 44.2 47.1 40")).unwrap_err();
+        assert_eq!(e.error, ParseError::TooManyValues);
+        assert_eq!((e.line, e.index, e.token.as_deref()), (Some(4), Some(3), Some("40")));
+    }
+
+    #[test]
+    fn duplicate_date_is_reported_with_its_position(){
+        let data = SAMPLE.replace("date: 2024-04-25 2024-04-24", "date: 2024-04-25 2024-04-25");
+        let e = WeatherData::from_data(data).unwrap_err();
+        assert_eq!(e.error, ParseError::DuplicateDate(date("2024-04-25")));
+        assert_eq!((e.line, e.field, e.index), (Some(1), Some(DataPoint::Date), Some(2)));
+        assert_eq!(e.to_string(), "line 1 (date), value #2 for 2024-04-25: duplicate date 2024-04-25");
+    }
+
+    #[test]
+    fn invalid_value_names_the_token(){
+        let e = WeatherData::from_data(SAMPLE.replace("52.6", "abc")).unwrap_err();
+        assert_eq!(e.error, ParseError::InvalidTemperature("abc".to_string()));
+        assert_eq!(e.to_string(), "line 3 (temperature_max), value #2 for 2024-04-24: 'abc' is not a number");
+    }
+
+    #[test]
+    fn lenient_mode_collects_every_problem(){
+        let data = "date: 2024-04-24 2024-04-25 2024-02-30 2024-04-26\nweather_code: 3 61 3 3\ntemperature_max: 61.9 abc 50 53.1\n\
+                    bogus line\ntemperature_min: 47.1 44.2 40 45.3\nprecipitation_sum: 0 0.22 0\nwind_speed_max: 9.3 10.1 8.2 9.0\n\
+                    precipitation_probability_max: 45 100 20 30";
+        assert!(WeatherData::from_data(data.to_string()).is_err());
+        let report = WeatherData::from_data_with(data.to_string(), ParseOptions{ mode: ParseMode::Lenient }).unwrap();
+        assert_eq!(report.data.keys().copied().collect::<Vec<Date>>(), vec![date("2024-04-24")]);
+        let problems: Vec<(Option<usize>, Option<usize>, &ParseError)> =
+            report.problems.iter().map(|problem| (problem.line, problem.index, &problem.error)).collect();
+        assert_eq!(problems, vec![
+            (Some(4), None, &ParseError::InvalidLine("bogus line".to_string())),
+            (Some(1), Some(3), &ParseError::InvalidDate("Invalid day: 30 (month 2 of 2024 has 29 days)".to_string())),
+            (Some(3), Some(2), &ParseError::InvalidTemperature("abc".to_string())),
+            (Some(6), Some(4), &ParseError::TooFewValues),
+        ]);
+        assert_eq!(report.problems[3].date, Some(date("2024-04-26")));
+    }
+
+    #[test]
+    fn missing_values_round_trip_through_the_text_format(){
+        let data = SAMPLE.replace("weather_code: 3 61", "weather_code: - 61").replace("52.6", "null").replace("wind_speed_max: 9.3 10.1", "wind_speed_max: NaN -");
+        let map = WeatherData::from_data(data).unwrap();
+        let (first, second) = (&map[&date("2024-04-24")], &map[&date("2024-04-25")]);
+        assert_eq!((first.weather_code.is_some(), first.temp_max, first.max_wind), (true, None, None));
+        assert_eq!((second.weather_code, second.temp_max, second.max_wind), (None, Some(54.9), None));
+        let file = map.to_file();
+        assert!(file.contains("weather_code:61 - \n"), "{}", file);
+        assert!(file.contains("wind_speed_max:- - \n"), "{}", file);
+        assert_eq!(WeatherData::from_data(file.clone()).unwrap().to_file(), file);
+    }
+}
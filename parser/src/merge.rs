@@ -0,0 +1,210 @@
+use serde::Serialize;
+
+use crate::{DataPoint, Date, WeatherData, WeatherDataMap};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+/// How [`DataOps::merge`](crate::DataOps::merge) settles a field both maps have different values for
+///
+/// A value only one of the maps has is always kept, whatever the strategy
+pub enum MergeStrategy{
+    /// Keeps the value of the map being merged into
+    PreferLeft,
+    /// Keeps the value of the map being merged in
+    PreferRight,
+    /// Averages the two values, weather codes can't be averaged so the left one is kept
+    Average,
+    /// Merges nothing if any value differs
+    #[default]
+    FailOnConflict,
+}
+
+impl MergeStrategy{
+    /// Reads a strategy from its name ("prefer-left", "prefer-right", "average" or "fail-on-conflict"), ignoring case
+    pub fn from_name(name: &str) -> Option<MergeStrategy>{
+        match name.to_ascii_lowercase().as_str(){
+            "prefer-left" => Some(MergeStrategy::PreferLeft),
+            "prefer-right" => Some(MergeStrategy::PreferRight),
+            "average" => Some(MergeStrategy::Average),
+            "fail-on-conflict" => Some(MergeStrategy::FailOnConflict),
+            _ => None,
+        }
+    }
+    pub fn name(&self) -> &'static str{
+        match self{
+            MergeStrategy::PreferLeft => "prefer-left",
+            MergeStrategy::PreferRight => "prefer-right",
+            MergeStrategy::Average => "average",
+            MergeStrategy::FailOnConflict => "fail-on-conflict",
+        }
+    }
+}
+
+impl std::fmt::Display for MergeStrategy{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result{
+        write!(f, "{}", self.name())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+/// A field two maps have different values for on the same date
+pub struct FieldConflict{
+    pub field: DataPoint,
+    pub left: f32,
+    pub right: f32,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+/// A date both maps have, with the fields whose values differ
+pub struct Conflict{
+    pub date: Date,
+    pub fields: Vec<FieldConflict>,
+}
+
+#[derive(Clone, Debug)]
+/// Result of [`DataOps::merge`](crate::DataOps::merge)
+pub struct MergeReport{
+    /// Every day of both maps, sorted by date
+    pub data: WeatherDataMap,
+    /// Number of dates only the right map had
+    pub added: usize,
+    /// Number of dates both maps had, with or without conflicts
+    pub overlapping: usize,
+    /// How the conflicts were settled
+    pub strategy: MergeStrategy,
+    /// Dates with differing values, in date order
+    pub conflicts: Vec<Conflict>,
+}
+
+/// Fields of two entries for the same date whose values differ
+fn differences(left: &WeatherData, right: &WeatherData) -> Vec<FieldConflict>{
+    DataPoint::ALL
+        .into_iter()
+        .filter_map(|field| match (left.value(field), right.value(field)){
+            (Some(left), Some(right)) if left != right => Some(FieldConflict{ field, left, right }),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Combines the entries of both maps for one date
+fn combine(left: &WeatherData, right: &WeatherData, strategy: MergeStrategy) -> WeatherData{
+    let mut data = left.clone();
+    for field in DataPoint::ALL{
+        let value = match (left.value(field), right.value(field), strategy){
+            (Some(left), Some(right), MergeStrategy::Average) if field != DataPoint::WeatherCode => Some((left + right) / 2.0),
+            (Some(_), Some(right), MergeStrategy::PreferRight) => Some(right),
+            (Some(left), _, _) => Some(left),
+            (None, right, _) => right,
+        };
+        data.set_value(field, value);
+    }
+    // Only made up if neither map recorded the day
+    data.synthetic = left.synthetic && right.synthetic;
+    data
+}
+
+pub(crate) fn merge(left: &WeatherDataMap, right: &WeatherDataMap, strategy: MergeStrategy) -> Result<MergeReport, Vec<Conflict>>{
+    let mut data = left.clone();
+    let mut conflicts = vec![];
+    let (mut added, mut overlapping) = (0, 0);
+    for (date, other) in right.iter(){
+        let existing = match left.get(date){
+            Some(existing) => existing,
+            None => {
+                added += 1;
+                data.insert(*date, other.clone());
+                continue;
+            }
+        };
+        overlapping += 1;
+        let fields = differences(existing, other);
+        if !fields.is_empty(){
+            conflicts.push(Conflict{ date: *date, fields });
+        }
+        data.insert(*date, combine(existing, other, strategy));
+    }
+    conflicts.sort_by_key(|conflict| conflict.date);
+    if strategy == MergeStrategy::FailOnConflict && !conflicts.is_empty(){
+        return Err(conflicts);
+    }
+    data.sort_keys();
+    Ok(MergeReport{ data, added, overlapping, strategy, conflicts })
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::WeatherCode;
+
+    fn date(date: &str) -> Date{
+        Date::from_string(date).unwrap()
+    }
+
+    fn day(date: &str, code: u8, temp_max: f32, precip_sum: Option<f32>) -> (Date, WeatherData){
+        let data = WeatherData{ precip_sum, ..WeatherData::new(self::date(date), WeatherCode::new(code).unwrap(), temp_max, 40.0, 0.0, 10.0, 20.0) };
+        (data.date, data)
+    }
+
+    /// The left map has 04-01 and 04-02, the right one 04-03 and a different 04-02, missing one value each side
+    fn maps() -> (WeatherDataMap, WeatherDataMap){
+        let left = [day("2024-04-02", 3, 52.0, None), day("2024-04-01", 3, 50.0, Some(0.0))].into_iter().collect();
+        let right = [day("2024-04-03", 0, 60.0, Some(0.0)), day("2024-04-02", 61, 56.0, Some(0.3))].into_iter().collect();
+        (left, right)
+    }
+
+    fn merged_day(report: &MergeReport) -> (Option<u8>, Option<f32>, Option<f32>){
+        let data = &report.data[&date("2024-04-02")];
+        (data.weather_code.map(|code| code.code()), data.temp_max, data.precip_sum)
+    }
+
+    #[test]
+    fn each_strategy(){
+        let (left, right) = maps();
+        let report = merge(&left, &right, MergeStrategy::PreferLeft).unwrap();
+        assert_eq!(report.data.keys().copied().collect::<Vec<Date>>(), vec![date("2024-04-01"), date("2024-04-02"), date("2024-04-03")]);
+        assert_eq!((report.added, report.overlapping, report.strategy), (1, 1, MergeStrategy::PreferLeft));
+        assert_eq!(merged_day(&report), (Some(3), Some(52.0), Some(0.3)));
+        assert_eq!(report.data[&date("2024-04-03")].temp_max, Some(60.0));
+
+        let report = merge(&left, &right, MergeStrategy::PreferRight).unwrap();
+        assert_eq!(merged_day(&report), (Some(61), Some(56.0), Some(0.3)));
+        let report = merge(&left, &right, MergeStrategy::Average).unwrap();
+        assert_eq!(merged_day(&report), (Some(3), Some(54.0), Some(0.3)));
+
+        // Conflicts are reported whichever way they're settled
+        assert_eq!(report.conflicts, vec![Conflict{
+            date: date("2024-04-02"),
+            fields: vec![
+                FieldConflict{ field: DataPoint::WeatherCode, left: 3.0, right: 61.0 },
+                FieldConflict{ field: DataPoint::TemperatureMax, left: 52.0, right: 56.0 },
+            ],
+        }]);
+    }
+
+    #[test]
+    fn fail_on_conflict_changes_nothing(){
+        let (left, right) = maps();
+        let conflicts = merge(&left, &right, MergeStrategy::FailOnConflict).unwrap_err();
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].date, date("2024-04-02"));
+        assert_eq!(left.keys().copied().collect::<Vec<Date>>(), vec![date("2024-04-02"), date("2024-04-01")]);
+        assert_eq!(left[&date("2024-04-02")].precip_sum, None);
+
+        // Without a conflict it merges like any other strategy, filling in the missing values
+        let same: WeatherDataMap = [day("2024-04-02", 3, 52.0, Some(0.3)), day("2024-04-04", 0, 61.0, None)].into_iter().collect();
+        let report = merge(&left, &same, MergeStrategy::FailOnConflict).unwrap();
+        assert!(report.conflicts.is_empty());
+        assert_eq!((report.added, report.overlapping), (1, 1));
+        assert_eq!(merged_day(&report), (Some(3), Some(52.0), Some(0.3)));
+    }
+
+    #[test]
+    fn synthetic_days_only_stay_synthetic_on_both_sides(){
+        let (mut left, right) = maps();
+        left.values_mut().for_each(|data| data.synthetic = true);
+        let report = merge(&left, &right, MergeStrategy::PreferLeft).unwrap();
+        let synthetic: Vec<bool> = report.data.values().map(|data| data.synthetic).collect();
+        assert_eq!(synthetic, vec![true, false, false]);
+        assert_eq!(merge(&left, &WeatherDataMap::new(), MergeStrategy::FailOnConflict).unwrap().added, 0);
+    }
+}
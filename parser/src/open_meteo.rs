@@ -0,0 +1,270 @@
+use std::collections::HashSet;
+use std::path::Path;
+
+use indexmap::IndexMap;
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::units::read_unit;
+use crate::{invalid_value, DataPoint, Date, ParseDiagnostic, ParseError, ParseOptions, ParseReport, Problems, WeatherCode, WeatherData};
+
+/// The parts of an Open-Meteo forecast or archive response that are read, everything else is ignored
+#[derive(Deserialize)]
+struct Document{
+    latitude: Option<f64>,
+    longitude: Option<f64>,
+    elevation: Option<f64>,
+    timezone: Option<String>,
+    daily: IndexMap<String, Vec<Value>>,
+    #[serde(default)]
+    daily_units: IndexMap<String, String>,
+}
+
+#[derive(Debug, Clone)]
+/// Daily data imported from an Open-Meteo document, along with the location and units it came with
+pub struct OpenMeteoImport{
+    pub report: ParseReport,
+    /// Unit of each field as given in `daily_units` (e.g. "°F", "inch", "mp/h")
+    pub units: IndexMap<DataPoint, String>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    pub elevation: Option<f64>,
+    pub timezone: Option<String>,
+}
+
+/// Returns the field an Open-Meteo daily variable is read into, if it's one we keep
+fn field_for(variable: &str) -> Option<DataPoint>{
+    match variable{
+        "time" => Some(DataPoint::Date),
+        "weather_code" | "weathercode" => Some(DataPoint::WeatherCode),
+        "temperature_2m_max" => Some(DataPoint::TemperatureMax),
+        "temperature_2m_min" => Some(DataPoint::TemperatureMin),
+        "precipitation_sum" => Some(DataPoint::PrecipitationSum),
+        "wind_speed_10m_max" | "windspeed_10m_max" => Some(DataPoint::WindSpeedMax),
+        "precipitation_probability_max" => Some(DataPoint::PrecipitationProbabilityMax),
+        // Also accepts documents that already use our own field names
+        _ => DataPoint::from_name(variable),
+    }
+}
+
+fn diagnostic(error: ParseError, field: DataPoint, index: usize, value: &Value, date: Option<Date>) -> ParseDiagnostic{
+    ParseDiagnostic{
+        error,
+        line: None,
+        field: Some(field),
+        index: Some(index + 1),
+        token: Some(value.to_string()),
+        date,
+    }
+}
+
+/// Reads the `daily` block of an Open-Meteo JSON document (as saved from the forecast or archive API)
+///
+/// Daily variables are matched to fields by name (`temperature_2m_max` is read as `temperature_max`,
+/// `wind_speed_10m_max` as `wind_speed_max`, ...), variables we don't keep are ignored and fields without
+/// a variable are read as missing. A field with two variables (like `weathercode` and `weather_code`) is a
+/// [`ParseError::DuplicateField`], and a variable with fewer values than `time` is [`ParseError::TooFewValues`].
+///
+/// The units in `daily_units` decide [`ParseReport::units`]: the system the temperatures are in, or that of
+/// the other fields if there are none. Fields in a unit of the other system (or in m/s or knots) are converted to it,
+/// and fields whose unit isn't known are taken as is.
+/// # Errors
+/// Returns an error if the document isn't valid JSON or has no `daily.time`, and otherwise behaves like
+/// the text format parser for the given [`ParseOptions`]
+/// # Examples
+/// ```
+/// use parser::{from_open_meteo, DataPoint, Date, ParseOptions, UnitSystem};
+/// let json = r#"{
+///     "latitude": 38.97, "longitude": -95.26,
+///     "daily_units": {"time": "iso8601", "temperature_2m_max": "°F"},
+///     "daily": {"time": ["2024-04-24", "2024-04-25"], "temperature_2m_max": [61.9, null], "sunrise": ["", ""]}
+/// }"#;
+/// let import = from_open_meteo(json, ParseOptions::default()).unwrap();
+/// let data = &import.report.data;
+/// assert_eq!(data[&Date::from_string("2024-04-24").unwrap()].temp_max, Some(61.9));
+/// assert_eq!(data[&Date::from_string("2024-04-25").unwrap()].temp_max, None);
+/// assert_eq!(import.units[&DataPoint::TemperatureMax], "°F");
+/// assert_eq!(import.report.units, Some(UnitSystem::Imperial));
+/// ```
+pub fn from_open_meteo(json: &str, options: ParseOptions) -> Result<OpenMeteoImport, ParseDiagnostic>{
+    let document: Document = match serde_json::from_str(json){
+        Ok(document) => document,
+        Err(e) => return Err(ParseDiagnostic::from(ParseError::InvalidJson(e.to_string()))),
+    };
+    let mut problems = Problems{
+        mode: options.mode,
+        found: vec![],
+    };
+
+    let times = match document.daily.get("time"){
+        Some(times) => times,
+        None => return Err(ParseDiagnostic::from(ParseError::MissingField(DataPoint::Date))),
+    };
+    let mut dates: Vec<Option<Date>> = Vec::with_capacity(times.len());
+    let mut seen: HashSet<Date> = HashSet::with_capacity(times.len());
+    for (i, time) in times.iter().enumerate(){
+        let parsed = match time.as_str(){
+            Some(time) => Date::from_string(time),
+            None => Err(ParseError::InvalidDate(format!("Invalid date: {}", time))),
+        };
+        match parsed{
+            Ok(date) if !seen.insert(date) => {
+                problems.report(diagnostic(ParseError::DuplicateDate(date), DataPoint::Date, i, time, Some(date)))?;
+                dates.push(None);
+            }
+            Ok(date) => dates.push(Some(date)),
+            Err(e) => {
+                problems.report(diagnostic(e, DataPoint::Date, i, time, None))?;
+                dates.push(None);
+            }
+        }
+    }
+
+    // Older and newer names of a variable (`weathercode` and `weather_code`) are read into the same field, only the first
+    // one in the document is kept
+    let mut variables: IndexMap<DataPoint, &str> = IndexMap::new();
+    for variable in document.daily.keys(){
+        match field_for(variable){
+            Some(DataPoint::Date) | None => continue,
+            Some(field) if variables.contains_key(&field) => problems.report(ParseDiagnostic{
+                field: Some(field),
+                token: Some(variable.clone()),
+                ..ParseDiagnostic::from(ParseError::DuplicateField(field))
+            })?,
+            Some(field) => {
+                variables.insert(field, variable);
+            }
+        };
+    }
+    let is_read = |field: DataPoint, variable: &str| variables.get(&field).is_none_or(|read| *read == variable);
+
+    let mut units: IndexMap<DataPoint, String> = IndexMap::new();
+    for (variable, unit) in document.daily_units.iter(){
+        match field_for(variable){
+            Some(DataPoint::Date) | None => continue,
+            Some(field) if is_read(field, variable) => units.insert(field, unit.clone()),
+            Some(_) => continue,
+        };
+    }
+    let temperature = [DataPoint::TemperatureMax, DataPoint::TemperatureMin].iter().filter_map(|field| units.get(field));
+    let system = temperature.chain(units.values()).find_map(|unit| read_unit(unit)).map(|(system, _)| system);
+
+    // Days are dropped if any of their values is invalid, the same as in the text format
+    let mut days: Vec<Option<WeatherData>> = dates.iter().map(|date| date.map(WeatherData::empty)).collect();
+    for (variable, values) in document.daily.iter(){
+        let field = match field_for(variable){
+            Some(DataPoint::Date) | None => continue,
+            Some(field) if is_read(field, variable) => field,
+            Some(_) => continue,
+        };
+        // Brings the values to the unit of the chosen system, leaving them as is if the unit isn't known
+        let unit = units.get(&field).and_then(|unit| read_unit(unit));
+        let normalize = |value: f32| match (unit, system){
+            (Some((from, scale)), Some(to)) => from.convert(value * scale, field, to),
+            _ => value,
+        };
+        if values.len() > dates.len(){
+            problems.report(diagnostic(ParseError::TooManyValues, field, dates.len(), &values[dates.len()], None))?;
+        }
+        if values.len() < dates.len(){
+            problems.report(diagnostic(ParseError::TooFewValues, field, values.len(), &Value::Null, dates[values.len()]))?;
+            days[values.len()..].iter_mut().for_each(|day| *day = None);
+        }
+        for (i, value) in values.iter().enumerate().take(dates.len()){
+            let number = match value{
+                Value::Null => continue,
+                Value::Number(number) => number.as_f64(),
+                _ => None,
+            };
+            let number = match (field, number){
+                (DataPoint::WeatherCode, Some(code)) if WeatherCode::from_value(code as f32).is_none() => None,
+                (_, number) => number,
+            };
+            match number{
+                Some(number) => {
+                    if let Some(day) = days[i].as_mut(){
+                        day.set_value(field, Some(normalize(number as f32)));
+                    }
+                }
+                None => {
+                    problems.report(diagnostic(invalid_value(field)(value.to_string()), field, i, value, dates[i]))?;
+                    days[i] = None;
+                }
+            }
+        }
+    }
+
+    let mut weather_data: Vec<WeatherData> = days.into_iter().flatten().collect();
+    weather_data.sort_by_key(|data| data.date);
+    Ok(OpenMeteoImport{
+        report: ParseReport{
+            data: weather_data.into_iter().map(|data| (data.date, data)).collect(),
+            problems: problems.found,
+            units: system,
+        },
+        units,
+        latitude: document.latitude,
+        longitude: document.longitude,
+        elevation: document.elevation,
+        timezone: document.timezone,
+    })
+}
+
+/// Reads an Open-Meteo JSON document from disk, see [`from_open_meteo`]
+pub fn read_open_meteo<P: AsRef<Path>>(path: P, options: ParseOptions) -> Result<OpenMeteoImport, ParseDiagnostic>{
+    match std::fs::read_to_string(path){
+        Ok(json) => from_open_meteo(&json, options),
+        Err(e) => Err(ParseDiagnostic::from(ParseError::Io(e.to_string()))),
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::ParseMode;
+
+    fn import(daily: &str, mode: ParseMode) -> Result<OpenMeteoImport, ParseDiagnostic>{
+        let json = format!(r#"{{"daily_units": {{"weathercode": "wmo code", "weather_code": "wmo code"}}, "daily": {}}}"#, daily);
+        from_open_meteo(&json, ParseOptions{ mode })
+    }
+
+    fn date(date: &str) -> Date{
+        Date::from_string(date).unwrap()
+    }
+
+    #[test]
+    fn both_names_of_a_variable(){
+        let daily = r#"{"time": ["2024-04-24", "2024-04-25"], "weathercode": [3, 61], "temperature_2m_max": [61.9, 52.6], "weather_code": [0, 0]}"#;
+        let e = import(daily, ParseMode::Strict).unwrap_err();
+        assert_eq!(e.error, ParseError::DuplicateField(DataPoint::WeatherCode));
+        assert_eq!((e.field, e.token.as_deref()), (Some(DataPoint::WeatherCode), Some("weather_code")));
+
+        // The first one is kept
+        let import = import(daily, ParseMode::Lenient).unwrap();
+        assert_eq!(import.report.problems.len(), 1);
+        let codes: Vec<Option<u8>> = import.report.data.values().map(|data| data.weather_code.map(|code| code.code())).collect();
+        assert_eq!(codes, vec![Some(3), Some(61)]);
+        assert_eq!(import.units[&DataPoint::WeatherCode], "wmo code");
+    }
+
+    #[test]
+    fn column_shorter_than_time(){
+        let daily = r#"{"time": ["2024-04-24", "2024-04-25", "2024-04-26"], "temperature_2m_max": [61.9, 52.6, 54.9], "precipitation_sum": [0]}"#;
+        let e = import(daily, ParseMode::Strict).unwrap_err();
+        assert_eq!(e.error, ParseError::TooFewValues);
+        assert_eq!((e.field, e.index, e.date), (Some(DataPoint::PrecipitationSum), Some(2), Some(date("2024-04-25"))));
+
+        // The days without a value are dropped, like a day with an invalid value
+        let report = import(daily, ParseMode::Lenient).unwrap().report;
+        assert_eq!(report.data.keys().copied().collect::<Vec<Date>>(), vec![date("2024-04-24")]);
+        assert_eq!(report.problems.iter().map(|problem| &problem.error).collect::<Vec<&ParseError>>(), vec![&ParseError::TooFewValues]);
+    }
+
+    #[test]
+    fn column_longer_than_time(){
+        let daily = r#"{"time": ["2024-04-24"], "temperature_2m_max": [61.9, 52.6]}"#;
+        assert_eq!(import(daily, ParseMode::Strict).unwrap_err().error, ParseError::TooManyValues);
+        let report = import(daily, ParseMode::Lenient).unwrap().report;
+        assert_eq!(report.data[&date("2024-04-24")].temp_max, Some(61.9));
+    }
+}
@@ -0,0 +1,283 @@
+use std::collections::HashSet;
+
+use indexmap::IndexMap;
+use serde::Serialize;
+
+use crate::{stats, DataPoint, Date, UnitSystem, WeatherDataMap};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+/// Test for values that are far from the rest of a field, see [`DataOps::check_quality`](crate::DataOps::check_quality)
+pub enum OutlierTest{
+    /// Values more than `limit` standard deviations from the mean
+    ZScore{ limit: f32 },
+    /// Values more than `k` interquartile ranges below the first quartile or above the third (Tukey's fences)
+    Iqr{ k: f32 },
+}
+
+impl OutlierTest{
+    /// Reads a test from its name ("z_score" or "iqr") with the usual limit, 3 standard deviations or 1.5 interquartile ranges
+    pub fn from_name(name: &str) -> Option<OutlierTest>{
+        match name.to_ascii_lowercase().as_str(){
+            "z_score" | "zscore" => Some(OutlierTest::ZScore{ limit: 3.0 }),
+            "iqr" => Some(OutlierTest::Iqr{ k: 1.5 }),
+            _ => None,
+        }
+    }
+    pub fn name(&self) -> &'static str{
+        match self{
+            OutlierTest::ZScore{ .. } => "z_score",
+            OutlierTest::Iqr{ .. } => "iqr",
+        }
+    }
+}
+
+impl Default for OutlierTest{
+    /// Z-scores over 3, which unlike the interquartile range doesn't flag every wet day of a mostly dry month
+    fn default() -> OutlierTest{
+        OutlierTest::ZScore{ limit: 3.0 }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+/// Why a value can't be right
+pub enum Implausible{
+    /// Precipitation or wind speed below zero
+    Negative,
+    /// Lower than the value can physically be, like a precipitation chance under 0%
+    TooLow,
+    /// Higher than the value can physically be, like a precipitation chance over 100% or a high past the world record
+    TooHigh,
+    /// Low temperature above the high temperature of the same day
+    AboveTemperatureMax,
+}
+
+impl Implausible{
+    /// Name of the reason, as used in JSON
+    pub fn name(&self) -> &'static str{
+        match self{
+            Implausible::Negative => "negative",
+            Implausible::TooLow => "too_low",
+            Implausible::TooHigh => "too_high",
+            Implausible::AboveTemperatureMax => "above_temperature_max",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Issue{
+    /// The value is physically impossible
+    Implausible{ reason: Implausible },
+    /// The value is possible but far from the rest of the field, `score` is how far in the units of the test
+    /// (standard deviations or interquartile ranges), negative below the rest
+    Outlier{ test: &'static str, score: f32 },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+/// A questionable value of one field of a day
+pub struct QualityFlag{
+    pub field: DataPoint,
+    pub value: f32,
+    #[serde(flatten)]
+    pub issue: Issue,
+}
+
+/// Lowest and highest values a field can physically have in imperial units, from the world records
+/// (-128.6°F, 134.1°F, 71.8 in of rain in a day and 253 mph winds) rounded out
+fn limits(point: DataPoint) -> Option<(f32, f32)>{
+    match point{
+        DataPoint::TemperatureMax | DataPoint::TemperatureMin => Some((-130.0, 135.0)),
+        DataPoint::PrecipitationSum => Some((0.0, 72.0)),
+        DataPoint::WindSpeedMax => Some((0.0, 254.0)),
+        DataPoint::PrecipitationProbabilityMax => Some((0.0, 100.0)),
+        DataPoint::WeatherCode | DataPoint::Date => None,
+    }
+}
+
+/// Fields whose values are compared with each other for outliers, the weather code is a category rather than an amount
+const NUMERIC: [DataPoint; 5] = [
+    DataPoint::TemperatureMax,
+    DataPoint::TemperatureMin,
+    DataPoint::PrecipitationSum,
+    DataPoint::WindSpeedMax,
+    DataPoint::PrecipitationProbabilityMax,
+];
+
+/// Fewest values of a field that are tested for outliers, with fewer there isn't a spread to compare against
+const MIN_OUTLIER_VALUES: usize = 5;
+
+pub(crate) fn check(map: &WeatherDataMap, units: UnitSystem, test: Option<OutlierTest>) -> IndexMap<Date, Vec<QualityFlag>>{
+    let mut flags: IndexMap<Date, Vec<QualityFlag>> = IndexMap::new();
+    for (date, data) in map.iter(){
+        for point in NUMERIC{
+            let (value, (low, high)) = match (data.value(point), limits(point)){
+                (Some(value), Some(limits)) => (value, limits),
+                _ => continue,
+            };
+            let (low, high) = (UnitSystem::Imperial.convert(low, point, units), UnitSystem::Imperial.convert(high, point, units));
+            let reason = if value < 0.0 && low == 0.0 && point != DataPoint::PrecipitationProbabilityMax{
+                Implausible::Negative
+            } else if value < low{
+                Implausible::TooLow
+            } else if value > high{
+                Implausible::TooHigh
+            } else if let (DataPoint::TemperatureMin, Some(max)) = (point, data.temp_max){
+                if value <= max{
+                    continue;
+                }
+                Implausible::AboveTemperatureMax
+            } else {
+                continue;
+            };
+            flags.entry(*date).or_default().push(QualityFlag{ field: point, value, issue: Issue::Implausible{ reason } });
+        }
+    }
+
+    if let Some(test) = test{
+        // Impossible values are already flagged, and would skew the spread the others are compared against
+        let implausible: HashSet<(Date, DataPoint)> =
+            flags.iter().flat_map(|(date, flags)| flags.iter().map(|flag| (*date, flag.field))).collect();
+        let plausible: WeatherDataMap = map
+            .iter()
+            .map(|(date, data)| {
+                let mut data = data.clone();
+                for point in NUMERIC.into_iter().filter(|point| implausible.contains(&(*date, *point))){
+                    data.set_value(point, None);
+                }
+                (*date, data)
+            })
+            .collect();
+        for point in NUMERIC{
+            for (date, value, score) in outliers(&plausible, point, test){
+                flags.entry(date).or_default().push(QualityFlag{ field: point, value, issue: Issue::Outlier{ test: test.name(), score } });
+            }
+        }
+    }
+
+    // Back in the order of the map, with each day's flags in field order
+    flags.sort_by_key(|date, _| map.get_index_of(date));
+    for day in flags.values_mut(){
+        day.sort_by_key(|flag| NUMERIC.iter().position(|point| *point == flag.field));
+    }
+    flags
+}
+
+/// Values of a field that fail the test, with their score
+fn outliers(map: &WeatherDataMap, point: DataPoint, test: OutlierTest) -> Vec<(Date, f32, f32)>{
+    let values = stats::values(map, point);
+    if values.len() < MIN_OUTLIER_VALUES{
+        return vec![];
+    }
+    // Scores each value, None if it's within the limit
+    let score: Box<dyn Fn(f32) -> Option<f32>> = match test{
+        OutlierTest::ZScore{ limit } => {
+            let (mean, stddev) = (stats::mean(map, point).unwrap(), stats::stddev(map, point).unwrap());
+            if stddev == 0.0{
+                return vec![];
+            }
+            Box::new(move |value| {
+                let score = (value - mean) / stddev;
+                (score.abs() > limit).then_some(score)
+            })
+        }
+        OutlierTest::Iqr{ k } => {
+            let (q1, q3) = (stats::percentile(map, point, 25.0).unwrap(), stats::percentile(map, point, 75.0).unwrap());
+            let iqr = q3 - q1;
+            if iqr == 0.0{
+                return vec![];
+            }
+            Box::new(move |value| {
+                let score = if value > q3 { (value - q3) / iqr } else { (value - q1) / iqr };
+                (score.abs() > k && (value > q3 || value < q1)).then_some(score)
+            })
+        }
+    };
+    values.into_iter().filter_map(|(date, value)| Some((date, value, score(value)?))).collect()
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::WeatherData;
+
+    /// Consecutive days from 2024-04-01 with the given high temperatures and nothing else
+    fn highs(temps: &[f32]) -> WeatherDataMap{
+        let first = Date::from_string("2024-04-01").unwrap();
+        temps.iter().enumerate().map(|(i, temp)| {
+            let date = first.add_days(i as i64).unwrap();
+            (date, WeatherData{ temp_max: Some(*temp), ..WeatherData::empty(date) })
+        }).collect()
+    }
+
+    fn issues(flags: &IndexMap<Date, Vec<QualityFlag>>) -> Vec<(DataPoint, f32, Issue)>{
+        flags.values().flatten().map(|flag| (flag.field, flag.value, flag.issue)).collect()
+    }
+
+    #[test]
+    fn each_implausible_reason(){
+        let date = Date::from_string("2024-04-01").unwrap();
+        let day = WeatherData{
+            temp_max: Some(140.0),
+            temp_min: Some(-140.0),
+            precip_sum: Some(-0.1),
+            max_wind: Some(-1.0),
+            precip_prob_max: Some(120.0),
+            ..WeatherData::empty(date)
+        };
+        let next = date.succ().unwrap();
+        let next_day = WeatherData{ temp_max: Some(50.0), temp_min: Some(55.0), precip_prob_max: Some(-5.0), ..WeatherData::empty(next) };
+        let map: WeatherDataMap = [(date, day), (next, next_day)].into_iter().collect();
+
+        let implausible = |reason| Issue::Implausible{ reason };
+        assert_eq!(issues(&check(&map, UnitSystem::Imperial, None)), vec![
+            (DataPoint::TemperatureMax, 140.0, implausible(Implausible::TooHigh)),
+            (DataPoint::TemperatureMin, -140.0, implausible(Implausible::TooLow)),
+            (DataPoint::PrecipitationSum, -0.1, implausible(Implausible::Negative)),
+            (DataPoint::WindSpeedMax, -1.0, implausible(Implausible::Negative)),
+            (DataPoint::PrecipitationProbabilityMax, 120.0, implausible(Implausible::TooHigh)),
+            (DataPoint::TemperatureMin, 55.0, implausible(Implausible::AboveTemperatureMax)),
+            (DataPoint::PrecipitationProbabilityMax, -5.0, implausible(Implausible::TooLow)),
+        ]);
+
+        // The limits follow the units, 60°C is past the record but 60°F isn't
+        let hot = highs(&[60.0]);
+        assert!(check(&hot, UnitSystem::Imperial, None).is_empty());
+        assert_eq!(issues(&check(&hot, UnitSystem::Metric, None)), vec![(DataPoint::TemperatureMax, 60.0, implausible(Implausible::TooHigh))]);
+    }
+
+    #[test]
+    fn z_score_outliers(){
+        // Mean 19 and standard deviation 27, so 100 is exactly 3 standard deviations out
+        let map = highs(&[10.0, 10.0, 10.0, 10.0, 10.0, 10.0, 10.0, 10.0, 10.0, 100.0]);
+        let flags = check(&map, UnitSystem::Imperial, Some(OutlierTest::ZScore{ limit: 2.0 }));
+        assert_eq!(flags.keys().copied().collect::<Vec<Date>>(), vec![Date::from_string("2024-04-10").unwrap()]);
+        assert_eq!(issues(&flags), vec![(DataPoint::TemperatureMax, 100.0, Issue::Outlier{ test: "z_score", score: 3.0 })]);
+        assert!(check(&map, UnitSystem::Imperial, Some(OutlierTest::default())).is_empty());
+        assert!(check(&highs(&[10.0; 6]), UnitSystem::Imperial, Some(OutlierTest::default())).is_empty());
+    }
+
+    #[test]
+    fn iqr_outliers(){
+        // Quartiles of 12 and 16, 0 is 3 ranges below and 50 is 8.5 above
+        let map = highs(&[0.0, 11.0, 12.0, 13.0, 14.0, 15.0, 16.0, 17.0, 50.0]);
+        let flags = check(&map, UnitSystem::Imperial, Some(OutlierTest::Iqr{ k: 1.5 }));
+        assert_eq!(issues(&flags), vec![
+            (DataPoint::TemperatureMax, 0.0, Issue::Outlier{ test: "iqr", score: -3.0 }),
+            (DataPoint::TemperatureMax, 50.0, Issue::Outlier{ test: "iqr", score: 8.5 }),
+        ]);
+        let flags = check(&map, UnitSystem::Imperial, Some(OutlierTest::Iqr{ k: 5.0 }));
+        assert_eq!(issues(&flags), vec![(DataPoint::TemperatureMax, 50.0, Issue::Outlier{ test: "iqr", score: 8.5 })]);
+    }
+
+    #[test]
+    fn too_few_values_for_outliers(){
+        let test = Some(OutlierTest::Iqr{ k: 1.5 });
+        assert!(check(&highs(&[10.0, 11.0, 12.0, 90.0]), UnitSystem::Imperial, test).is_empty());
+        // Implausible values don't count towards the five, and aren't flagged again as outliers
+        let flags = check(&highs(&[10.0, 11.0, 12.0, 13.0, 500.0]), UnitSystem::Imperial, test);
+        assert_eq!(issues(&flags), vec![(DataPoint::TemperatureMax, 500.0, Issue::Implausible{ reason: Implausible::TooHigh })]);
+        let flags = check(&highs(&[10.0, 11.0, 12.0, 13.0, 14.0, 90.0]), UnitSystem::Imperial, test);
+        assert_eq!(flags.len(), 1);
+    }
+}
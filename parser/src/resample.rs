@@ -0,0 +1,186 @@
+use indexmap::IndexMap;
+
+use crate::{days_in_month, is_leap_year, stats, DataPoint, Date, WeatherData, WeatherDataMap};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+/// Calendar period days are grouped by in [`DataOps::resample`](crate::DataOps::resample)
+pub enum Period{
+    /// ISO 8601 week, starting on Monday
+    Week,
+    Month,
+    Year,
+}
+
+impl Period{
+    /// Reads a period from its name ("week", "month" or "year"), ignoring case
+    pub fn from_name(name: &str) -> Option<Period>{
+        match name.to_ascii_lowercase().as_str(){
+            "week" => Some(Period::Week),
+            "month" => Some(Period::Month),
+            "year" => Some(Period::Year),
+            _ => None,
+        }
+    }
+    pub fn name(&self) -> &'static str{
+        match self{
+            Period::Week => "week",
+            Period::Month => "month",
+            Period::Year => "year",
+        }
+    }
+    /// First day of the period a date falls in
+    pub fn start(&self, date: &Date) -> Date{
+        match self{
+            Period::Week => date.add_days(1 - date.weekday().number_from_monday() as i64).unwrap_or(*date),
+            Period::Month => Date{ day: 1, ..*date },
+            Period::Year => Date{ month: 1, day: 1, ..*date },
+        }
+    }
+    /// Number of days in the period starting on the given date
+    pub fn days(&self, start: &Date) -> u16{
+        match self{
+            Period::Week => 7,
+            Period::Month => days_in_month(start.year, start.month) as u16,
+            Period::Year if is_leap_year(start.year) => 366,
+            Period::Year => 365,
+        }
+    }
+    /// Short label for the period starting on the given date: `2024-W17`, `2024-04` or `2024`
+    pub fn label(&self, start: &Date) -> String{
+        match self{
+            Period::Week => {
+                let (year, week) = start.iso_week();
+                format!("{:04}-W{:02}", year, week)
+            }
+            Period::Month => format!("{:04}-{:02}", start.year, start.month),
+            Period::Year => format!("{:04}", start.year),
+        }
+    }
+}
+
+impl std::fmt::Display for Period{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result{
+        write!(f, "{}", self.name())
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+/// How [`DataOps::resample`](crate::DataOps::resample) deals with days that are missing from a period
+pub enum ResamplePolicy{
+    /// Aggregates whatever values a period has, a value is only missing if every day of the period is missing it
+    #[default]
+    SkipMissing,
+    /// A value is missing unless every day of the period has it, so partial periods at the edges of the data are left empty
+    RequireComplete,
+}
+
+/// Aggregates the days of one period into a single entry dated on the period's first day
+fn aggregate(start: Date, days: &WeatherDataMap, expected: usize, policy: ResamplePolicy) -> WeatherData{
+    let complete = |point: DataPoint| policy == ResamplePolicy::SkipMissing || stats::count(days, point) == expected;
+    let mut data = WeatherData::empty(start);
+    for point in DataPoint::ALL{
+        if !complete(point){
+            continue;
+        }
+        let value = match point{
+            DataPoint::WeatherCode => {
+                data.weather_code = stats::mode_weather_code(days);
+                continue;
+            }
+            DataPoint::TemperatureMax => stats::mean(days, point),
+            DataPoint::TemperatureMin => stats::argmin(days, point).map(|min| min.value),
+            DataPoint::PrecipitationSum => stats::sum(days, point),
+            DataPoint::WindSpeedMax | DataPoint::PrecipitationProbabilityMax => stats::argmax(days, point).map(|max| max.value),
+            DataPoint::Date => None,
+        };
+        data.set_value(point, value);
+    }
+    data
+}
+
+pub(crate) fn resample(map: &WeatherDataMap, period: Period, policy: ResamplePolicy) -> WeatherDataMap{
+    let mut buckets: IndexMap<Date, WeatherDataMap> = IndexMap::new();
+    for (date, data) in map.iter(){
+        buckets.entry(period.start(date)).or_default().insert(*date, data.clone());
+    }
+    buckets.sort_keys();
+    buckets
+        .iter()
+        .map(|(start, days)| (*start, aggregate(*start, days, period.days(start) as usize, policy)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn date(date: &str) -> Date{
+        Date::from_string(date).unwrap()
+    }
+
+    /// Consecutive days from `first`, one per temperature, with 1 of precipitation and a wind speed of 10 times the day's index
+    fn days(first: &str, temps: &[Option<f32>]) -> WeatherDataMap{
+        let first = date(first);
+        temps.iter().enumerate().map(|(i, temp)| {
+            let date = first.add_days(i as i64).unwrap();
+            let data = WeatherData{ temp_max: *temp, temp_min: temp.map(|temp| temp - 10.0), precip_sum: Some(1.0), max_wind: Some(10.0 * i as f32), ..WeatherData::empty(date) };
+            (date, data)
+        }).collect()
+    }
+
+    #[test]
+    fn weeks_start_on_monday(){
+        assert_eq!(Period::Week.start(&date("2024-04-25")), date("2024-04-22"));
+        assert_eq!(Period::Week.start(&date("2024-04-22")), date("2024-04-22"));
+        assert_eq!(Period::Week.start(&date("2024-04-28")), date("2024-04-22"));
+        assert_eq!(Period::Week.start(&date("2025-01-01")), date("2024-12-30"));
+        assert_eq!(Period::Week.label(&date("2024-12-30")), "2025-W01");
+
+        let weeks = resample(&days("2024-04-20", &[Some(10.0), Some(20.0), Some(30.0), Some(40.0)]), Period::Week, ResamplePolicy::SkipMissing);
+        assert_eq!(weeks.keys().copied().collect::<Vec<Date>>(), vec![date("2024-04-15"), date("2024-04-22")]);
+        let first = &weeks[&date("2024-04-15")];
+        assert_eq!((first.temp_max, first.temp_min, first.precip_sum, first.max_wind), (Some(15.0), Some(0.0), Some(2.0), Some(10.0)));
+        let second = &weeks[&date("2024-04-22")];
+        assert_eq!((second.temp_max, second.temp_min, second.precip_sum, second.max_wind), (Some(35.0), Some(20.0), Some(2.0), Some(30.0)));
+    }
+
+    #[test]
+    fn month_and_year_boundaries(){
+        assert_eq!(Period::Month.days(&date("2024-02-01")), 29);
+        assert_eq!(Period::Month.days(&date("2023-02-01")), 28);
+        assert_eq!((Period::Year.days(&date("2024-01-01")), Period::Year.days(&date("2023-01-01"))), (366, 365));
+
+        let map = days("2024-02-28", &[Some(1.0), Some(2.0), Some(3.0)]);
+        let months = resample(&map, Period::Month, ResamplePolicy::SkipMissing);
+        assert_eq!(months.keys().copied().collect::<Vec<Date>>(), vec![date("2024-02-01"), date("2024-03-01")]);
+        assert_eq!(months[&date("2024-02-01")].precip_sum, Some(2.0));
+        assert_eq!(Period::Month.label(&date("2024-03-01")), "2024-03");
+
+        let years = resample(&days("2023-12-31", &[Some(1.0), Some(2.0)]), Period::Year, ResamplePolicy::SkipMissing);
+        assert_eq!(years.keys().copied().collect::<Vec<Date>>(), vec![date("2023-01-01"), date("2024-01-01")]);
+        assert_eq!((years[&date("2023-01-01")].temp_max, years[&date("2024-01-01")].temp_max), (Some(1.0), Some(2.0)));
+        assert_eq!(Period::Year.label(&date("2024-01-01")), "2024");
+    }
+
+    #[test]
+    fn policy_for_missing_values(){
+        let mut temps = vec![Some(10.0); 7];
+        temps[2] = None;
+        temps[4] = Some(40.0);
+        let map = days("2024-04-22", &temps);
+
+        let week = &resample(&map, Period::Week, ResamplePolicy::SkipMissing)[&date("2024-04-22")];
+        assert_eq!((week.temp_max, week.precip_sum), (Some(15.0), Some(7.0)));
+        let week = &resample(&map, Period::Week, ResamplePolicy::RequireComplete)[&date("2024-04-22")];
+        assert_eq!((week.temp_max, week.temp_min, week.precip_sum, week.max_wind), (None, None, Some(7.0), Some(60.0)));
+
+        let partial = days("2024-04-22", &[Some(10.0), Some(20.0)]);
+        let week = &resample(&partial, Period::Week, ResamplePolicy::SkipMissing)[&date("2024-04-22")];
+        assert_eq!((week.temp_max, week.precip_sum), (Some(15.0), Some(2.0)));
+        let week = &resample(&partial, Period::Week, ResamplePolicy::RequireComplete)[&date("2024-04-22")];
+        assert_eq!((week.temp_max, week.precip_sum, week.weather_code), (None, None, None));
+
+        let empty = days("2024-04-22", &[None, None]);
+        assert_eq!(resample(&empty, Period::Week, ResamplePolicy::SkipMissing)[&date("2024-04-22")].temp_max, None);
+    }
+}
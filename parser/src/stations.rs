@@ -0,0 +1,283 @@
+use std::io::BufRead;
+
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+
+use crate::{DataOps, ParseDiagnostic, ParseError, ParseOptions, ParseReport, Problems, UnitSystem, WeatherData, WeatherDataMap};
+
+/// Id of the station that data without a `station:` line belongs to
+pub const DEFAULT_STATION: &str = "default";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+/// A place weather data is recorded at, along with the data recorded there
+pub struct Station{
+    /// Short name the station is looked up by, unique within a [`Dataset`]
+    pub id: String,
+    /// Full name of the station, e.g. "Lawrence Municipal Airport"
+    pub name: Option<String>,
+    /// Degrees north of the equator, negative in the southern hemisphere
+    pub latitude: Option<f64>,
+    /// Degrees east of the prime meridian, negative in the western hemisphere
+    pub longitude: Option<f64>,
+    /// Meters above sea level
+    pub elevation: Option<f64>,
+    /// Units the data is recorded in, None if the data doesn't say
+    pub units: Option<UnitSystem>,
+    #[serde(default, with = "crate::records")]
+    pub data: WeatherDataMap,
+}
+
+impl Station{
+    /// Creates a station with the given id and data, and nothing known about where it is
+    pub fn new(id: String, data: WeatherDataMap) -> Station{
+        Station{
+            id,
+            name: None,
+            latitude: None,
+            longitude: None,
+            elevation: None,
+            units: None,
+            data,
+        }
+    }
+
+    /// Whether this is the station of data that never said what station it's from
+    fn is_implicit(&self) -> bool{
+        self.id == DEFAULT_STATION && self.name.is_none() && self.latitude.is_none() && self.longitude.is_none() && self.elevation.is_none()
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+/// Weather data from any number of stations, keyed by station id in the order they were added
+pub struct Dataset{
+    pub stations: IndexMap<String, Station>,
+}
+
+#[derive(Debug, Clone)]
+/// Stations that were parsed successfully, along with every problem that was skipped over to get them
+pub struct DatasetReport{
+    pub dataset: Dataset,
+    pub problems: Vec<ParseDiagnostic>,
+}
+
+impl From<ParseReport> for DatasetReport{
+    /// Data read from a single station format (CSV, Open-Meteo...) as the [`DEFAULT_STATION`]
+    fn from(report: ParseReport) -> DatasetReport{
+        let mut station = Station::new(DEFAULT_STATION.to_string(), report.data);
+        station.units = report.units;
+        let mut dataset = Dataset::new();
+        dataset.insert(station);
+        DatasetReport{
+            dataset,
+            problems: report.problems,
+        }
+    }
+}
+
+/// The lines of one station in the multi-station text format, before its data is parsed
+struct Section{
+    station: Station,
+    /// Line number of the `station:` line, 0 for data before the first one
+    offset: usize,
+    /// The lines after the `station:` line, with the station's own lines blanked out so line numbers still match
+    body: String,
+    /// A station whose id was already used, read only to keep going in lenient mode
+    skip: bool,
+}
+
+impl Section{
+    fn new(id: &str, offset: usize) -> Section{
+        Section{
+            station: Station::new(id.to_string(), WeatherDataMap::new()),
+            offset,
+            body: String::new(),
+            skip: false,
+        }
+    }
+}
+
+impl Dataset{
+    pub fn new() -> Dataset{
+        Dataset::default()
+    }
+
+    /// Adds a station, returning the station it replaces if one with the same id was already there
+    pub fn insert(&mut self, station: Station) -> Option<Station>{
+        self.stations.insert(station.id.clone(), station)
+    }
+
+    pub fn get(&self, id: &str) -> Option<&Station>{
+        self.stations.get(id)
+    }
+
+    pub fn get_mut(&mut self, id: &str) -> Option<&mut Station>{
+        self.stations.get_mut(id)
+    }
+
+    /// Parses weather data from several stations in the column text format, see [`Dataset::from_data_with`]
+    /// # Errors
+    /// Returns a [`ParseDiagnostic`] pointing at the first problem found in the data
+    pub fn from_data(data: String) -> Result<Dataset, ParseDiagnostic>{
+        Ok(Dataset::from_data_with(data, ParseOptions::default())?.dataset)
+    }
+
+    /// Parses weather data from several stations with the given options
+    ///
+    /// Each station starts with a `station: <id>` line, followed by optional `name:`, `latitude:`, `longitude:` and
+    /// `elevation:` lines and then its data in the column text format (see [`WeatherData::from_data`]), `units:` line
+    /// included. Anything before the first `station:` line belongs to the [`DEFAULT_STATION`], so data written for a
+    /// single place is read as a dataset with just that station
+    ///
+    /// Line numbers in problems count from the start of the whole input
+    /// # Errors
+    /// In [`ParseMode::Strict`](crate::ParseMode::Strict), returns the first problem found in the data
+    /// # Examples
+    /// ```
+    /// use parser::{Dataset, Date};
+    /// let data = "station: lawrence
+    /// name: Lawrence, KS
+    /// latitude: 38.97
+    /// longitude: -95.24
+    /// date: 2024-04-24 2024-04-25
+    /// weather_code: 3 61
+    /// temperature_max: 60 51
+    /// temperature_min: 47.1 44.2
+    /// precipitation_sum: 0.1 0.22
+    /// wind_speed_max: 9.3 10.1
+    /// precipitation_probability_max: 45 100
+    ///
+    /// station: topeka
+    /// units: metric
+    /// date: 2024-04-24
+    /// weather_code: 3
+    /// temperature_max: 15.5
+    /// temperature_min: 8.2
+    /// precipitation_sum: 2.5
+    /// wind_speed_max: 15
+    /// precipitation_probability_max: 40".to_string();
+    ///
+    /// let dataset = Dataset::from_data(data).unwrap();
+    /// assert_eq!(dataset.stations.len(), 2);
+    /// let lawrence = dataset.get("lawrence").unwrap();
+    /// assert_eq!(lawrence.name.as_deref(), Some("Lawrence, KS"));
+    /// assert_eq!(lawrence.data.len(), 2);
+    /// assert_eq!(dataset.get("topeka").unwrap().data[&Date::from_string("2024-04-24").unwrap()].temp_max, Some(15.5));
+    /// // Written back the same way
+    /// assert_eq!(Dataset::from_data(dataset.to_file()).unwrap().stations.len(), 2);
+    /// ```
+    pub fn from_data_with(data: String, options: ParseOptions) -> Result<DatasetReport, ParseDiagnostic>{
+        Dataset::from_reader_with(data.as_bytes(), options)
+    }
+
+    /// Same as [`Dataset::from_data_with`], but reads the data line by line from any buffered reader
+    pub fn from_reader_with<R: BufRead>(reader: R, options: ParseOptions) -> Result<DatasetReport, ParseDiagnostic>{
+        let mut problems = Problems{
+            mode: options.mode,
+            found: vec![],
+        };
+        let mut sections: Vec<Section> = vec![];
+        let mut current = Section::new(DEFAULT_STATION, 0);
+        for (i, line) in reader.lines().enumerate(){
+            let number = i + 1;
+            let line = match line{
+                Ok(line) => line,
+                Err(e) => {
+                    problems.report(ParseDiagnostic::on_line(ParseError::Io(e.to_string()), number, None))?;
+                    break;
+                }
+            };
+            let header = line.split_once(':').map(|(key, value)| (key.trim(), value.trim()));
+            match header{
+                Some(("station", id)) => {
+                    // Lines before the first station only make a station of their own if there's data in them
+                    if current.offset > 0 || !current.body.trim().is_empty(){
+                        sections.push(current);
+                    }
+                    current = Section::new(id, number);
+                    if id.is_empty(){
+                        problems.report(ParseDiagnostic::on_line(ParseError::InvalidLine(line.clone()), number, None))?;
+                        current.skip = true;
+                    } else if sections.iter().any(|section| !section.skip && section.station.id == id){
+                        problems.report(ParseDiagnostic::on_line(ParseError::DuplicateStation(id.to_string()), number, None))?;
+                        current.skip = true;
+                    }
+                    continue;
+                }
+                Some(("name", name)) => {
+                    // Like the `units:` line, only the first one counts
+                    match current.station.name{
+                        Some(_) => problems.report(ParseDiagnostic::on_line(ParseError::InvalidLine(line.clone()), number, None))?,
+                        None => current.station.name = Some(name.to_string()),
+                    }
+                    current.body.push('\n');
+                }
+                Some((key @ ("latitude" | "longitude" | "elevation"), value)) => {
+                    let (coordinate, limit) = match key{
+                        "latitude" => (&mut current.station.latitude, 90.0),
+                        "longitude" => (&mut current.station.longitude, 180.0),
+                        _ => (&mut current.station.elevation, f64::INFINITY),
+                    };
+                    match value.parse::<f64>(){
+                        _ if coordinate.is_some() => problems.report(ParseDiagnostic::on_line(ParseError::InvalidLine(line.clone()), number, None))?,
+                        Ok(value) if value.is_finite() && value.abs() <= limit => *coordinate = Some(value),
+                        _ => problems.report(ParseDiagnostic::on_line(ParseError::InvalidCoordinate(value.to_string()), number, None))?,
+                    }
+                    current.body.push('\n');
+                }
+                _ => {
+                    current.body.push_str(&line);
+                    current.body.push('\n');
+                }
+            }
+        }
+        sections.push(current);
+
+        let mut dataset = Dataset::new();
+        for section in sections.into_iter().filter(|section| !section.skip){
+            // Problems are moved to where they are in the whole input
+            let offset = |mut problem: ParseDiagnostic| {
+                problem.line = problem.line.map(|line| line + section.offset);
+                problem
+            };
+            let report = WeatherData::from_data_with(section.body, options).map_err(offset)?;
+            problems.found.extend(report.problems.into_iter().map(offset));
+            let mut station = section.station;
+            station.data = report.data;
+            station.units = report.units;
+            dataset.insert(station);
+        }
+        Ok(DatasetReport{
+            dataset,
+            problems: problems.found,
+        })
+    }
+
+    /// Writes every station in the format read by [`Dataset::from_data_with`], with a blank line between stations
+    ///
+    /// A dataset with only the [`DEFAULT_STATION`] and nothing known about it is written without a `station:` line,
+    /// the same as [`DataOps::to_file`]
+    pub fn to_file(&self) -> String{
+        let mut sections: Vec<String> = Vec::with_capacity(self.stations.len());
+        for station in self.stations.values(){
+            let data = match station.units{
+                Some(units) => station.data.to_file_in(units),
+                None => station.data.to_file(),
+            };
+            if self.stations.len() == 1 && station.is_implicit(){
+                return data;
+            }
+            let mut section = format!("station: {}\n", station.id);
+            if let Some(name) = &station.name{
+                section.push_str(&format!("name: {}\n", name));
+            }
+            for (key, value) in [("latitude", station.latitude), ("longitude", station.longitude), ("elevation", station.elevation)]{
+                if let Some(value) = value{
+                    section.push_str(&format!("{}: {}\n", key, value));
+                }
+            }
+            section.push_str(&data);
+            sections.push(section);
+        }
+        sections.join("\n\n")
+    }
+}
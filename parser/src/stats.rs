@@ -0,0 +1,322 @@
+//! Statistics over one field of a [`WeatherDataMap`], usually a range taken with [`DataOps::take_range`](crate::DataOps::take_range)
+//!
+//! Missing values are left out of every statistic, and every function returns None when there are no values
+//! left, rather than panicking on an empty range
+//! # Examples
+//! ```
+//! use parser::{stats, DataPoint, Date, WeatherData};
+//! let data = WeatherData::from_data("date: 2024-04-24 2024-04-25 2024-04-26
+//! weather_code: 3 61 3
+//! temperature_max: 61.9 52.6 -
+//! temperature_min: 47.1 44.2 40.0
+//! precipitation_sum: 0 0.22 0.1
+//! wind_speed_max: 9.3 10.1 8.2
+//! precipitation_probability_max: 45 100 20".to_string()).unwrap();
+//!
+//! assert_eq!(stats::count(&data, DataPoint::TemperatureMax), 2);
+//! assert_eq!(stats::median(&data, DataPoint::TemperatureMin), Some(44.2));
+//! let coldest = stats::argmin(&data, DataPoint::TemperatureMin).unwrap();
+//! assert_eq!(coldest.date, Date::from_string("2024-04-26").unwrap());
+//! assert_eq!(stats::mode_weather_code(&data).unwrap().code(), 3);
+//! ```
+
+use indexmap::IndexMap;
+use serde::Serialize;
+
+use crate::{DataPoint, Date, WeatherCode, WeatherData, WeatherDataMap};
+
+/// Picks the value every statistic works on out of each day, a [`DataPoint`] or a [`Derived`](crate::Derived) value
+pub trait Selector: Copy{
+    fn select(&self, data: &WeatherData) -> Option<f32>;
+}
+
+impl Selector for DataPoint{
+    fn select(&self, data: &WeatherData) -> Option<f32>{
+        data.value(*self)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+/// The value of an extreme and the date it occurred on
+pub struct Extreme{
+    pub value: f32,
+    pub date: Date,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+/// Every statistic of a field at once, see [`summarize`]
+pub struct Summary{
+    /// Number of days with a value
+    pub count: usize,
+    /// Number of days where the value is missing
+    pub missing: usize,
+    pub sum: Option<f32>,
+    pub mean: Option<f32>,
+    pub median: Option<f32>,
+    pub stddev: Option<f32>,
+    pub min: Option<Extreme>,
+    pub max: Option<Extreme>,
+}
+
+/// The values of a field that aren't missing, along with their dates, in the order of the map
+pub fn values(map: &WeatherDataMap, point: impl Selector) -> Vec<(Date, f32)>{
+    map.iter().filter_map(|(date, data)| Some((*date, point.select(data)?))).collect()
+}
+
+/// Values sorted from lowest to highest, for the order statistics
+fn sorted(map: &WeatherDataMap, point: impl Selector) -> Vec<f32>{
+    let mut values: Vec<f32> = values(map, point).into_iter().map(|(_, value)| value).collect();
+    values.sort_by(f32::total_cmp);
+    values
+}
+
+/// Number of days where the field has a value
+pub fn count(map: &WeatherDataMap, point: impl Selector) -> usize{
+    map.values().filter(|data| point.select(data).is_some()).count()
+}
+
+pub fn sum(map: &WeatherDataMap, point: impl Selector) -> Option<f32>{
+    let values = values(map, point);
+    if values.is_empty(){
+        return None;
+    }
+    Some(values.iter().map(|(_, value)| *value as f64).sum::<f64>() as f32)
+}
+
+pub fn mean(map: &WeatherDataMap, point: impl Selector) -> Option<f32>{
+    let values = values(map, point);
+    if values.is_empty(){
+        return None;
+    }
+    let sum: f64 = values.iter().map(|(_, value)| *value as f64).sum();
+    Some((sum / values.len() as f64) as f32)
+}
+
+/// Middle value, or the mean of the two middle values when there's an even number of them
+pub fn median(map: &WeatherDataMap, point: impl Selector) -> Option<f32>{
+    percentile(map, point, 50.0)
+}
+
+/// Population standard deviation (dividing by the number of values, not one less)
+pub fn stddev(map: &WeatherDataMap, point: impl Selector) -> Option<f32>{
+    let values = values(map, point);
+    if values.is_empty(){
+        return None;
+    }
+    let len = values.len() as f64;
+    let mean: f64 = values.iter().map(|(_, value)| *value as f64).sum::<f64>() / len;
+    let variance: f64 = values.iter().map(|(_, value)| (*value as f64 - mean).powi(2)).sum::<f64>() / len;
+    Some(variance.sqrt() as f32)
+}
+
+/// Value below which the given percentage of values fall, interpolating linearly between the closest two values
+///
+/// Percentages are clamped to 0-100, so 0 is the minimum and 100 the maximum
+pub fn percentile(map: &WeatherDataMap, point: impl Selector, percent: f32) -> Option<f32>{
+    let values = sorted(map, point);
+    if values.is_empty(){
+        return None;
+    }
+    let rank = percent.clamp(0.0, 100.0) as f64 / 100.0 * (values.len() - 1) as f64;
+    let (lower, upper) = (rank.floor() as usize, rank.ceil() as usize);
+    let weight = rank - lower as f64;
+    Some((values[lower] as f64 * (1.0 - weight) + values[upper] as f64 * weight) as f32)
+}
+
+/// Lowest value and the date it occurred on, the earliest date if it occurred more than once
+pub fn argmin(map: &WeatherDataMap, point: impl Selector) -> Option<Extreme>{
+    values(map, point)
+        .into_iter()
+        .reduce(|min, value| if value.1 < min.1 || (value.1 == min.1 && value.0 < min.0) { value } else { min })
+        .map(|(date, value)| Extreme{ value, date })
+}
+
+/// Highest value and the date it occurred on, the earliest date if it occurred more than once
+pub fn argmax(map: &WeatherDataMap, point: impl Selector) -> Option<Extreme>{
+    values(map, point)
+        .into_iter()
+        .reduce(|max, value| if value.1 > max.1 || (value.1 == max.1 && value.0 < max.0) { value } else { max })
+        .map(|(date, value)| Extreme{ value, date })
+}
+
+/// Most common weather code, ties going to the code that occurred first
+pub fn mode_weather_code(map: &WeatherDataMap) -> Option<WeatherCode>{
+    // Counted in order of first occurrence, so the first code wins a tie
+    let mut counts: IndexMap<WeatherCode, usize> = IndexMap::new();
+    for code in map.values().filter_map(|data| data.weather_code){
+        *counts.entry(code).or_insert(0) += 1;
+    }
+    counts.into_iter().reduce(|mode, code| if code.1 > mode.1 { code } else { mode }).map(|(code, _)| code)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+/// How the values in a [`rolling`] window are combined
+pub enum RollingOp{
+    Mean,
+    Sum,
+    Min,
+    Max,
+}
+
+impl RollingOp{
+    /// The usual way a field is smoothed: moving averages for temperatures and precipitation chance,
+    /// rolling totals for precipitation and rolling maximums for wind, None for the weather code
+    pub fn for_field(point: DataPoint) -> Option<RollingOp>{
+        match point{
+            DataPoint::TemperatureMax | DataPoint::TemperatureMin | DataPoint::PrecipitationProbabilityMax => Some(RollingOp::Mean),
+            DataPoint::PrecipitationSum => Some(RollingOp::Sum),
+            DataPoint::WindSpeedMax => Some(RollingOp::Max),
+            DataPoint::WeatherCode | DataPoint::Date => None,
+        }
+    }
+}
+
+/// Combines the values of the `days` calendar days up to and including each date, for every date in the data
+///
+/// Windows are counted in calendar days rather than entries, so a gap in the dates shrinks the windows next to it
+/// instead of reaching further back. Missing values are left out, and a window without any values is None.
+/// # Examples
+/// ```
+/// use parser::stats::{rolling, RollingOp};
+/// use parser::{DataPoint, WeatherData};
+/// let data = WeatherData::from_data("date: 2024-04-24 2024-04-25 2024-04-28
+/// weather_code: 3 61 3
+/// temperature_max: 60 50 40
+/// temperature_min: 47.1 44.2 40.0
+/// precipitation_sum: 0 0.25 0.5
+/// wind_speed_max: 9.3 10.1 8.2
+/// precipitation_probability_max: 45 100 20".to_string()).unwrap();
+///
+/// let smoothed: Vec<Option<f32>> = rolling(&data, DataPoint::TemperatureMax, 3, RollingOp::Mean).into_values().collect();
+/// // 2024-04-28 is three days after 2024-04-25, so it's alone in its window
+/// assert_eq!(smoothed, vec![Some(60.0), Some(55.0), Some(40.0)]);
+/// ```
+pub fn rolling(map: &WeatherDataMap, point: impl Selector, days: u16, op: RollingOp) -> IndexMap<Date, Option<f32>>{
+    let mut values = values(map, point);
+    values.sort_by_key(|(date, _)| *date);
+    let days = days.max(1) as i64;
+
+    map.keys()
+        .map(|date| {
+            let first = date.add_days(1 - days).unwrap_or(Date{ year: 1, month: 1, day: 1 });
+            let begin = values.partition_point(|(other, _)| *other < first);
+            let end = values.partition_point(|(other, _)| other <= date);
+            let window = values[begin..end].iter().map(|(_, value)| *value as f64);
+            let combined = match op{
+                _ if begin >= end => None,
+                RollingOp::Mean => Some(window.sum::<f64>() / (end - begin) as f64),
+                RollingOp::Sum => Some(window.sum::<f64>()),
+                RollingOp::Min => window.reduce(f64::min),
+                RollingOp::Max => window.reduce(f64::max),
+            };
+            (*date, combined.map(|value| value as f32))
+        })
+        .collect()
+}
+
+/// Running total of a field from the start of the data up to each date, for adding up degree days or precipitation
+/// over a season. Missing values add nothing, and dates before the first value are None.
+/// # Examples
+/// ```
+/// use parser::{stats, DataPoint, WeatherData};
+/// let data = WeatherData::from_data("date: 2024-04-24 2024-04-25 2024-04-26
+/// weather_code: 3 61 3
+/// temperature_max: 61.9 52.6 40.0
+/// temperature_min: 47.1 44.2 40.0
+/// precipitation_sum: - 0.25 0.5
+/// wind_speed_max: 9.3 10.1 8.2
+/// precipitation_probability_max: 45 100 20".to_string()).unwrap();
+///
+/// let totals: Vec<Option<f32>> = stats::cumulative(&data, DataPoint::PrecipitationSum).into_values().collect();
+/// assert_eq!(totals, vec![None, Some(0.25), Some(0.75)]);
+/// ```
+pub fn cumulative(map: &WeatherDataMap, point: impl Selector) -> IndexMap<Date, Option<f32>>{
+    let mut dates: Vec<&Date> = map.keys().collect();
+    dates.sort();
+    let mut total: Option<f64> = None;
+    let mut totals: IndexMap<Date, Option<f32>> = IndexMap::new();
+    for date in dates{
+        if let Some(value) = point.select(&map[date]){
+            total = Some(total.unwrap_or(0.0) + value as f64);
+        }
+        totals.insert(*date, total.map(|total| total as f32));
+    }
+    // Back in the order of the map
+    map.keys().map(|date| (*date, totals[date])).collect()
+}
+
+/// Computes every statistic of a field over the data
+pub fn summarize(map: &WeatherDataMap, point: impl Selector) -> Summary{
+    let count = count(map, point);
+    Summary{
+        count,
+        missing: map.len() - count,
+        sum: sum(map, point),
+        mean: mean(map, point),
+        median: median(map, point),
+        stddev: stddev(map, point),
+        min: argmin(map, point),
+        max: argmax(map, point),
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    /// Days from 2024-04-01 with the given high temperatures, in the order given rather than by date
+    fn highs(temps: &[(u8, Option<f32>)]) -> WeatherDataMap{
+        temps.iter().map(|(day, temp)| {
+            let date = Date::new(2024, 4, *day).unwrap();
+            (date, WeatherData{ temp_max: *temp, ..WeatherData::empty(date) })
+        }).collect()
+    }
+
+    #[test]
+    fn median_and_percentiles(){
+        let odd = highs(&[(1, Some(50.0)), (2, Some(10.0)), (3, Some(30.0)), (4, Some(20.0)), (5, Some(40.0))]);
+        assert_eq!(median(&odd, DataPoint::TemperatureMax), Some(30.0));
+        assert_eq!(percentile(&odd, DataPoint::TemperatureMax, 25.0), Some(20.0));
+        assert_eq!(percentile(&odd, DataPoint::TemperatureMax, 90.0), Some(46.0));
+
+        let even = highs(&[(1, Some(40.0)), (2, Some(10.0)), (3, Some(30.0)), (4, Some(20.0))]);
+        assert_eq!(median(&even, DataPoint::TemperatureMax), Some(25.0));
+        assert_eq!(percentile(&even, DataPoint::TemperatureMax, 0.0), Some(10.0));
+        assert_eq!(percentile(&even, DataPoint::TemperatureMax, 100.0), Some(40.0));
+        assert_eq!(percentile(&even, DataPoint::TemperatureMax, 150.0), Some(40.0));
+        assert_eq!(percentile(&even, DataPoint::TemperatureMax, -10.0), Some(10.0));
+    }
+
+    #[test]
+    fn stddev_of_one_value(){
+        let one = highs(&[(1, Some(61.9))]);
+        assert_eq!(stddev(&one, DataPoint::TemperatureMax), Some(0.0));
+        assert_eq!(median(&one, DataPoint::TemperatureMax), Some(61.9));
+        let two = highs(&[(1, Some(10.0)), (2, Some(20.0))]);
+        assert_eq!(stddev(&two, DataPoint::TemperatureMax), Some(5.0));
+    }
+
+    #[test]
+    fn missing_values_are_skipped(){
+        let map = highs(&[(1, Some(10.0)), (2, None), (3, Some(20.0)), (4, None), (5, Some(60.0))]);
+        let summary = summarize(&map, DataPoint::TemperatureMax);
+        assert_eq!((summary.count, summary.missing), (3, 2));
+        assert_eq!((summary.sum, summary.mean, summary.median), (Some(90.0), Some(30.0), Some(20.0)));
+        assert_eq!(summary.min.map(|min| min.value), Some(10.0));
+
+        let empty = highs(&[(1, None), (2, None)]);
+        let summary = summarize(&empty, DataPoint::TemperatureMax);
+        assert_eq!((summary.count, summary.missing), (0, 2));
+        assert_eq!((summary.sum, summary.mean, summary.median, summary.stddev, summary.min, summary.max), (None, None, None, None, None, None));
+        assert_eq!(percentile(&empty, DataPoint::TemperatureMax, 50.0), None);
+    }
+
+    #[test]
+    fn ties_go_to_the_earliest_date(){
+        let map = highs(&[(5, Some(20.0)), (3, Some(60.0)), (2, Some(20.0)), (4, Some(60.0)), (6, None)]);
+        let min = argmin(&map, DataPoint::TemperatureMax).unwrap();
+        let max = argmax(&map, DataPoint::TemperatureMax).unwrap();
+        assert_eq!((min.value, min.date), (20.0, Date::new(2024, 4, 2).unwrap()));
+        assert_eq!((max.value, max.date), (60.0, Date::new(2024, 4, 3).unwrap()));
+    }
+}
@@ -0,0 +1,139 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{DataPoint, WeatherData, WeatherDataMap};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+/// Units the values of weather data are recorded in
+///
+/// Weather codes and precipitation probabilities (a percentage) are the same in both systems
+pub enum UnitSystem{
+    /// Degrees Celsius, millimeters and kilometers per hour
+    Metric,
+    /// Degrees Fahrenheit, inches and miles per hour, what the sample data is recorded in
+    #[default]
+    Imperial,
+}
+
+impl UnitSystem{
+    /// Reads a unit system from its name ("metric" or "imperial"), ignoring case
+    pub fn from_name(name: &str) -> Option<UnitSystem>{
+        match name.to_ascii_lowercase().as_str(){
+            "metric" => Some(UnitSystem::Metric),
+            "imperial" => Some(UnitSystem::Imperial),
+            _ => None,
+        }
+    }
+    /// Name of the unit system, as used in the `units:` line of the text format
+    pub fn name(&self) -> &'static str{
+        match self{
+            UnitSystem::Metric => "metric",
+            UnitSystem::Imperial => "imperial",
+        }
+    }
+    /// The other unit system
+    pub fn other(&self) -> UnitSystem{
+        match self{
+            UnitSystem::Metric => UnitSystem::Imperial,
+            UnitSystem::Imperial => UnitSystem::Metric,
+        }
+    }
+    /// Symbol of the unit a field is measured in, empty for weather codes and dates
+    pub fn unit(&self, point: DataPoint) -> &'static str{
+        match (self, point){
+            (UnitSystem::Metric, DataPoint::TemperatureMax | DataPoint::TemperatureMin) => "°C",
+            (UnitSystem::Imperial, DataPoint::TemperatureMax | DataPoint::TemperatureMin) => "°F",
+            (UnitSystem::Metric, DataPoint::PrecipitationSum) => "mm",
+            (UnitSystem::Imperial, DataPoint::PrecipitationSum) => "in",
+            (UnitSystem::Metric, DataPoint::WindSpeedMax) => "km/h",
+            (UnitSystem::Imperial, DataPoint::WindSpeedMax) => "mph",
+            (_, DataPoint::PrecipitationProbabilityMax) => "%",
+            (_, DataPoint::WeatherCode | DataPoint::Date) => "",
+        }
+    }
+    /// Converts a value of a field from this unit system to another one
+    /// # Examples
+    /// ```
+    /// use parser::{DataPoint, UnitSystem};
+    /// assert_eq!(UnitSystem::Imperial.convert(212.0, DataPoint::TemperatureMax, UnitSystem::Metric), 100.0);
+    /// assert_eq!(UnitSystem::Metric.convert(25.4, DataPoint::PrecipitationSum, UnitSystem::Imperial), 1.0);
+    /// assert_eq!(UnitSystem::Metric.convert(61.0, DataPoint::WeatherCode, UnitSystem::Imperial), 61.0);
+    /// ```
+    pub fn convert(&self, value: f32, point: DataPoint, to: UnitSystem) -> f32{
+        match (self, to, point){
+            (UnitSystem::Imperial, UnitSystem::Metric, DataPoint::TemperatureMax | DataPoint::TemperatureMin) => fahrenheit_to_celsius(value),
+            (UnitSystem::Metric, UnitSystem::Imperial, DataPoint::TemperatureMax | DataPoint::TemperatureMin) => celsius_to_fahrenheit(value),
+            (UnitSystem::Imperial, UnitSystem::Metric, DataPoint::PrecipitationSum) => inches_to_millimeters(value),
+            (UnitSystem::Metric, UnitSystem::Imperial, DataPoint::PrecipitationSum) => millimeters_to_inches(value),
+            (UnitSystem::Imperial, UnitSystem::Metric, DataPoint::WindSpeedMax) => mph_to_kmh(value),
+            (UnitSystem::Metric, UnitSystem::Imperial, DataPoint::WindSpeedMax) => kmh_to_mph(value),
+            _ => value,
+        }
+    }
+}
+
+impl std::fmt::Display for UnitSystem{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result{
+        write!(f, "{}", self.name())
+    }
+}
+
+pub fn fahrenheit_to_celsius(fahrenheit: f32) -> f32{
+    (fahrenheit - 32.0) * 5.0 / 9.0
+}
+
+pub fn celsius_to_fahrenheit(celsius: f32) -> f32{
+    celsius * 9.0 / 5.0 + 32.0
+}
+
+pub fn inches_to_millimeters(inches: f32) -> f32{
+    inches * 25.4
+}
+
+pub fn millimeters_to_inches(millimeters: f32) -> f32{
+    millimeters / 25.4
+}
+
+pub fn mph_to_kmh(mph: f32) -> f32{
+    mph * 1.609344
+}
+
+pub fn kmh_to_mph(kmh: f32) -> f32{
+    kmh / 1.609344
+}
+
+/// Reads a unit symbol as written by Open-Meteo in `daily_units`, returning its system and the factor that
+/// brings a value to that system's unit (m/s and knots are both read as metric and scaled to km/h)
+pub(crate) fn read_unit(unit: &str) -> Option<(UnitSystem, f32)>{
+    match unit{
+        "°C" => Some((UnitSystem::Metric, 1.0)),
+        "°F" => Some((UnitSystem::Imperial, 1.0)),
+        "mm" => Some((UnitSystem::Metric, 1.0)),
+        "cm" => Some((UnitSystem::Metric, 10.0)),
+        "inch" | "in" => Some((UnitSystem::Imperial, 1.0)),
+        "km/h" => Some((UnitSystem::Metric, 1.0)),
+        "m/s" => Some((UnitSystem::Metric, 3.6)),
+        "kn" => Some((UnitSystem::Metric, 1.852)),
+        "mp/h" | "mph" => Some((UnitSystem::Imperial, 1.0)),
+        _ => None,
+    }
+}
+
+impl WeatherData{
+    /// Returns a copy of the entry with its values converted from one unit system to another
+    pub fn converted(&self, from: UnitSystem, to: UnitSystem) -> WeatherData{
+        let mut converted = self.clone();
+        for point in DataPoint::ALL{
+            converted.set_value(point, self.value(point).map(|value| from.convert(value, point, to)));
+        }
+        converted
+    }
+}
+
+/// Returns a copy of the map with every entry converted, see [`DataOps::convert_units`](crate::DataOps::convert_units)
+pub(crate) fn convert_map(map: &WeatherDataMap, from: UnitSystem, to: UnitSystem) -> WeatherDataMap{
+    if from == to{
+        return map.clone();
+    }
+    map.iter().map(|(date, data)| (*date, data.converted(from, to))).collect()
+}
@@ -0,0 +1,148 @@
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{ParseError, ParseResult};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+/// Coarse kind of weather a [`WeatherCode`] falls under
+pub enum WeatherCategory{
+    Clear,
+    Cloudy,
+    Fog,
+    Drizzle,
+    Rain,
+    Snow,
+    Thunderstorm,
+}
+
+impl WeatherCategory{
+    /// Lowercase name of the category, as used in JSON
+    pub fn name(&self) -> &'static str{
+        match self{
+            WeatherCategory::Clear => "clear",
+            WeatherCategory::Cloudy => "cloudy",
+            WeatherCategory::Fog => "fog",
+            WeatherCategory::Drizzle => "drizzle",
+            WeatherCategory::Rain => "rain",
+            WeatherCategory::Snow => "snow",
+            WeatherCategory::Thunderstorm => "thunderstorm",
+        }
+    }
+}
+
+impl std::fmt::Display for WeatherCategory{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result{
+        write!(f, "{}", self.name())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+/// A WMO 4677 weather interpretation code, limited to the codes used for daily weather (the ones Open-Meteo reports)
+///
+/// Serializes as the bare number, and deserializing rejects codes that aren't defined
+/// # Examples
+/// ```
+/// use parser::{WeatherCategory, WeatherCode};
+/// let code = WeatherCode::new(63).unwrap();
+/// assert_eq!(code.description(), "Moderate rain");
+/// assert_eq!(code.category(), WeatherCategory::Rain);
+/// assert!(WeatherCode::new(4).is_err());
+/// ```
+pub struct WeatherCode(u8);
+
+/// Description and category of every defined code
+fn describe(code: u8) -> Option<(&'static str, WeatherCategory)>{
+    use WeatherCategory::*;
+    let described = match code{
+        0 => ("Clear sky", Clear),
+        1 => ("Mainly clear", Clear),
+        2 => ("Partly cloudy", Cloudy),
+        3 => ("Overcast", Cloudy),
+        45 => ("Fog", Fog),
+        48 => ("Depositing rime fog", Fog),
+        51 => ("Light drizzle", Drizzle),
+        53 => ("Moderate drizzle", Drizzle),
+        55 => ("Dense drizzle", Drizzle),
+        56 => ("Light freezing drizzle", Drizzle),
+        57 => ("Dense freezing drizzle", Drizzle),
+        61 => ("Slight rain", Rain),
+        63 => ("Moderate rain", Rain),
+        65 => ("Heavy rain", Rain),
+        66 => ("Light freezing rain", Rain),
+        67 => ("Heavy freezing rain", Rain),
+        71 => ("Slight snow fall", Snow),
+        73 => ("Moderate snow fall", Snow),
+        75 => ("Heavy snow fall", Snow),
+        77 => ("Snow grains", Snow),
+        80 => ("Slight rain showers", Rain),
+        81 => ("Moderate rain showers", Rain),
+        82 => ("Violent rain showers", Rain),
+        85 => ("Slight snow showers", Snow),
+        86 => ("Heavy snow showers", Snow),
+        95 => ("Thunderstorm", Thunderstorm),
+        96 => ("Thunderstorm with slight hail", Thunderstorm),
+        99 => ("Thunderstorm with heavy hail", Thunderstorm),
+        _ => return None,
+    };
+    Some(described)
+}
+
+impl WeatherCode{
+    /// Creates a weather code, checking that it's defined
+    /// # Errors
+    /// Returns [`ParseError::InvalidWeatherCode`] for codes that aren't defined
+    pub fn new(code: u8) -> ParseResult<WeatherCode>{
+        match describe(code){
+            Some(_) => Ok(WeatherCode(code)),
+            None => Err(ParseError::InvalidWeatherCode(code.to_string())),
+        }
+    }
+    /// Reads a weather code from a number, truncating the decimal part the same way the text format does
+    pub fn from_value(value: f32) -> Option<WeatherCode>{
+        if !(0.0..256.0).contains(&value){
+            return None;
+        }
+        WeatherCode::new(value as u8).ok()
+    }
+    pub fn code(&self) -> u8{
+        self.0
+    }
+    /// Short description of the weather, e.g. "Moderate rain"
+    pub fn description(&self) -> &'static str{
+        describe(self.0).expect("weather codes are checked when created").0
+    }
+    pub fn category(&self) -> WeatherCategory{
+        describe(self.0).expect("weather codes are checked when created").1
+    }
+}
+
+impl From<WeatherCode> for u8{
+    fn from(code: WeatherCode) -> u8{
+        code.0
+    }
+}
+
+impl From<WeatherCode> for f32{
+    fn from(code: WeatherCode) -> f32{
+        f32::from(code.0)
+    }
+}
+
+impl std::fmt::Display for WeatherCode{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result{
+        write!(f, "{}", self.0)
+    }
+}
+
+impl Serialize for WeatherCode{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error>{
+        serializer.serialize_u8(self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for WeatherCode{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error>{
+        let code = u8::deserialize(deserializer)?;
+        WeatherCode::new(code).map_err(D::Error::custom)
+    }
+}
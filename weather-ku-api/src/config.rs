@@ -0,0 +1,255 @@
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use clap::Parser;
+use serde::Deserialize;
+
+use parser::ParseMode;
+
+use crate::cors::Cors;
+
+/// Serves weather data from a file over HTTP
+///
+/// Every setting can also be given with the environment variable listed under it, or in a TOML config file.
+/// Flags override the environment, which overrides the config file
+#[derive(Parser)]
+#[command(version)]
+struct Cli {
+    /// File the weather data is read from and saved back to
+    #[arg(env = "WEATHER_KU_DATA_FILE")]
+    data_file: Option<PathBuf>,
+    /// TOML file with any of the settings below, flags and environment variables take precedence over it
+    #[arg(short, long, env = "WEATHER_KU_CONFIG")]
+    config: Option<PathBuf>,
+    /// Address to listen on [default: 127.0.0.1]
+    #[arg(long, env = "WEATHER_KU_BIND")]
+    bind: Option<IpAddr>,
+    /// Port to listen on [default: 3000]
+    #[arg(short, long, env = "WEATHER_KU_PORT")]
+    port: Option<u16>,
+    /// File server activity is logged to [default: log.txt]
+    #[arg(long, env = "WEATHER_KU_LOG_FILE")]
+    log_file: Option<PathBuf>,
+    /// Seconds between saves of the data file [default: 15]
+    #[arg(long, env = "WEATHER_KU_SAVE_INTERVAL", value_parser = clap::value_parser!(u64).range(1..))]
+    save_interval: Option<u64>,
+    /// Refuses every request that changes the data, and never writes to the data file
+    #[arg(long, env = "WEATHER_KU_READ_ONLY", num_args = 0..=1, default_missing_value = "true")]
+    read_only: Option<bool>,
+    /// Skips the days of the data file that can't be read, logging them, instead of refusing to start
+    #[arg(long, env = "WEATHER_KU_LENIENT", num_args = 0..=1, default_missing_value = "true")]
+    lenient: Option<bool>,
+    /// Comma separated origins of the web pages allowed to call the API, * for any [default: *]
+    #[arg(long, env = "WEATHER_KU_ALLOWED_ORIGINS", value_delimiter = ',')]
+    allowed_origins: Option<Vec<String>>,
+    /// Lets the allowed pages send cookies and authorization with their requests, only with a list of origins rather than *
+    #[arg(long, env = "WEATHER_KU_ALLOW_CREDENTIALS", num_args = 0..=1, default_missing_value = "true")]
+    allow_credentials: Option<bool>,
+}
+
+/// Settings of the config file, with the same names as the flags in snake case and the CORS settings in a `[cors]` table
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct ConfigFile {
+    data_file: Option<PathBuf>,
+    bind: Option<IpAddr>,
+    port: Option<u16>,
+    log_file: Option<PathBuf>,
+    save_interval: Option<u64>,
+    read_only: Option<bool>,
+    lenient: Option<bool>,
+    cors: Option<Cors>,
+}
+
+/// Everything the server is run with
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub data_file: PathBuf,
+    pub addr: SocketAddr,
+    pub log_file: PathBuf,
+    /// Time between saves of the data file
+    pub save_interval: Duration,
+    pub read_only: bool,
+    /// How the data file is read, strict unless `lenient` is set
+    pub parse_mode: ParseMode,
+    pub cors: Cors,
+}
+
+impl ConfigFile {
+    fn read(path: &Path) -> Result<ConfigFile, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("could not read config file {}: {}", path.display(), e))?;
+        toml::from_str(&text).map_err(|e| format!("invalid config file {}: {}", path.display(), e))
+    }
+}
+
+impl Config {
+    /// Reads the command line, the environment and the config file if one is given, exiting with the usage if the
+    /// command line can't be read
+    /// # Errors
+    /// Returns a message if the config file can't be read or the settings don't make a config, see [`Config::from_parts`]
+    pub fn load() -> Result<Config, String> {
+        let cli = Cli::parse();
+        let file = match &cli.config {
+            Some(path) => Some(ConfigFile::read(path)?),
+            None => None,
+        };
+        Config::from_parts(cli, file)
+    }
+
+    /// Merges the command line (with the environment already in it) over the config file, filling in the defaults
+    /// # Errors
+    /// Returns a message if the data file isn't given anywhere, the save interval of the file is 0 or credentials are
+    /// allowed for any origin
+    fn from_parts(cli: Cli, file: Option<ConfigFile>) -> Result<Config, String> {
+        let file = file.unwrap_or_default();
+        if file.save_interval == Some(0) {
+            return Err("save_interval must be at least 1 second".to_string());
+        }
+        let mut cors = file.cors.unwrap_or_default();
+        if let Some(origins) = cli.allowed_origins {
+            cors.allowed_origins = origins;
+        }
+        if let Some(credentials) = cli.allow_credentials {
+            cors.allow_credentials = credentials;
+        }
+        cors.allowed_origins = cors
+            .allowed_origins
+            .iter()
+            .map(|origin| origin.trim().trim_end_matches('/').to_string())
+            .filter(|origin| !origin.is_empty())
+            .collect();
+        if cors.allow_credentials && cors.allowed_origins.iter().any(|origin| origin == "*") {
+            return Err("allow_credentials can't be used with * in allowed_origins, list the origins that may send credentials".to_string());
+        }
+        let data_file = match cli.data_file.or(file.data_file) {
+            Some(data_file) => data_file,
+            None => return Err("no data file given, pass it as an argument or set data_file in the config file".to_string()),
+        };
+        let bind = cli.bind.or(file.bind).unwrap_or(IpAddr::V4(Ipv4Addr::LOCALHOST));
+        Ok(Config {
+            data_file,
+            addr: SocketAddr::new(bind, cli.port.or(file.port).unwrap_or(3000)),
+            log_file: cli.log_file.or(file.log_file).unwrap_or_else(|| PathBuf::from("log.txt")),
+            save_interval: Duration::from_secs(cli.save_interval.or(file.save_interval).unwrap_or(15)),
+            read_only: cli.read_only.or(file.read_only).unwrap_or(false),
+            parse_mode: match cli.lenient.or(file.lenient) {
+                Some(true) => ParseMode::Lenient,
+                _ => ParseMode::Strict,
+            },
+            cors,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+
+    /// Tests that read the environment run one at a time, since it's shared by the whole process
+    static ENV: Mutex<()> = Mutex::new(());
+
+    const VARS: [&str; 10] = [
+        "WEATHER_KU_DATA_FILE",
+        "WEATHER_KU_CONFIG",
+        "WEATHER_KU_BIND",
+        "WEATHER_KU_PORT",
+        "WEATHER_KU_LOG_FILE",
+        "WEATHER_KU_SAVE_INTERVAL",
+        "WEATHER_KU_READ_ONLY",
+        "WEATHER_KU_LENIENT",
+        "WEATHER_KU_ALLOWED_ORIGINS",
+        "WEATHER_KU_ALLOW_CREDENTIALS",
+    ];
+
+    /// Builds a config from the given arguments, environment variables and config file, with every other variable unset
+    fn config(args: &[&str], env: &[(&str, &str)], file: Option<&str>) -> Result<Config, String> {
+        let _lock = ENV.lock().unwrap_or_else(|e| e.into_inner());
+        for var in VARS {
+            std::env::remove_var(var);
+        }
+        for (var, value) in env {
+            std::env::set_var(var, value);
+        }
+        let cli = Cli::try_parse_from(["weather-ku-api"].iter().chain(args)).map_err(|e| e.to_string());
+        for (var, _) in env {
+            std::env::remove_var(var);
+        }
+        let file = file.map(|text| toml::from_str(text).map_err(|e: toml::de::Error| e.to_string())).transpose()?;
+        Config::from_parts(cli?, file)
+    }
+
+    #[test]
+    fn defaults() {
+        let config = config(&["data.txt"], &[], None).unwrap();
+        assert_eq!(config.data_file, PathBuf::from("data.txt"));
+        assert_eq!(config.addr, SocketAddr::from(([127, 0, 0, 1], 3000)));
+        assert_eq!(config.log_file, PathBuf::from("log.txt"));
+        assert_eq!(config.save_interval, Duration::from_secs(15));
+        assert!(!config.read_only);
+        assert_eq!(config.parse_mode, ParseMode::Strict);
+        assert_eq!(config.cors.allowed_origins, vec!["*"]);
+        assert!(!config.cors.allow_credentials);
+    }
+
+    #[test]
+    fn file_over_defaults() {
+        let file = r#"
+            data_file = "file.txt"
+            bind = "0.0.0.0"
+            port = 3100
+            save_interval = 60
+            read_only = true
+            lenient = true
+
+            [cors]
+            allowed_origins = ["http://localhost:5173/"]
+            allow_credentials = true
+        "#;
+        let config = config(&[], &[], Some(file)).unwrap();
+        assert_eq!(config.data_file, PathBuf::from("file.txt"));
+        assert_eq!(config.addr, SocketAddr::from(([0, 0, 0, 0], 3100)));
+        assert_eq!(config.save_interval, Duration::from_secs(60));
+        assert!(config.read_only);
+        assert_eq!(config.parse_mode, ParseMode::Lenient);
+        assert_eq!(config.cors.allowed_origins, vec!["http://localhost:5173"]);
+        assert!(config.cors.allow_credentials);
+    }
+
+    #[test]
+    fn env_over_file() {
+        let file = "data_file = \"file.txt\"\nport = 3100\n[cors]\nallowed_origins = [\"http://a.example\"]";
+        let env = [("WEATHER_KU_PORT", "3200"), ("WEATHER_KU_DATA_FILE", "env.txt"), ("WEATHER_KU_ALLOWED_ORIGINS", "http://b.example,http://c.example")];
+        let config = config(&[], &env, Some(file)).unwrap();
+        assert_eq!(config.addr.port(), 3200);
+        assert_eq!(config.data_file, PathBuf::from("env.txt"));
+        assert_eq!(config.cors.allowed_origins, vec!["http://b.example", "http://c.example"]);
+    }
+
+    #[test]
+    fn cli_over_env() {
+        let env = [("WEATHER_KU_PORT", "3200"), ("WEATHER_KU_READ_ONLY", "false"), ("WEATHER_KU_ALLOW_CREDENTIALS", "false"), ("WEATHER_KU_LENIENT", "true")];
+        let args = ["cli.txt", "--port", "3300", "--read-only", "--allow-credentials", "--allowed-origins", "http://a.example", "--log-file", "cli.log", "--lenient=false"];
+        let config = config(&args, &env, Some("port = 3100\nread_only = false")).unwrap();
+        assert_eq!(config.parse_mode, ParseMode::Strict);
+        assert_eq!(config.addr.port(), 3300);
+        assert!(config.read_only);
+        assert!(config.cors.allow_credentials);
+        assert_eq!(config.log_file, PathBuf::from("cli.log"));
+    }
+
+    #[test]
+    fn invalid_settings() {
+        assert!(config(&[], &[], None).is_err());
+        assert!(config(&["data.txt"], &[], Some("save_interval = 0")).is_err());
+        assert!(config(&["data.txt", "--save-interval", "0"], &[], None).is_err());
+        assert!(config(&["data.txt"], &[], Some("bogus = 1")).is_err());
+        assert!(config(&["data.txt"], &[], Some("[cors]\nallowed = []")).is_err());
+        assert!(config(&["data.txt"], &[("WEATHER_KU_PORT", "port")], None).is_err());
+        // Credentials for any origin, whether * comes from the default or is given
+        assert!(config(&["data.txt", "--allow-credentials"], &[], None).is_err());
+        assert!(config(&["data.txt"], &[("WEATHER_KU_ALLOW_CREDENTIALS", "true")], Some("[cors]\nallowed_origins = [\"http://a.example\", \"*\"]")).is_err());
+    }
+}
@@ -0,0 +1,134 @@
+use hyper::header::{HeaderMap, HeaderValue};
+use serde::Deserialize;
+
+/// Headers browsers may send on cross-origin requests besides the ones they always can
+const ALLOW_HEADERS: &str = "Content-Type, Accept";
+
+/// How long browsers may keep the answer to a preflight request, in seconds
+const MAX_AGE: u32 = 86400;
+
+/// Which web pages may call the API from a browser, the `[cors]` table of the config file
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Cors {
+    /// Origins like `http://localhost:5173`, where `*` allows any origin
+    pub allowed_origins: Vec<String>,
+    /// Whether those pages may send cookies and authorization, which `*` doesn't extend to
+    pub allow_credentials: bool,
+}
+
+impl Default for Cors {
+    fn default() -> Cors {
+        Cors { allowed_origins: vec!["*".to_string()], allow_credentials: false }
+    }
+}
+
+impl Cors {
+    /// The value of `Access-Control-Allow-Origin` for a request from `origin`, None if the origin isn't allowed
+    ///
+    /// Any origin gets `*`, unless credentials are allowed. Browsers only accept those with the origin itself, and echoing
+    /// whatever origin asked would let any page act for the user, so only the listed origins get through then
+    fn allow_origin<'a>(&self, origin: &'a str) -> Option<&'a str> {
+        if self.allowed_origins.iter().any(|allowed| allowed == "*") && !self.allow_credentials {
+            Some("*")
+        } else if self.allowed_origins.iter().any(|allowed| allowed.eq_ignore_ascii_case(origin)) {
+            Some(origin)
+        } else {
+            None
+        }
+    }
+
+    /// Adds the CORS headers for a request with the given headers to its response, leaving them out if the request has no
+    /// `Origin` or it isn't allowed
+    ///
+    /// The methods of a preflight request are already in the `Access-Control-Allow-Methods` header of the response
+    pub fn apply(&self, req: &HeaderMap, res: &mut HeaderMap) {
+        let origin = match req.get("origin").and_then(|origin| origin.to_str().ok()) {
+            Some(origin) => origin,
+            None => return,
+        };
+        let allow_origin = match self.allow_origin(origin).and_then(|allowed| HeaderValue::from_str(allowed).ok()) {
+            Some(allow_origin) => allow_origin,
+            None => {
+                res.remove("Access-Control-Allow-Methods");
+                return;
+            }
+        };
+        res.insert("Access-Control-Allow-Origin", allow_origin);
+        if self.allow_credentials {
+            res.insert("Access-Control-Allow-Credentials", HeaderValue::from_static("true"));
+        }
+        if res.contains_key("Access-Control-Allow-Methods") {
+            res.insert("Access-Control-Allow-Headers", HeaderValue::from_static(ALLOW_HEADERS));
+            res.insert("Access-Control-Max-Age", HeaderValue::from(MAX_AGE));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(origin: Option<&'static str>) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        if let Some(origin) = origin {
+            headers.insert("origin", HeaderValue::from_static(origin));
+        }
+        headers
+    }
+
+    fn listed(origins: &[&str], allow_credentials: bool) -> Cors {
+        Cors { allowed_origins: origins.iter().map(|origin| origin.to_string()).collect(), allow_credentials }
+    }
+
+    #[test]
+    fn any_origin_gets_a_wildcard() {
+        let mut res = HeaderMap::new();
+        Cors::default().apply(&request(Some("http://example.com")), &mut res);
+        assert_eq!(res["access-control-allow-origin"], "*");
+        assert!(!res.contains_key("access-control-allow-credentials"));
+        // Nothing for requests that aren't cross-origin
+        let mut res = HeaderMap::new();
+        Cors::default().apply(&request(None), &mut res);
+        assert!(res.is_empty());
+    }
+
+    #[test]
+    fn allowed_origin_is_echoed() {
+        let mut res = HeaderMap::new();
+        listed(&["http://localhost:5173"], false).apply(&request(Some("http://localhost:5173")), &mut res);
+        assert_eq!(res["access-control-allow-origin"], "http://localhost:5173");
+        assert!(!res.contains_key("access-control-max-age"));
+    }
+
+    #[test]
+    fn disallowed_origin_gets_nothing() {
+        let mut res = HeaderMap::new();
+        res.insert("access-control-allow-methods", HeaderValue::from_static("GET, OPTIONS"));
+        listed(&["http://localhost:5173"], true).apply(&request(Some("http://evil.example")), &mut res);
+        assert!(res.is_empty());
+    }
+
+    #[test]
+    fn credentials_never_go_to_any_origin() {
+        let mut res = HeaderMap::new();
+        listed(&["*"], true).apply(&request(Some("http://example.com")), &mut res);
+        assert!(res.is_empty());
+        // Listed origins still get them
+        let mut res = HeaderMap::new();
+        listed(&["*", "http://localhost:5173"], true).apply(&request(Some("http://localhost:5173")), &mut res);
+        assert_eq!(res["access-control-allow-origin"], "http://localhost:5173");
+        assert_eq!(res["access-control-allow-credentials"], "true");
+    }
+
+    #[test]
+    fn preflight_gets_headers_and_max_age() {
+        let mut res = HeaderMap::new();
+        res.insert("access-control-allow-methods", HeaderValue::from_static("GET, PUT, DELETE, OPTIONS"));
+        listed(&["http://localhost:5173"], false).apply(&request(Some("http://localhost:5173")), &mut res);
+        assert_eq!(res["access-control-allow-origin"], "http://localhost:5173");
+        assert_eq!(res["access-control-allow-methods"], "GET, PUT, DELETE, OPTIONS");
+        assert_eq!(res["access-control-allow-headers"], ALLOW_HEADERS);
+        assert_eq!(res["access-control-max-age"], MAX_AGE.to_string().as_str());
+    }
+}
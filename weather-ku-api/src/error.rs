@@ -0,0 +1,297 @@
+use hyper::StatusCode;
+use parser::{Conflict, Date, ParseDiagnostic, ParseError};
+use serde::Serialize;
+
+use crate::query::QueryError;
+
+/// Everything a request can fail with, sent back as `{"error": {"code": ..., "message": ..., "field": ...}}`
+/// (or as `application/problem+json` if the client asks for it) with the status that goes with it
+#[derive(Debug)]
+pub enum ApiError {
+    /// Nothing at the path
+    NotFound,
+    /// The station of a `/stations/{id}` path isn't in the data
+    StationNotFound(String),
+    /// The data has no stations to send requests without a `/stations/{id}` prefix to
+    NoStations,
+    /// A path that isn't a valid URI once the `/stations/{id}` prefix is taken off
+    InvalidPath,
+    /// The date of `/days/{date}` isn't formatted YYYY-MM-DD
+    InvalidPathDate(String),
+    /// The resource doesn't accept the method, with the ones it does
+    MethodNotAllowed(&'static [&'static str]),
+    InvalidQuery(QueryError),
+    /// Query parameters sent to a route that takes none
+    UnexpectedQuery,
+    /// The content-type of the body is missing or not one of the types the route reads
+    UnsupportedMediaType(&'static [&'static str]),
+    /// The body isn't UTF-8 text
+    InvalidEncoding,
+    /// The body couldn't be read as weather data
+    InvalidBody(ParseDiagnostic),
+    /// A JSON body that doesn't have the fields the route expects, with the message of serde_json
+    InvalidJson(String),
+    /// A JSON list mixing days and hourly observations, with the index of the first record that isn't the same kind
+    /// as the first one
+    MixedRecords { index: usize, hourly: bool },
+    /// `PUT /q` was sent a different number of updates than dates
+    CountMismatch { dates: usize, updates: usize },
+    /// A day that's being added is already there
+    DateExists(Date),
+    /// A day that's being read or changed isn't there
+    DateNotFound(Date),
+    /// The server was started in read-only mode and the request would change the data
+    ReadOnly,
+    /// `POST /import` with `strategy=fail-on-conflict` found dates with differing values
+    MergeConflict(Vec<Conflict>),
+    /// The connection failed while reading the request, which there's nobody left to tell about
+    Connection(hyper::Error),
+}
+
+/// The `error` object of an error response
+#[derive(Serialize)]
+struct ErrorObject<'a> {
+    code: &'static str,
+    message: String,
+    /// Query parameter, field or column the error is about, null if it isn't about any one of them
+    field: Option<&'a str>,
+}
+
+#[derive(Serialize)]
+struct ErrorBody<'a> {
+    error: ErrorObject<'a>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    conflicts: Option<&'a [Conflict]>,
+}
+
+/// An error response in the format of RFC 7807, with our code and field as extension members
+#[derive(Serialize)]
+struct Problem<'a> {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    title: &'static str,
+    status: u16,
+    detail: String,
+    code: &'static str,
+    field: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    conflicts: Option<&'a [Conflict]>,
+}
+
+/// Machine readable code of each kind of parse error
+fn parse_code(error: &ParseError) -> &'static str {
+    match error {
+        ParseError::InvalidDate(_) => "invalid_date",
+        ParseError::InvalidWeatherCode(_) => "invalid_weather_code",
+        ParseError::InvalidTemperature(_) => "invalid_temperature",
+        ParseError::InvalidPrecipitation(_) => "invalid_precipitation",
+        ParseError::InvalidWind(_) => "invalid_wind",
+        ParseError::InvalidPrecipitationProbability(_) => "invalid_precipitation_probability",
+        ParseError::InvalidLine(_) => "invalid_line",
+        ParseError::TooManyValues => "too_many_values",
+        ParseError::TooFewValues => "too_few_values",
+        ParseError::DuplicateDate(_) => "duplicate_date",
+        ParseError::DuplicateField(_) => "duplicate_field",
+        ParseError::UnknownField(_) => "unknown_field",
+        ParseError::MissingField(_) => "missing_field",
+        ParseError::Io(_) => "unreadable_body",
+        ParseError::InvalidJson(_) => "invalid_json",
+        ParseError::InvalidUnits(_) => "invalid_units",
+        ParseError::DuplicateStation(_) => "duplicate_station",
+        ParseError::InvalidCoordinate(_) => "invalid_coordinate",
+        ParseError::InvalidTimestamp(_) => "invalid_timestamp",
+        ParseError::DuplicateTimestamp(_) => "duplicate_timestamp",
+        ParseError::InvalidValue(_) => "invalid_value",
+        ParseError::DuplicateColumn(_) => "duplicate_column",
+        ParseError::MissingColumn(_) => "missing_column",
+    }
+}
+
+impl ApiError {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            ApiError::NotFound | ApiError::StationNotFound(_) | ApiError::NoStations | ApiError::DateNotFound(_) => StatusCode::NOT_FOUND,
+            ApiError::MethodNotAllowed(_) => StatusCode::METHOD_NOT_ALLOWED,
+            ApiError::ReadOnly => StatusCode::FORBIDDEN,
+            ApiError::UnsupportedMediaType(_) | ApiError::InvalidEncoding => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            ApiError::DateExists(_) | ApiError::MergeConflict(_) => StatusCode::CONFLICT,
+            ApiError::Connection(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            _ => StatusCode::BAD_REQUEST,
+        }
+    }
+
+    /// Code clients can branch on, which unlike the message never changes
+    pub fn code(&self) -> &'static str {
+        match self {
+            ApiError::NotFound => "not_found",
+            ApiError::StationNotFound(_) => "station_not_found",
+            ApiError::NoStations => "no_stations",
+            ApiError::InvalidPath => "invalid_path",
+            ApiError::InvalidPathDate(_) => "invalid_date",
+            ApiError::MethodNotAllowed(_) => "method_not_allowed",
+            ApiError::InvalidQuery(e) => e.code,
+            ApiError::UnexpectedQuery => "unexpected_parameter",
+            ApiError::UnsupportedMediaType(_) => "unsupported_media_type",
+            ApiError::InvalidEncoding => "invalid_encoding",
+            ApiError::InvalidBody(diagnostic) => parse_code(&diagnostic.error),
+            ApiError::InvalidJson(_) => "invalid_json",
+            ApiError::MixedRecords { .. } => "mixed_records",
+            ApiError::CountMismatch { .. } => "count_mismatch",
+            ApiError::DateExists(_) => "date_exists",
+            ApiError::DateNotFound(_) => "date_not_found",
+            ApiError::ReadOnly => "read_only",
+            ApiError::MergeConflict(_) => "merge_conflict",
+            ApiError::Connection(_) => "connection_failed",
+        }
+    }
+
+    /// The query parameter, field or column the error is about
+    pub fn field(&self) -> Option<&str> {
+        match self {
+            ApiError::InvalidQuery(e) => Some(&e.parameter),
+            ApiError::InvalidPathDate(_) | ApiError::DateExists(_) | ApiError::DateNotFound(_) => Some("date"),
+            ApiError::InvalidBody(diagnostic) => match &diagnostic.error {
+                ParseError::DuplicateField(field) | ParseError::MissingField(field) => Some(field.name()),
+                ParseError::UnknownField(name) | ParseError::DuplicateColumn(name) | ParseError::MissingColumn(name) => Some(name),
+                _ => diagnostic.field.map(|field| field.name()),
+            },
+            _ => None,
+        }
+    }
+
+    /// Builds the body of the response, as `application/problem+json` if `problem` is set
+    pub fn body(&self, problem: bool) -> String {
+        let conflicts = match self {
+            ApiError::MergeConflict(conflicts) => Some(conflicts.as_slice()),
+            _ => None,
+        };
+        let body = if problem {
+            let status = self.status();
+            serde_json::to_string(&Problem {
+                kind: "about:blank",
+                title: status.canonical_reason().unwrap_or("Error"),
+                status: status.as_u16(),
+                detail: self.to_string(),
+                code: self.code(),
+                field: self.field(),
+                conflicts,
+            })
+        } else {
+            serde_json::to_string(&ErrorBody {
+                error: ErrorObject { code: self.code(), message: self.to_string(), field: self.field() },
+                conflicts,
+            })
+        };
+        body.expect("errors are always serializable to JSON")
+    }
+}
+
+impl std::fmt::Display for ApiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ApiError::NotFound => write!(f, "path does not exist"),
+            ApiError::StationNotFound(id) => write!(f, "station does not exist: {}", id),
+            ApiError::NoStations => write!(f, "no stations in data"),
+            ApiError::InvalidPath => write!(f, "invalid path"),
+            ApiError::InvalidPathDate(date) => write!(f, "invalid date in path: {}", date),
+            ApiError::MethodNotAllowed(methods) => write!(f, "method not allowed, expected {}", methods.join(", ")),
+            ApiError::InvalidQuery(e) => write!(f, "{}", e.message),
+            ApiError::UnexpectedQuery => write!(f, "no query parameters accepted"),
+            ApiError::UnsupportedMediaType(types) => write!(f, "content-type header required, must be {}", types.join(" or ")),
+            ApiError::InvalidEncoding => write!(f, "body must be valid utf-8 text"),
+            ApiError::InvalidBody(diagnostic) => write!(f, "invalid body: {}", diagnostic),
+            ApiError::InvalidJson(msg) => write!(f, "invalid body: {}", msg),
+            ApiError::MixedRecords { index, hourly } => {
+                let (first, other) = if *hourly { ("an hour", "a day") } else { ("a day", "an hour") };
+                write!(f, "record {} is {} but the first record is {}, records must all have a date or all have a time", index, other, first)
+            }
+            ApiError::CountMismatch { dates, updates } => write!(f, "number of dates and values must be equal ({} dates, {} values)", dates, updates),
+            ApiError::DateExists(date) => write!(f, "date already exists: {}", date),
+            ApiError::DateNotFound(date) => write!(f, "date does not exist: {}", date),
+            ApiError::ReadOnly => write!(f, "the server is read-only, data can't be changed"),
+            ApiError::MergeConflict(conflicts) => write!(f, "{} conflicting date(s), nothing was imported", conflicts.len()),
+            ApiError::Connection(e) => write!(f, "connection failed: {}", e),
+        }
+    }
+}
+
+impl From<QueryError> for ApiError {
+    fn from(e: QueryError) -> ApiError {
+        ApiError::InvalidQuery(e)
+    }
+}
+
+impl From<ParseDiagnostic> for ApiError {
+    fn from(diagnostic: ParseDiagnostic) -> ApiError {
+        ApiError::InvalidBody(diagnostic)
+    }
+}
+
+impl From<serde_json::Error> for ApiError {
+    fn from(e: serde_json::Error) -> ApiError {
+        ApiError::InvalidJson(e.to_string())
+    }
+}
+
+impl From<hyper::Error> for ApiError {
+    fn from(e: hyper::Error) -> ApiError {
+        ApiError::Connection(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parser::{DataOps, DataPoint, WeatherDataMap};
+    use serde_json::{json, Value};
+
+    fn body(e: &ApiError, problem: bool) -> Value {
+        serde_json::from_str(&e.body(problem)).unwrap()
+    }
+
+    #[test]
+    fn body_has_code_message_and_field() {
+        let e = ApiError::InvalidQuery(QueryError::new("units", "units must be metric or imperial"));
+        assert_eq!(e.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(
+            body(&e, false),
+            json!({ "error": { "code": "invalid_parameter", "message": "units must be metric or imperial", "field": "units" } })
+        );
+        // Errors that aren't about a field still have one, as null
+        assert_eq!(body(&ApiError::NotFound, false), json!({ "error": { "code": "not_found", "message": "path does not exist", "field": null } }));
+    }
+
+    #[test]
+    fn problem_body() {
+        let date = Date::from_string("2024-04-24").unwrap();
+        assert_eq!(
+            body(&ApiError::DateNotFound(date), true),
+            json!({
+                "type": "about:blank",
+                "title": "Not Found",
+                "status": 404,
+                "detail": "date does not exist: 2024-04-24",
+                "code": "date_not_found",
+                "field": "date",
+            })
+        );
+    }
+
+    #[test]
+    fn parse_errors_keep_their_kind_and_field() {
+        let diagnostic = WeatherDataMap::from_csv("date,temperature_max\n2024-04-24,abc\n".as_bytes(), Default::default()).unwrap_err();
+        let e = ApiError::from(diagnostic);
+        let body = body(&e, false);
+        assert_eq!(body["error"]["code"], "invalid_temperature");
+        assert_eq!(body["error"]["field"], DataPoint::TemperatureMax.name());
+    }
+
+    #[test]
+    fn merge_conflicts_are_listed() {
+        let e = ApiError::MergeConflict(vec![]);
+        assert_eq!(e.status(), StatusCode::CONFLICT);
+        assert_eq!(body(&e, false)["conflicts"], json!([]));
+        assert_eq!(body(&e, true)["conflicts"], json!([]));
+        assert!(body(&ApiError::NotFound, true).get("conflicts").is_none());
+    }
+}
@@ -1,51 +1,651 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::fs::OpenOptions;
 use std::io::Write;
-use std::net::SocketAddr;
-use std::sync::{Arc, Condvar, Mutex, RwLock};
-use std::time::Duration;
-use std::borrow::Borrow;
+use std::path::Path;
+use std::sync::{Arc, Mutex, RwLock};
 
 use http_body_util::{combinators::BoxBody, BodyExt};
-use http_body_util::{Empty, Full};
+use http_body_util::Full;
 use hyper::body::Bytes;
 use hyper::server::conn::http1;
 use hyper::service::service_fn;
+use hyper::header::{HeaderMap, HeaderValue};
 use hyper::{Method, StatusCode};
 use hyper::{Request, Response};
 use hyper_util::rt::TokioIo;
 use indexmap::IndexMap;
-use serde_json::Value;
+use serde::{Deserialize, Deserializer, Serialize};
 use tokio::net::TcpListener;
 
 use chrono::DurationRound;
 
-use parser::{DataOps, Date, WeatherData, WeatherDataMap};
+use parser::stats::Selector;
+use parser::{
+    stats, Conflict, CsvOptions, DataOps, DataPoint, Dataset, Date, DegreeDayBases, Derived, Event, EventKind, HourlyData, Issue,
+    MergeStrategy, OutlierTest, ParseOptions, Period, PointsView, QualityFlag, ResamplePolicy, Station, Thresholds, UnitSystem, WeatherCode, WeatherData,
+    WeatherDataMap,
+};
 
-/// Origin for CORS Allow Origin header 
-const CORS_ALLOW_ORIGIN: &str =  "*";
+mod config;
+mod cors;
+mod error;
+mod query;
+use config::Config;
+use error::ApiError;
+use query::{Query, QueryError};
+
+
+/// Fields of a weather entry that can be changed with a PUT request, any field left out is kept as is
+/// and a field set to null is marked as missing
+#[derive(Deserialize)]
+struct WeatherDataUpdate {
+    #[serde(default, deserialize_with = "present")]
+    weather_code: Option<Option<WeatherCode>>,
+    #[serde(default, deserialize_with = "present")]
+    temperature_max: Option<Option<f32>>,
+    #[serde(default, deserialize_with = "present")]
+    temperature_min: Option<Option<f32>>,
+    #[serde(default, deserialize_with = "present")]
+    precipitation_sum: Option<Option<f32>>,
+    #[serde(default, deserialize_with = "present")]
+    wind_speed_max: Option<Option<f32>>,
+    #[serde(default, deserialize_with = "present")]
+    precipitation_probability_max: Option<Option<f32>>,
+}
+
+/// Deserializes a field that's present in the body (even as null) as Some, leaving absent fields to the default of None
+fn present<'de, D: Deserializer<'de>, T: Deserialize<'de>>(deserializer: D) -> Result<Option<Option<T>>, D::Error> {
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+impl WeatherDataUpdate {
+    fn apply(self, data: &mut WeatherData) {
+        if let Some(weather_code) = self.weather_code {
+            data.weather_code = weather_code;
+        }
+        if let Some(temp_max) = self.temperature_max {
+            data.temp_max = temp_max;
+        }
+        if let Some(temp_min) = self.temperature_min {
+            data.temp_min = temp_min;
+        }
+        if let Some(precip_sum) = self.precipitation_sum {
+            data.precip_sum = precip_sum;
+        }
+        if let Some(max_wind) = self.wind_speed_max {
+            data.max_wind = max_wind;
+        }
+        if let Some(precip_prob_max) = self.precipitation_probability_max {
+            data.precip_prob_max = precip_prob_max;
+        }
+    }
+}
 
 #[inline]
-/// Builds a response with uniform headers 
-fn res_with_body(body: &'static str, status: StatusCode) -> Response<BoxBody<Bytes, hyper::Error>> {
+/// Builds a JSON response with uniform headers 
+fn res_with_body<T: Into<Bytes>>(body: T, status: StatusCode) -> Response<BoxBody<Bytes, hyper::Error>> {
+    res_with_type(body, status, "application/json")
+}
+
+/// Builds a response with uniform headers and the given content type
+fn res_with_type<T: Into<Bytes>>(body: T, status: StatusCode, content_type: &str) -> Response<BoxBody<Bytes, hyper::Error>> {
+    let body: Bytes = body.into();
     Response::builder()
         .status(status)
-        .header("Content-Type", "application/json")
+        .header("Content-Type", content_type)
         .header("Content-Length", format!("{}", body.len()))
-        .header("Access-Control-Allow-Origin", CORS_ALLOW_ORIGIN)
         .header("Vary", "Origin")
         .body(full(body))
         .unwrap()
 
 }
 
-/// Log function to monitor server activity with a log file (logfile.txt) One of the few functions that can panic if error occurs
-fn log(msg: &str) {
+/// Returns the media type of the request body (without parameters like charset), lowercased
+fn content_type(headers: &HeaderMap) -> Option<String> {
+    let value = headers.get("content-type")?.to_str().ok()?;
+    Some(value.split(';').next()?.trim().to_ascii_lowercase())
+}
+
+/// Checks that the request body is JSON by its media type, so `application/json; charset=utf-8` passes
+fn require_json(headers: &HeaderMap) -> Result<(), ApiError> {
+    match content_type(headers).as_deref() {
+        Some("application/json") => Ok(()),
+        _ => Err(ApiError::UnsupportedMediaType(&["application/json"])),
+    }
+}
+
+/// Returns true if the client asked for CSV in its Accept header
+fn accepts_csv(headers: &HeaderMap) -> bool {
+    headers
+        .get_all("accept")
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|media| media.split(';').next().unwrap_or("").trim().eq_ignore_ascii_case("text/csv"))
+}
+
+/// Returns true if the client asked for errors as `application/problem+json` in its Accept header
+fn accepts_problem(headers: &HeaderMap) -> bool {
+    headers
+        .get_all("accept")
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|media| media.split(';').next().unwrap_or("").trim().eq_ignore_ascii_case("application/problem+json"))
+}
+
+/// Builds the response to an error, listing the accepted methods in the `Allow` header if it's one the resource doesn't accept
+fn error_response(e: &ApiError, problem: bool) -> Response<BoxBody<Bytes, hyper::Error>> {
+    let content_type = if problem { "application/problem+json" } else { "application/json" };
+    let mut res = res_with_type(e.body(problem), e.status(), content_type);
+    if let ApiError::MethodNotAllowed(methods) = e {
+        res.headers_mut().insert("Allow", HeaderValue::from_str(&methods.join(", ")).unwrap());
+    }
+    res
+}
+
+/// Reads the `dates=` parameter, the first and last day separated by a space (`+` or `%20`) or a comma
+fn query_dates(query: &Query) -> Result<(Date, Date), QueryError> {
+    let dates = query.list("dates");
+    if dates.len() != 2 {
+        return Err(QueryError::new("dates", "dates must be two dates formatted YYYY-MM-DD separated by a space"));
+    }
+    match (Date::from_string(dates[0]), Date::from_string(dates[1])) {
+        (Ok(begin_date), Ok(end_date)) => Ok((begin_date, end_date)),
+        _ => Err(QueryError::new("dates", "invalid date format, expected YYYY-MM-DD")),
+    }
+}
+
+/// Reads the `from=` and `to=` parameters, a range left open at either end reaches the first or last day
+fn query_from_to(query: &Query) -> Result<(Date, Date), QueryError> {
+    // Ranges snap to the closest days in the data, so the earliest and latest possible dates take in every day
+    let bound = |name: &str, open: (u32, u8, u8)| match query.get(name) {
+        Some(date) => Date::from_string(date).map_err(|_| QueryError::new(name, format!("{} must be a date formatted YYYY-MM-DD", name))),
+        None => Ok(Date::new(open.0, open.1, open.2).expect("the earliest and latest dates are valid")),
+    };
+    Ok((bound("from", (1, 1, 1))?, bound("to", (9999, 12, 31))?))
+}
+
+/// Reads the comma separated `values=` parameter, an empty set (meaning every field) if it's left out or empty
+/// Fields from a repeated `values=` are added together, and derived values can be named by their short name (`hdd`)
+/// or their column (`heating_degree_days`)
+fn query_values(query: &Query, bases: &DegreeDayBases) -> Result<(HashSet<DataPoint>, Vec<Derived>), QueryError> {
+    let mut points = HashSet::new();
+    let mut derived = vec![];
+    for point in query.list("values") {
+        let derived_name = match point {
+            "temp_mean" => Some("temperature_mean"),
+            "temp_range" => Some("temperature_range"),
+            "hdd" => Some("heating_degree_days"),
+            "cdd" => Some("cooling_degree_days"),
+            "gdd" => Some("growing_degree_days"),
+            // The names of the columns they come back in work too
+            name => Some(name),
+        };
+        if let Some(value) = derived_name.and_then(|name| Derived::from_name(name, bases)) {
+            if !derived.contains(&value) {
+                derived.push(value);
+            }
+            continue;
+        }
+        match point {
+            "weather_code" => points.insert(DataPoint::WeatherCode),
+            "temp_max" => points.insert(DataPoint::TemperatureMax),
+            "temp_min" => points.insert(DataPoint::TemperatureMin),
+            "precip_sum" => points.insert(DataPoint::PrecipitationSum),
+            "max_wind" => points.insert(DataPoint::WindSpeedMax),
+            "prob_precip_max" => points.insert(DataPoint::PrecipitationProbabilityMax),
+            _ => {
+                return Err(QueryError::new("values", format!("invalid value field '{}'", point)));
+            }
+        };
+    }
+    Ok((points, derived))
+}
+
+/// Reads the `hdd_base`, `cdd_base`, `gdd_base` and `gdd_cap` parameters, in the units of the response,
+/// each one defaulting to the usual base for those units
+fn query_bases(query: &Query, units: UnitSystem) -> Result<DegreeDayBases, QueryError> {
+    let mut bases = DegreeDayBases::for_units(units);
+    let fields = [
+        ("hdd_base", &mut bases.heating),
+        ("cdd_base", &mut bases.cooling),
+        ("gdd_base", &mut bases.growing),
+    ];
+    for (name, base) in fields {
+        if let Some(value) = query.get(name) {
+            match value.parse::<f32>() {
+                Ok(value) if value.is_finite() => *base = value,
+                _ => return Err(QueryError::new(name, format!("{} must be a temperature", name))),
+            }
+        }
+    }
+    match query.get("gdd_cap") {
+        Some("none") => bases.growing_cap = None,
+        Some(value) => match value.parse::<f32>() {
+            Ok(value) if value.is_finite() => bases.growing_cap = Some(value),
+            _ => return Err(QueryError::new("gdd_cap", "gdd_cap must be a temperature or none")),
+        },
+        None => {}
+    }
+    Ok(bases)
+}
+
+/// Reads the comma separated `kinds=` parameter of `/events`, defaulting to every kind of event
+fn query_kinds(query: &Query) -> Result<Vec<EventKind>, QueryError> {
+    let names = query.list("kinds");
+    if names.is_empty() {
+        return Ok(EventKind::ALL.to_vec());
+    }
+    let mut kinds = vec![];
+    for name in names {
+        match EventKind::from_name(name) {
+            Some(kind) if !kinds.contains(&kind) => kinds.push(kind),
+            Some(_) => {}
+            None => {
+                return Err(QueryError::new("kinds", format!("invalid event kind '{}' (expected dry_spell, rain_streak, heat_wave, frost or high_wind)", name)));
+            }
+        }
+    }
+    Ok(kinds)
+}
+
+/// Reads the `wet`, `heat`, `frost`, `wind` and `heat_wave_days` parameters of `/events`, in the units of the response,
+/// each one defaulting to the usual threshold for those units
+fn query_thresholds(query: &Query, units: UnitSystem) -> Result<Thresholds, QueryError> {
+    let mut thresholds = Thresholds::for_units(units);
+    let fields = [
+        ("wet", &mut thresholds.wet),
+        ("heat", &mut thresholds.heat),
+        ("frost", &mut thresholds.frost),
+        ("wind", &mut thresholds.wind),
+    ];
+    for (name, threshold) in fields {
+        if let Some(value) = query.get(name) {
+            match value.parse::<f32>() {
+                Ok(value) if value.is_finite() => *threshold = value,
+                _ => return Err(QueryError::new(name, format!("{} must be a number", name))),
+            }
+        }
+    }
+    if let Some(days) = query.get("heat_wave_days") {
+        match days.parse::<u16>() {
+            Ok(days) if days >= 1 => thresholds.heat_wave_days = days,
+            _ => return Err(QueryError::new("heat_wave_days", "heat_wave_days must be a number of days from 1")),
+        }
+    }
+    Ok(thresholds)
+}
+
+/// Events of one kind found by `/events`
+#[derive(Serialize)]
+struct EventSummary {
+    /// Number of events
+    count: usize,
+    /// Number of days in all the events together
+    days: u32,
+    /// The longest event, the earliest one if there's a tie
+    longest: Option<Event>,
+    events: Vec<Event>,
+}
+
+impl EventSummary {
+    fn new(events: Vec<Event>) -> EventSummary {
+        EventSummary {
+            count: events.len(),
+            days: events.iter().map(|event| event.days).sum(),
+            longest: events.iter().copied().reduce(|longest, event| if event.days > longest.days { event } else { longest }),
+            events,
+        }
+    }
+}
+
+/// A field of the weather data or a value derived from its temperatures, as asked for with `values=`
+#[derive(Clone, Copy)]
+enum Field {
+    Point(DataPoint),
+    Derived(Derived),
+}
+
+impl Selector for Field {
+    fn select(&self, data: &WeatherData) -> Option<f32> {
+        match self {
+            Field::Point(point) => point.select(data),
+            Field::Derived(derived) => derived.select(data),
+        }
+    }
+}
+
+/// Derived values of each day of the data, or of each period when grouped: degree days are added up over
+/// the period and the other values averaged. Keyed by the dates of `map`, which is resampled by the same period
+fn derived_column(daily: &WeatherDataMap, map: &WeatherDataMap, derived: Derived, period: Option<Period>) -> IndexMap<Date, Option<f32>> {
+    let period = match period {
+        Some(period) => period,
+        None => return map.iter().map(|(date, data)| (*date, derived.value(data))).collect(),
+    };
+    let mut buckets: HashMap<Date, WeatherDataMap> = HashMap::new();
+    for (date, data) in daily.iter() {
+        buckets.entry(period.start(date)).or_default().insert(*date, data.clone());
+    }
+    map.keys()
+        .map(|start| {
+            let value = buckets.get(start).and_then(|days| {
+                if derived.is_degree_days() {
+                    stats::sum(days, derived)
+                } else {
+                    stats::mean(days, derived)
+                }
+            });
+            (*start, value)
+        })
+        .collect()
+}
+
+/// The derived values asked for, as named columns keyed by the dates of `map`, each set of degree days followed by
+/// its running total from the start of the range (`<name>_cumulative`)
+fn derived_columns(daily: &WeatherDataMap, map: &WeatherDataMap, derived: &[Derived], period: Option<Period>) -> Vec<(String, IndexMap<Date, Option<f32>>)> {
+    let mut columns = vec![];
+    for value in derived {
+        let column = derived_column(daily, map, *value, period);
+        if value.is_degree_days() {
+            let mut total: Option<f32> = None;
+            let totals = column
+                .iter()
+                .map(|(date, value)| {
+                    if let Some(value) = value {
+                        total = Some(total.unwrap_or(0.0) + value);
+                    }
+                    (*date, total)
+                })
+                .collect();
+            columns.push((value.name().to_string(), column));
+            columns.push((format!("{}_cumulative", value.name()), totals));
+        } else {
+            columns.push((value.name().to_string(), column));
+        }
+    }
+    columns
+}
+
+/// The days of a `GET /days` response with everything asked for on top of their fields, written out either as JSON
+/// records or as CSV rows
+struct DaysTable<'a> {
+    map: &'a WeatherDataMap,
+    /// Fields sent in the order of [`DataPoint::ALL`], none if only derived values were asked for
+    points: Vec<DataPoint>,
+    /// Derived columns from [`derived_columns`]
+    columns: Vec<(String, IndexMap<Date, Option<f32>>)>,
+    /// Smoothed values of the days, None if nothing is smoothed
+    smoothed: Option<WeatherDataMap>,
+    /// Fields that get smoothed, in the order of [`DataPoint::ALL`]
+    rolling_points: Vec<DataPoint>,
+    /// Quality flags of the days, None if they weren't checked
+    flags: Option<IndexMap<Date, Vec<QualityFlag>>>,
+}
+
+/// A day of a [`DaysTable`] as a JSON record: the record [`DataOps::json`] would write, then the derived columns, the
+/// smoothed values in a `rolling` object and the quality flags in a `quality` list
+#[derive(Serialize)]
+struct DayRecord<'a> {
+    #[serde(flatten)]
+    fields: PointsView<'a>,
+    #[serde(flatten)]
+    columns: IndexMap<&'a str, Option<f32>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    rolling: Option<IndexMap<&'static str, Option<f32>>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    quality: Option<&'a [QualityFlag]>,
+}
+
+impl DaysTable<'_> {
+    /// The value of a derived column for a day, None if it's missing
+    fn column(values: &IndexMap<Date, Option<f32>>, date: &Date) -> Option<f32> {
+        values.get(date).copied().flatten()
+    }
+
+    /// The smoothed value of a field for a day, None if it's missing or nothing is smoothed
+    fn smoothed(&self, date: &Date, point: DataPoint) -> Option<f32> {
+        self.smoothed.as_ref()?.get(date)?.value(point)
+    }
+
+    /// The quality flags of a day, empty for days without any or if they weren't checked
+    fn quality(&self, date: &Date) -> &[QualityFlag] {
+        self.flags.as_ref().and_then(|flags| flags.get(date)).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    fn json(&self) -> String {
+        let records: Vec<DayRecord> = self
+            .map
+            .iter()
+            .map(|(date, data)| DayRecord {
+                fields: PointsView::new(data, &self.points),
+                columns: self.columns.iter().map(|(name, values)| (name.as_str(), DaysTable::column(values, date))).collect(),
+                rolling: self
+                    .smoothed
+                    .is_some()
+                    .then(|| self.rolling_points.iter().map(|point| (point.name(), self.smoothed(date, *point))).collect()),
+                quality: self.flags.is_some().then(|| self.quality(date)),
+            })
+            .collect();
+        serde_json::to_string(&records).expect("weather data is always serializable to JSON")
+    }
+
+    /// A header row, then a row for each day with the same columns as [`DataOps::to_csv`] followed by the derived ones
+    /// and the smoothed fields (named with a `_rolling` suffix), missing values left empty
+    ///
+    /// The `quality` column lists the flags of each day as `field:reason` or `field:outlier` separated by semicolons
+    fn csv(&self) -> String {
+        let mut writer = csv::Writer::from_writer(vec![]);
+        // Filled in days are marked the same way `DataOps::to_csv` does
+        let synthetic = self.map.values().any(|data| data.synthetic);
+        let rolling_points = if self.smoothed.is_some() { self.rolling_points.as_slice() } else { &[] };
+        let mut header: Vec<String> = std::iter::once(DataPoint::Date).chain(self.points.iter().copied()).map(|point| point.name().to_string()).collect();
+        header.extend(synthetic.then(|| "synthetic".to_string()));
+        header.extend(self.columns.iter().map(|(name, _)| name.clone()));
+        header.extend(rolling_points.iter().map(|point| format!("{}_rolling", point.name())));
+        header.extend(self.flags.is_some().then(|| "quality".to_string()));
+        writer.write_record(header).expect("writing to memory can't fail");
+        let cell = |value: Option<f32>| value.map(|value| value.to_string()).unwrap_or_default();
+        for (date, data) in self.map.iter() {
+            let mut row = vec![date.to_string()];
+            row.extend(self.points.iter().map(|point| cell(data.value(*point))));
+            if synthetic {
+                row.push(if data.synthetic { "true".to_string() } else { String::new() });
+            }
+            row.extend(self.columns.iter().map(|(_, values)| cell(DaysTable::column(values, date))));
+            row.extend(rolling_points.iter().map(|point| cell(self.smoothed(date, *point))));
+            if self.flags.is_some() {
+                let flags: Vec<String> = self
+                    .quality(date)
+                    .iter()
+                    .map(|flag| match flag.issue {
+                        Issue::Implausible { reason } => format!("{}:{}", flag.field.name(), reason.name()),
+                        Issue::Outlier { .. } => format!("{}:outlier", flag.field.name()),
+                    })
+                    .collect();
+                row.push(flags.join(";"));
+            }
+            writer.write_record(&row).expect("writing to memory can't fail");
+        }
+        let bytes = writer.into_inner().expect("writing to memory can't fail");
+        String::from_utf8(bytes).expect("dates and numbers are always valid utf-8")
+    }
+}
+
+/// Reads the `units=metric|imperial` parameter, defaulting to the units the data is stored in
+fn query_units(query: &Query, units: UnitSystem) -> Result<UnitSystem, QueryError> {
+    match query.get("units") {
+        Some(name) => match UnitSystem::from_name(name) {
+            Some(to_units) => Ok(to_units),
+            None => Err(QueryError::new("units", "units must be metric or imperial")),
+        },
+        None => Ok(units),
+    }
+}
+
+/// Reads the `group_by=week|month|year` parameter, None if the days aren't grouped
+fn query_period(query: &Query) -> Result<Option<Period>, QueryError> {
+    match query.get("group_by") {
+        Some(name) => match Period::from_name(name) {
+            Some(period) => Ok(Some(period)),
+            None => Err(QueryError::new("group_by", "group_by must be week, month or year")),
+        },
+        None => Ok(None),
+    }
+}
+
+/// Reads the `strategy=` parameter of `/import`, failing on any conflict if left out
+fn query_strategy(query: &Query) -> Result<MergeStrategy, QueryError> {
+    query.only(&["strategy"])?;
+    match query.get("strategy") {
+        Some(name) => match MergeStrategy::from_name(name) {
+            Some(strategy) => Ok(strategy),
+            None => Err(QueryError::new("strategy", "strategy must be prefer-left, prefer-right, average or fail-on-conflict")),
+        },
+        None => Ok(MergeStrategy::default()),
+    }
+}
+
+/// Response of `/import`
+#[derive(Serialize)]
+struct ImportSummary {
+    /// Number of new dates
+    added: usize,
+    /// Number of dates that were already there, with or without conflicts
+    overlapping: usize,
+    strategy: &'static str,
+    conflicts: Vec<Conflict>,
+}
+
+/// Reads a `name=true|false` parameter like `qc` or `fill`, false if left out and true if given without a value
+fn query_flag(query: &Query, name: &str) -> Result<bool, QueryError> {
+    match query.get(name) {
+        Some("true" | "") => Ok(true),
+        Some("false") | None => Ok(false),
+        Some(_) => Err(QueryError::new(name, format!("{} must be true or false", name))),
+    }
+}
+
+/// Reads the `outliers=z_score|iqr|none` parameter, the test `qc=true` flags outliers with, defaulting to z-scores
+fn query_outliers(query: &Query) -> Result<Option<OutlierTest>, QueryError> {
+    match query.get("outliers") {
+        Some("none") => Ok(None),
+        Some(name) => match OutlierTest::from_name(name) {
+            Some(test) => Ok(Some(test)),
+            None => Err(QueryError::new("outliers", "outliers must be z_score, iqr or none")),
+        },
+        None => Ok(Some(OutlierTest::default())),
+    }
+}
+
+/// Reads the `rolling=N` parameter, the number of days in each window (1 to 366), None if nothing is smoothed
+fn query_rolling(query: &Query) -> Result<Option<u16>, QueryError> {
+    match query.get("rolling") {
+        Some(days) => match days.parse::<u16>() {
+            Ok(days) if (1..=366).contains(&days) => Ok(Some(days)),
+            _ => Err(QueryError::new("rolling", "rolling must be a number of days from 1 to 366")),
+        },
+        None => Ok(None),
+    }
+}
+
+/// An aggregation that can be asked for with the `ops=` parameter of `/stats`
+#[derive(Clone, Copy)]
+enum StatOp {
+    Count,
+    Sum,
+    Mean,
+    Median,
+    Stddev,
+    Min,
+    Max,
+    /// Most common value, only for the weather code
+    Mode,
+    /// `p0` to `p100`
+    Percentile(f32),
+}
+
+impl StatOp {
+    fn from_name(name: &str) -> Option<StatOp> {
+        match name {
+            "count" => Some(StatOp::Count),
+            "sum" => Some(StatOp::Sum),
+            "mean" => Some(StatOp::Mean),
+            "median" => Some(StatOp::Median),
+            "stddev" => Some(StatOp::Stddev),
+            "min" => Some(StatOp::Min),
+            "max" => Some(StatOp::Max),
+            "mode" => Some(StatOp::Mode),
+            _ => {
+                let percent: f32 = name.strip_prefix('p')?.parse().ok()?;
+                (0.0..=100.0).contains(&percent).then_some(StatOp::Percentile(percent))
+            }
+        }
+    }
+
+    /// Runs the aggregation over a field, None if it doesn't apply to the field
+    /// (the weather code only has a count and a mode, and only the weather code has a mode)
+    fn run(self, map: &WeatherDataMap, point: Field) -> Option<StatValue> {
+        let value = match (self, point) {
+            (StatOp::Count, _) => StatValue::Count(stats::count(map, point)),
+            (StatOp::Mode, Field::Point(DataPoint::WeatherCode)) => StatValue::Mode(stats::mode_weather_code(map).map(|code| Mode {
+                value: code,
+                description: code.description(),
+            })),
+            (StatOp::Mode, _) | (_, Field::Point(DataPoint::WeatherCode)) => return None,
+            (StatOp::Sum, _) => StatValue::Number(stats::sum(map, point)),
+            (StatOp::Mean, _) => StatValue::Number(stats::mean(map, point)),
+            (StatOp::Median, _) => StatValue::Number(stats::median(map, point)),
+            (StatOp::Stddev, _) => StatValue::Number(stats::stddev(map, point)),
+            (StatOp::Min, _) => StatValue::Extreme(stats::argmin(map, point)),
+            (StatOp::Max, _) => StatValue::Extreme(stats::argmax(map, point)),
+            (StatOp::Percentile(percent), _) => StatValue::Number(stats::percentile(map, point, percent)),
+        };
+        Some(value)
+    }
+}
+
+/// Result of a [`StatOp`], serialized as is (null when there are no values to aggregate)
+#[derive(Serialize)]
+#[serde(untagged)]
+enum StatValue {
+    Count(usize),
+    Number(Option<f32>),
+    Extreme(Option<stats::Extreme>),
+    Mode(Option<Mode>),
+}
+
+#[derive(Serialize)]
+struct Mode {
+    value: WeatherCode,
+    description: &'static str,
+}
+
+/// Reads the comma separated `ops=` parameter, defaulting to mean, min and max
+fn query_ops(query: &Query) -> Result<Vec<(&str, StatOp)>, QueryError> {
+    let mut names = query.list("ops");
+    if names.is_empty() {
+        names = vec!["mean", "min", "max"];
+    }
+    let mut ops = vec![];
+    for name in names {
+        match StatOp::from_name(name) {
+            Some(op) => ops.push((name, op)),
+            None => {
+                return Err(QueryError::new("ops", format!("invalid op '{}' (expected count, sum, mean, median, stddev, min, max, mode or p0 to p100)", name)));
+            }
+        }
+    }
+    Ok(ops)
+}
+
+/// Log function to monitor server activity with a log file (log.txt unless configured otherwise) One of the few functions that can panic if error occurs
+fn log(path: &Path, msg: &str) {
     // Opens log file with write permissions
     let mut file = match std::fs::OpenOptions::new()
         .create(true)
         .append(true)
-        .open("log.txt")
+        .open(path)
     {
         Ok(f) => f,
         Err(e) => match e.kind() {
@@ -89,504 +689,573 @@ fn log(msg: &str) {
     print!("{}", log_msg);
 }
 
+/// Writes the contents of the data file over what it had
+fn save(path: &Path, contents: &str) -> std::io::Result<()> {
+    let mut file = OpenOptions::new().write(true).truncate(true).open(path)?;
+    file.write_all(contents.as_bytes())
+}
+
 /// Heartbeat function that updates data in memory to be consistent with data stored in file
-/// Runs in a background thread and updates the file every 15 seconds
-async fn heartbeat(data: Arc<RwLock<WeatherDataMap>>, quit: Arc<Mutex<bool>>) {
-    // Gets path of file that will be updated by server
-    let path = std::env::args().skip(1).next().unwrap();
+/// Runs in a background thread and updates the file every save interval (15 seconds by default), keeping the station and
+/// units lines the file had. Never writes to the file in read-only mode
+async fn heartbeat(data: Arc<RwLock<Dataset>>, quit: Arc<Mutex<bool>>, config: Arc<Config>) {
+    if config.read_only {
+        log(&config.log_file, "Read-only mode, the data file is never saved");
+        return;
+    }
 
     // Indicates that heartbeat process has started
-    log("Started heartbeat process");
+    log(&config.log_file, "Started heartbeat process");
 
     //Continuously loops to either update file or stop the heartbeat process
     loop {
         tokio::select! {
-            _ = tokio::time::sleep(std::time::Duration::from_secs(15)) => {},
+            _ = tokio::time::sleep(config.save_interval) => {},
             _ = tokio::signal::ctrl_c() => {
                 *quit.lock().unwrap() = true;
             }
         }
-        let mut file = match OpenOptions::new()
-            .write(true)
-            .truncate(true)
-            .open(path.clone())
-        {
-            Ok(file) => file,
-            Err(e) => panic!("Error: {}", e),
-        };
-
-        match file.write_all(data.read().unwrap().to_file().as_bytes()) {
-            Ok(_) => {}
-            Err(e) => panic!("File error: {}", e),
-        };
-        log("Server updated by heartbeat thread");
+        // A failed save is tried again at the next interval, the data is still all in memory
+        let contents = data.read().unwrap().to_file();
+        match save(&config.data_file, &contents) {
+            Ok(_) => log(&config.log_file, "Server updated by heartbeat thread"),
+            Err(e) => log(&config.log_file, format!("Failed to save data to {}: {}", config.data_file.display(), e).as_str()),
+        }
         if *quit.lock().unwrap() {
             break;
         }
     }
 }
 
-fn startup() -> Arc<RwLock<WeatherDataMap>> {
-    log("Starting weather-ku-api server from specified file path");
-    let args = std::env::args();
-    let file_path = args
-        .skip(1)
-        .next()
-        .expect("Error: No file path in arguments");
-    let file_str = std::fs::read_to_string(&file_path)
+/// Loads the data file, with one or more stations
+fn startup(config: &Config) -> Arc<RwLock<Dataset>> {
+    log(&config.log_file, "Starting weather-ku-api server from specified file path");
+    let file_path = config.data_file.display();
+    let file_str = std::fs::read_to_string(&config.data_file)
         .expect("Error: could not read from specified file path");
-    let data = WeatherData::from_data(file_str)
-        .expect("Error: Failed to parse data (check file for errors)");
-    log("Data loaded successfully!");
-    let data = Arc::new(RwLock::new(data));
-    data
+    let report = match Dataset::from_data_with(file_str, ParseOptions { mode: config.parse_mode }) {
+        Ok(report) => report,
+        Err(e) => {
+            log(&config.log_file, format!("Failed to parse data file {}: {}", file_path, e).as_str());
+            std::process::exit(1);
+        }
+    };
+    if !report.problems.is_empty() {
+        for problem in report.problems.iter() {
+            log(&config.log_file, format!("Skipped invalid data in {}: {}", file_path, problem).as_str());
+        }
+        if config.read_only {
+            // Nothing is ever saved, so the file keeps the days that were skipped
+            log(&config.log_file, format!("{} problem(s) found, {} is left untouched", report.problems.len(), file_path).as_str());
+        } else {
+            // The heartbeat rewrites the file with only the valid days, so the original is kept around
+            let backup_path = format!("{}.bak", file_path);
+            if let Err(e) = std::fs::copy(&config.data_file, &backup_path) {
+                log(&config.log_file, format!("Failed to back up {} to {}: {}", file_path, backup_path, e).as_str());
+                std::process::exit(1);
+            }
+            log(&config.log_file, format!("{} problem(s) found, original data backed up to {}", report.problems.len(), backup_path).as_str());
+        }
+    }
+    for station in report.dataset.stations.values() {
+        match station.units {
+            Some(units) => log(&config.log_file, format!("Data loaded successfully for station {}! Values are in {} units", station.id, units).as_str()),
+            None => log(&config.log_file, format!("Data loaded successfully for station {}! No units line, values are taken to be {}", station.id, UnitSystem::default()).as_str()),
+        }
+    }
+    Arc::new(RwLock::new(report.dataset))
 }
 
-async fn handle_req(
+#[derive(Serialize)]
+/// What `GET /stations` lists about each station
+struct StationSummary<'a> {
+    id: &'a str,
+    name: Option<&'a str>,
+    latitude: Option<f64>,
+    longitude: Option<f64>,
+    elevation: Option<f64>,
+    units: UnitSystem,
+    days: usize,
+    first: Option<Date>,
+    last: Option<Date>,
+}
+
+impl StationSummary<'_> {
+    fn new(station: &Station) -> StationSummary<'_> {
+        StationSummary {
+            id: &station.id,
+            name: station.name.as_deref(),
+            latitude: station.latitude,
+            longitude: station.longitude,
+            elevation: station.elevation,
+            units: station.units.unwrap_or_default(),
+            days: station.data.len(),
+            first: station.data.keys().min().copied(),
+            last: station.data.keys().max().copied(),
+        }
+    }
+}
+
+/// Handles a request, sending back any error it fails with in the format the client asked for, with the CORS headers
+/// for the page that sent it
+async fn route(
     req: Request<hyper::body::Incoming>,
-    data: Arc<RwLock<WeatherDataMap>>,
+    data: Arc<RwLock<Dataset>>,
+    config: Arc<Config>,
 ) -> Result<Response<BoxBody<Bytes, hyper::Error>>, hyper::Error> {
-    let method = req.method();
-    let uri = req.uri();
-    match method {
-        &Method::GET => {
-            let path = uri.path();
-            if !path.starts_with("/q") {
-                return Ok(res_with_body("{\"error\": \"path does not exist\"}", StatusCode::NOT_FOUND));
-            }
-            let query = match uri.query() {
-                Some(query) => query,
-                None => {
-                    return Ok(res_with_body("{\"error\": \"query required\"}", StatusCode::BAD_REQUEST));
-                }
-            };
-            let query_parts: Vec<&str> = query.split('&').collect();
-            let mut query_map: std::collections::HashMap<&str, &str> =
-                std::collections::HashMap::new();
+    let headers = req.headers().clone();
+    let mut res = match dispatch(req, data, &config).await {
+        Ok(res) => res,
+        Err(ApiError::Connection(e)) => return Err(e),
+        Err(e) => error_response(&e, accepts_problem(&headers)),
+    };
+    config.cors.apply(&headers, res.headers_mut());
+    Ok(res)
+}
 
-            for part in query_parts {
-                let kv: Vec<&str> = part.split('=').collect();
-                query_map.insert(kv[0], kv[1]);
-            }
+/// Builds the response to an `OPTIONS` request, which is a CORS preflight if it has an `Access-Control-Request-Method`
+fn options(req: &Request<hyper::body::Incoming>, methods: &[&str]) -> Response<BoxBody<Bytes, hyper::Error>> {
+    let mut res = res_with_body("", StatusCode::NO_CONTENT);
+    let methods: Vec<&str> = methods.iter().copied().chain(["OPTIONS"]).collect();
+    let methods = HeaderValue::from_str(&methods.join(", ")).unwrap();
+    if req.headers().contains_key("access-control-request-method") {
+        res.headers_mut().insert("Access-Control-Allow-Methods", methods.clone());
+    }
+    res.headers_mut().insert("Allow", methods);
+    res
+}
 
-            if query_map.len() > 2 || !query_map.contains_key("dates") {
-                return Ok(res_with_body("{\"error\": invalid query (only dates and values allowed)\"}", StatusCode::BAD_REQUEST));
-            } else if query_map.len() == 2 && !query_map.contains_key("values") {
-                return Ok(res_with_body("{\"error\": \"invalid query (only dates and values allowed)\"}", StatusCode::BAD_REQUEST));
-            }
-            let date_str = *query_map.get("dates").unwrap();
+/// Decodes the `{id}` of a `/stations/{id}` path, where `+` stands for itself rather than a space as it does in a query
+fn station_id(segment: &str) -> Result<String, ApiError> {
+    query::percent_decode(&segment.replace('+', "%2B")).ok_or(ApiError::InvalidPath)
+}
 
-            let split: Vec<&str> = date_str.split("%20").collect();
-            if split.len() != 2 {
-                return Ok(res_with_body("{\"error\": \"Dates field must be in format YYYY-MM-DD%20YYYY-MM-DD\"}", StatusCode::BAD_REQUEST))
-            }
-            let begin_date = match Date::from_string(split[0]) {
-                Ok(date) => date,
-                Err(_) => {
-                    return Ok(res_with_body("{\"error\": \"invalid date format\"}", StatusCode::BAD_REQUEST));
-                }
+/// Sends requests under `/stations/{id}` to the routes of that station with the prefix taken off (`/stations/{id}/q` is `/q`
+/// for that station), and every other request to the first station in the file
+async fn dispatch(mut req: Request<hyper::body::Incoming>, data: Arc<RwLock<Dataset>>, config: &Config) -> HandlerResult {
+    let path = req.uri().path().trim_end_matches('/').to_string();
+    if path == "/stations" {
+        if req.method() == Method::OPTIONS {
+            return Ok(options(&req, &["GET"]));
+        }
+        if req.method() != Method::GET {
+            return Err(ApiError::MethodNotAllowed(&["GET"]));
+        }
+        let dataset = data.read().unwrap();
+        let stations: Vec<StationSummary> = dataset.stations.values().map(StationSummary::new).collect();
+        let body = serde_json::to_string(&stations).expect("stations are always serializable to JSON");
+        return Ok(res_with_body(body, StatusCode::OK));
+    }
+    let station = match req.uri().path().strip_prefix("/stations/") {
+        Some(rest) => {
+            let (id, rest) = match rest.find('/') {
+                Some(i) => rest.split_at(i),
+                None => (rest, "/"),
             };
-            let end_date = match Date::from_string(split[1]) {
-                Ok(date) => date,
-                Err(_) => {
-                    return Ok(res_with_body("{\"error\": \"invalid date format\"}", StatusCode::BAD_REQUEST));
-                }
+            let uri = match req.uri().query() {
+                Some(query) => format!("{}?{}", rest, query),
+                None => rest.to_string(),
             };
-            let data = data.read().unwrap();
-
-            let map: WeatherDataMap = data.take_range(&begin_date, &end_date);
-            let mut points: HashSet<parser::DataPoint> = HashSet::new();
-            if let Some(options) = query_map.get("values") {
-                let points_str: Vec<&str> = options.split(',').collect();
-                points = HashSet::with_capacity(points_str.len());
-
-                for point in points_str {
-                    match point {
-                        "weather_code" => points.insert(parser::DataPoint::WeatherCode),
-                        "temp_max" => points.insert(parser::DataPoint::TemperatureMax),
-                        "temp_min" => points.insert(parser::DataPoint::TemperatureMin),
-                        "precip_sum" => points.insert(parser::DataPoint::PrecipitationSum),
-                        "max_wind" => points.insert(parser::DataPoint::WindSpeedMax),
-                        "prob_precip_max" => points.insert(parser::DataPoint::PrecipitationProbabilityMax),
-                        _ => {
-                            return Ok(res_with_body("{\"error\": \"invalid value field\"}", StatusCode::BAD_REQUEST));
-                        }
-                    };
-                }
-            }
-            let json = map.json(points);
+            let id = station_id(id)?;
+            *req.uri_mut() = match uri.parse() {
+                Ok(uri) => uri,
+                Err(_) => return Err(ApiError::InvalidPath),
+            };
+            id
+        }
+        None => match data.read().unwrap().stations.keys().next() {
+            Some(id) => id.clone(),
+            None => return Err(ApiError::NoStations),
+        },
+    };
+    handle_req(req, data, station, config.read_only).await
+}
 
-            Ok(Response::builder()
-        .status(StatusCode::OK)
-        .header("Content-Type", "application/json")
-        .header("Content-Length", format!("{}", json.len()))
-        .header("Access-Control-Allow-Origin", CORS_ALLOW_ORIGIN)
-        .header("Vary", "Origin")
-        .body(full(json))
-        .unwrap())
+/// Result of every handler, errors are turned into responses by [`route`]
+type HandlerResult = Result<Response<BoxBody<Bytes, hyper::Error>>, ApiError>;
+
+/// A resource of the API, found from the path of a request once any `/stations/{id}` prefix is taken off
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Resource<'a> {
+    /// `/`, where days were added before `/days` existed
+    Root,
+    /// `/days`, every day of the station
+    Days,
+    /// `/days/{date}`, with the date as it is in the path
+    Day(&'a str),
+    /// `/q`, kept as an alias of `/days` along with its `dates=` versions of PUT and DELETE
+    Query,
+    Stats,
+    Events,
+    Import,
+}
+
+impl Resource<'_> {
+    fn from_path(path: &str) -> Option<Resource<'_>> {
+        let segments: Vec<&str> = path.split('/').filter(|segment| !segment.is_empty()).collect();
+        match segments.as_slice() {
+            [] => Some(Resource::Root),
+            ["days"] => Some(Resource::Days),
+            ["days", date] => Some(Resource::Day(date)),
+            ["q"] => Some(Resource::Query),
+            ["stats"] => Some(Resource::Stats),
+            ["events"] => Some(Resource::Events),
+            ["import"] => Some(Resource::Import),
+            _ => None,
         }
-        &Method::POST => {
-            let uri = req.uri();
-            if uri.path() != "/" || uri.query() != None {
-                return Ok(res_with_body("{\"error: path should be empty, no queries accepted\"}", StatusCode::NOT_FOUND));
-            }
-            if req.headers().get("content-type") != Some(&"application/json".parse().unwrap()) {
-                return Ok(res_with_body("{\"error\": \"content-type must be application/json, content-type header REQUIRED\"}", StatusCode::UNSUPPORTED_MEDIA_TYPE))
+    }
+    /// Methods the resource accepts, as listed in the `Allow` header
+    fn methods(&self) -> &'static [&'static str] {
+        match self {
+            Resource::Root | Resource::Import => &["POST"],
+            Resource::Days => &["GET", "POST"],
+            Resource::Day(_) => &["GET", "PUT", "PATCH", "DELETE"],
+            Resource::Query => &["GET", "PUT", "DELETE"],
+            Resource::Stats | Resource::Events => &["GET"],
+        }
+    }
+}
+
+/// Finds the handler for a request to one station, refusing any that would change the data if `read_only` is set
+async fn handle_req(
+    req: Request<hyper::body::Incoming>,
+    data: Arc<RwLock<Dataset>>,
+    station: String,
+    read_only: bool,
+) -> HandlerResult {
+    // Stations are never removed, so once it's found it's always there
+    let units = match data.read().unwrap().get(&station) {
+        Some(station) => station.units.unwrap_or_default(),
+        None => return Err(ApiError::StationNotFound(station)),
+    };
+    let path = req.uri().path().to_string();
+    let resource = match Resource::from_path(&path) {
+        Some(resource) => resource,
+        None => return Err(ApiError::NotFound),
+    };
+    let day = match resource {
+        Resource::Day(date) => match Date::from_string(date) {
+            Ok(date) => Some(date),
+            Err(_) => return Err(ApiError::InvalidPathDate(date.to_string())),
+        },
+        _ => None,
+    };
+    let method = req.method().clone();
+    if read_only && method != Method::GET && resource.methods().contains(&method.as_str()) {
+        return Err(ApiError::ReadOnly);
+    }
+    match (resource, method.as_str(), day) {
+        (Resource::Days | Resource::Query, "GET", _) => get_days(req, &data, &station, units, resource == Resource::Days),
+        (Resource::Stats, "GET", _) => get_stats(req, &data, &station, units),
+        (Resource::Events, "GET", _) => get_events(req, &data, &station, units),
+        (Resource::Root | Resource::Days, "POST", _) => {
+            if req.uri().query().is_some() {
+                return Err(ApiError::UnexpectedQuery);
             }
-            let body = match String::from_utf8(req.collect().await?.to_bytes().to_vec()) {
-                Ok(body) => body,
-                Err(_) => {
-                    return Ok(res_with_body("{\"error\": \"body must be valid utf-8 text\"}", StatusCode::UNSUPPORTED_MEDIA_TYPE));
-                }
-            };
+            post_days(req, &data, &station, units, None).await
+        }
+        (Resource::Import, "POST", _) => {
+            let strategy = query_strategy(&Query::parse(req.uri().query().unwrap_or(""))?)?;
+            post_days(req, &data, &station, units, Some(strategy)).await
+        }
+        (Resource::Query, "PUT", _) => put_dates(req, &data, &station).await,
+        (Resource::Query, "DELETE", _) => delete_dates(req, &data, &station),
+        (Resource::Day(_), "GET", Some(date)) => get_day(req, &data, &station, units, date),
+        (Resource::Day(_), "PUT", Some(date)) => put_day(req, &data, &station, date, false).await,
+        (Resource::Day(_), "PATCH", Some(date)) => put_day(req, &data, &station, date, true).await,
+        (Resource::Day(_), "DELETE", Some(date)) => delete_day(&data, &station, date),
+        (_, "OPTIONS", _) => Ok(options(&req, resource.methods())),
+        _ => Err(ApiError::MethodNotAllowed(resource.methods())),
+    }
+}
 
-            let values: Vec<Value> = match serde_json::from_str(&body) {
-                Ok(Value::Array(data)) => data,
-                Ok(_) => {
-                    return Ok(res_with_body("{\"error\": \"body must be a json array\"}", StatusCode::BAD_REQUEST));
-                }
-                Err(_) => {
-                    return Ok(res_with_body("{\"error\": \"body must be valid json\"}", StatusCode::BAD_REQUEST));
-                }
-            };
+/// The days a GET request asks for, in the units it asks for them in
+struct DaysQuery {
+    query: Query,
+    to_units: UnitSystem,
+    begin_date: Date,
+    end_date: Date,
+    fill: bool,
+    map: WeatherDataMap,
+}
 
-            let points = vec![
-                "date",
-                "weather_code",
-                "temperature_max",
-                "temperature_min",
-                "precipitation_sum",
-                "wind_speed_max",
-                "precipitation_probability_max",
-            ];
-            let mut to_add: WeatherDataMap = IndexMap::with_capacity(values.len());
-            for item in values {
-                let mut date: Date = Date::from_string("0-0-0").unwrap();
-                let mut temp_max: f32 = 0.0;
-                let mut temp_min: f32 = 0.0;
-                let mut precip_sum: f32 = 0.0;
-                let mut wind_speed_max: f32 = 0.0;
-                let mut precip_prob_max: f32 = 0.0;
-                let mut weather_code: u8 = 0;
-
-                let item_obj = match item.as_object() {
-                    Some(obj) => obj,
-                    None => {
-                        return Ok(res_with_body("{\"error\": \"body must be a JSON array of objects\"}", StatusCode::BAD_REQUEST))
-                    }
-                };
-
-                for point in points.iter() {
-                    match *point {
-                        "date" => {
-                            let date_str = match item_obj.get("date") {
-                                Some(date) => date,
-                                None => {
-                                    return Ok(res_with_body("{\"error\": \"date field required\"}", StatusCode::BAD_REQUEST));
-                                }
-                            };
-                            let date_str = match date_str.as_str() {
-                                Some(date) => date,
-                                None => {
-                                    return Ok(res_with_body("{\"error\": \"date field must be a string\"}", StatusCode::BAD_REQUEST))
-                                }
-                            };
-                            let new_date = match Date::from_string(date_str) {
-                                Ok(new_date) => new_date,
-                                Err(_) => {
-                                    return Ok(res_with_body("{\"error\": \"date field must be in format YYYY-MM-DD\"}", StatusCode::BAD_REQUEST));
-                                }
-                            };
-                            if data.read().unwrap().contains_key(&new_date) {
-                                return Ok(res_with_body("{\"error\": \"date already exists\"}", StatusCode::BAD_REQUEST));
-                            }
-                            date = new_date;
-                        }
-                        "weather_code" => {
-                            let code = match item_obj.get("weather_code") {
-                                Some(code) => code,
-                                None => {
-                                    return Ok(res_with_body("{\"error\": \"weather_code field required\"}", StatusCode::BAD_REQUEST));
-                                }
-                            };
-                            let code = match code.as_u64() {
-                                Some(code) => {
-                                    if code > 255 {
-                                        return Ok(res_with_body("{\"error\": \"weather_code field must be a number between 0 and 255\"}", StatusCode::BAD_REQUEST));
-                                    } else {
-                                        code
-                                    }
-                                }
-                                None => {
-                                    return Ok(res_with_body("{\"error\": \"weather_code field must be a number\"}", StatusCode::BAD_REQUEST));
-                                }
-                            };
-                            weather_code = code as u8;
-                        }
-                        "temperature_max" => {
-                            let temp = match item_obj.get("temperature_max") {
-                                Some(temp) => temp,
-                                None => {
-                                    return Ok(res_with_body("{\"error\": \"temperature_max field required\"}", StatusCode::BAD_REQUEST));
-                                }
-                            };
-                            let temp = match temp.as_f64() {
-                                Some(temp) => temp,
-                                None => {
-                                    return Ok(res_with_body("{\"error\": \"temperature_max field must be a number\"}", StatusCode::BAD_REQUEST));
-                                }
-                            };
-                            temp_max = temp as f32;
-                        }
-                        "temperature_min" => {
-                            let temp = match item_obj.get("temperature_min") {
-                                Some(temp) => temp,
-                                None => {
-                                    return Ok(res_with_body("{\"error\": \"temperature_min field required\"}", StatusCode::BAD_REQUEST));
-                                }
-                            };
-                            let temp = match temp.as_f64() {
-                                Some(temp) => temp,
-                                None => {
-                                    return Ok(res_with_body("{\"error\": \"temperature_min field must be a number\"}", StatusCode::BAD_REQUEST));
-                                }
-                            };
-                            temp_min = temp as f32;
-                        }
-                        "precipitation_sum" => {
-                            let precip = match item_obj.get("precipitation_sum") {
-                                Some(precip) => precip,
-                                None => {
-                                    return Ok(res_with_body("{\"error\": \"precipitation_sum field required\"}", StatusCode::BAD_REQUEST));
-                                }
-                            };
-                            let precip = match precip.as_f64() {
-                                Some(precip) => precip,
-                                None => {
-                                    return Ok(res_with_body("{\"error\": \"precipitation_sum field must be a number\"}", StatusCode::BAD_REQUEST));
-                                }
-                            };
-                            precip_sum = precip as f32;
-                        }
-                        "wind_speed_max" => {
-                            let wind = match item_obj.get("wind_speed_max") {
-                                Some(wind) => wind,
-                                None => {
-                                    return Ok(res_with_body("{\"error\": \"wind_speed_max field required\"}", StatusCode::BAD_REQUEST));
-                                }
-                            };
-                            let wind = match wind.as_f64() {
-                                Some(wind) => wind,
-                                None => {
-                                    return Ok(res_with_body("{\"error\": \"wind_speed_max field must be a number\"}", StatusCode::BAD_REQUEST));
-                                }
-                            };
-                            wind_speed_max = wind as f32;
-                        }
-                        "precipitation_probability_max" => {
-                            let prob = match item_obj.get("precipitation_probability_max") {
-                                Some(prob) => prob,
-                                None => {
-                                    return Ok(res_with_body("{\"error\": \"precipitation_probability_max field required\"}", StatusCode::BAD_REQUEST));
-                                }
-                            };
-                            let prob = match prob.as_f64() {
-                                Some(prob) => prob,
-                                None => {
-                                    return Ok(res_with_body("{\"error\": \"precipitation_probability_max field must be a number\"}", StatusCode::BAD_REQUEST));
-                                }
-                            };
-                            precip_prob_max = prob as f32;
-                        }
-                        _ => {
-                            return Ok(res_with_body("{\"error\": \"invalid value field\"}", StatusCode::BAD_REQUEST));
-                        }
-                    }
-                }
-                if to_add.contains_key(&date) {
-                    return Ok(res_with_body("{\"error\": \"duplicate date found\"}", StatusCode::BAD_REQUEST));
-                }
-                to_add.insert(
-                    date,
-                    WeatherData::new(
-                        date,
-                        weather_code,
-                        temp_max,
-                        temp_min,
-                        precip_sum,
-                        wind_speed_max,
-                        precip_prob_max,
-                    ),
-                );
-            }
+/// Reads the parameters every GET route has: the range of days, `units=` and `fill=`, checking that no parameter outside
+/// of `allowed` is there
+///
+/// The range is given either as `dates=` with both ends, or as `from=` and `to=` where a missing end leaves the range open
+/// on that side. Without any of them every day is taken if `open_range` is set
+fn days_query(req: &Request<hyper::body::Incoming>, allowed: &[&str], data: &RwLock<Dataset>, station: &str, units: UnitSystem, open_range: bool) -> Result<DaysQuery, QueryError> {
+    let query = Query::parse(req.uri().query().unwrap_or(""))?;
+    query.only(allowed)?;
+    let to_units = query_units(&query, units)?;
+    let bounded = query.contains("from") || query.contains("to");
+    let (begin_date, end_date) = match (query.contains("dates"), bounded) {
+        (true, true) => return Err(QueryError::new("dates", "dates can't be combined with from or to")),
+        (true, false) => query_dates(&query)?,
+        (false, false) if !open_range => return Err(QueryError::missing("dates", "dates (or from and to) required")),
+        (false, _) => query_from_to(&query)?,
+    };
+    let fill = query_flag(&query, "fill")?;
+    let map = days_in_range(data, station, &begin_date, &end_date, fill).convert_units(units, to_units);
+    Ok(DaysQuery { query, to_units, begin_date, end_date, fill, map })
+}
 
-            let mut data_write = data.write().unwrap();
-            for item in &to_add {
-                data_write.insert(item.0.clone(), item.1.clone());
-            }
+/// Days of a station from one date to another, with the missing days in between made up and marked as synthetic if `fill` is set
+fn days_in_range(data: &RwLock<Dataset>, station: &str, begin: &Date, end: &Date, fill: bool) -> WeatherDataMap {
+    let range = data.read().unwrap().stations[station].data.take_range(begin, end);
+    if fill { range.fill_gaps() } else { range }
+}
 
-            return Ok(res_with_body("{\"success\": \"data successfully added\"}", StatusCode::OK));
+/// `GET /days` and `GET /q`, the days in a range with any derived values, grouping, smoothing and quality flags asked for
+fn get_days(req: Request<hyper::body::Incoming>, data: &RwLock<Dataset>, station: &str, units: UnitSystem, open_range: bool) -> HandlerResult {
+    let allowed = &["dates", "from", "to", "values", "units", "group_by", "rolling", "hdd_base", "cdd_base", "gdd_base", "gdd_cap", "qc", "outliers", "fill"];
+    let DaysQuery { query, to_units, begin_date, end_date, fill, map } = days_query(&req, allowed, data, station, units, open_range)?;
+    // Degree day bases are given in the units of the response
+    let (points, derived) = query_values(&query, &query_bases(&query, to_units)?)?;
+    // Only derived values were asked for, the fields they come from aren't sent
+    let only_derived = points.is_empty() && !derived.is_empty();
+    let (period, rolling) = match (query_period(&query)?, query_rolling(&query)?) {
+        (Some(_), Some(_)) => return Err(QueryError::new("rolling", "rolling can't be combined with group_by").into()),
+        (period, rolling) => (period, rolling),
+    };
+    let (qc, outliers) = (query_flag(&query, "qc")?, query_outliers(&query)?);
+    // Each period comes back as a single entry dated on its first day
+    let daily = map;
+    let map = match period {
+        Some(period) => daily.resample(period, ResamplePolicy::default()),
+        None => daily.clone(),
+    };
+    let columns = derived_columns(&daily, &map, &derived, period);
+    // Only the fields that get smoothed, the weather code never does
+    let rolling_points: Vec<DataPoint> = DataPoint::ALL
+        .into_iter()
+        .filter(|point| stats::RollingOp::for_field(*point).is_some() && !only_derived && (points.is_empty() || points.contains(point)))
+        .collect();
+    let smoothed = match rolling {
+        Some(days) if !rolling_points.is_empty() => {
+            // Windows at the start of the range reach back before it
+            let first = begin_date.add_days(1 - days as i64).unwrap_or(begin_date);
+            let smoothed = days_in_range(data, station, &first, &end_date, fill).rolling(days).convert_units(units, to_units);
+            Some(smoothed.into_iter().filter(|(date, _)| map.contains_key(date)).collect::<WeatherDataMap>())
         }
-        &Method::PUT => {
-            let path = uri.path();
-            if !path.starts_with("/q") {
-                return Ok(res_with_body("{\"error\": \"path does not exist\"}", StatusCode::NOT_FOUND));
-            }
-            let query = match uri.query() {
-                Some(query) => query,
-                None => {
-                    return Ok(res_with_body("{\"error\": \"date query required\"}", StatusCode::BAD_REQUEST));
-                }
-            };
-            if !query.starts_with("dates=") {
-                return Ok(res_with_body("{\"error\": \"date query required\"}", StatusCode::BAD_REQUEST));
-            }
-            let date_str = query.strip_prefix("dates=").unwrap();
-            let dates: Vec<&str> = date_str.split("%20").collect();
-            let mut dates_to_change: Vec<Date> = Vec::with_capacity(dates.len());
-            for date_str in dates {
-                let date = match Date::from_string(date_str) {
-                    Ok(date) => date,
-                    Err(_) => {
-                        return Ok(res_with_body("{\"error\": \"invalid date format\"}", StatusCode::BAD_REQUEST));
-                    }
-                };
-                dates_to_change.push(date);
-            }
+        _ => None,
+    };
+    // Checked in the units of the response, so the limits match the values
+    let flags = qc.then(|| map.check_quality(to_units, outliers));
+    let csv = accepts_csv(req.headers());
+    let table = DaysTable {
+        map: &map,
+        points: DataPoint::ALL.into_iter().filter(|point| !only_derived && (points.is_empty() || points.contains(point))).collect(),
+        columns,
+        smoothed,
+        rolling_points,
+        flags,
+    };
+    let body = if csv { table.csv() } else { table.json() };
+    let mut res = if csv { res_with_type(body, StatusCode::OK, "text/csv") } else { res_with_body(body, StatusCode::OK) };
+    res.headers_mut().insert("Vary", HeaderValue::from_static("Origin, Accept"));
+    Ok(res)
+}
 
-            let body = match String::from_utf8(req.collect().await?.to_bytes().to_vec()) {
-                Ok(body) => body,
-                Err(_) => {
-                    return Ok(res_with_body("{\"error\": \"body must be valid utf-8 text\"}", StatusCode::UNSUPPORTED_MEDIA_TYPE));
-                }
-            };
-            let values: Vec<Value> = match serde_json::from_str(&body) {
-                Ok(Value::Array(data)) => data,
-                Ok(_) => {
-                    return Ok(res_with_body("{\"error\": \"body must be a json array\"}", StatusCode::BAD_REQUEST));
-                }
-                Err(_) => {
-                    return Ok(res_with_body("{\"error\": \"body must be valid json\"}", StatusCode::BAD_REQUEST));
-                }
-            };
-            if values.len() != dates_to_change.len() {
-                return Ok(res_with_body("{\"error\": \"number of dates and values must be equal\"}", StatusCode::BAD_REQUEST));
-            }
-            let mut index: u16 = 0;
-            let mut data = data.write().unwrap();
-            for value in values {
-                if !value.is_object() {
-                    return Ok(res_with_body("{\"error\": \"body must be a json array of objects\"}", StatusCode::BAD_REQUEST));
-                }
-                let value = value.as_object().unwrap();
-                match data.get_mut(&dates_to_change[index as usize]) {
-                    Some(changing) => {
-                        if let Some(weather_code) = value.get("weather_code") {
-                            if let Some(weather_code) = weather_code.as_u64() {
-                                if weather_code > 255 {
-                                    return Ok(res_with_body("{\"error\": \"weather_code must be a number between 0 and 255\"}", StatusCode::BAD_REQUEST))
-                                }
-                                changing.weather_code = weather_code as u8;
-                            } else {
-                                return Ok(res_with_body("{\"error\": \"weather_code must be a number\"}", StatusCode::BAD_REQUEST));
-                            }
-                        }
-                        if let Some(temp_max) = value.get("temperature_max") {
-                            if let Some(temp_max) = temp_max.as_f64() {
-                                changing.temp_max = temp_max as f32;
-                            } else {
-                                return Ok(res_with_body("{\"error\": \"temperature_max must be a number\"}", StatusCode::BAD_REQUEST));
-                            }
-                        }
-                        if let Some(temp_min) = value.get("temperature_min") {
-                            if let Some(temp_min) = temp_min.as_f64() {
-                                changing.temp_min = temp_min as f32;
-                            } else {
-                                return Ok(res_with_body("{\"error\": \"temperature_min must be a number\"}", StatusCode::BAD_REQUEST));
-                            }
-                        }
-                        if let Some(precip_sum) = value.get("precipitation_sum") {
-                            if let Some(precip_sum) = precip_sum.as_f64() {
-                                changing.precip_sum = precip_sum as f32;
-                            } else {
-                                return Ok(res_with_body("{\"error\": \"precipitation_sum must be a number\"}", StatusCode::BAD_REQUEST));
-                            }
-                        }
-                        if let Some(wind_speed_max) = value.get("wind_speed_max") {
-                            if let Some(wind_speed_max) = wind_speed_max.as_f64() {
-                                changing.max_wind = wind_speed_max as f32;
-                            } else {
-                                return Ok(res_with_body("{\"error\": \"wind_speed_max must be a number\"}", StatusCode::BAD_REQUEST));
-                            }
-                        }
-                        if let Some(precip_prob_max) = value.get("precipitation_probability_max") {
-                            if let Some(precip_prob_max) = precip_prob_max.as_f64() {
-                                changing.precip_prob_max = precip_prob_max as f32;
-                            } else {
-                                return Ok(res_with_body("{\"error\": \"precipitation_probability_max must be a number\"}", StatusCode::BAD_REQUEST));
-                            }
-                        }
-                    }
-                    None => {
-                        return Ok(res_with_body("{\"error\": \"date does not exist\"}", StatusCode::BAD_REQUEST));
-                    }
-                }
-                index += 1;
-            }
+/// `GET /stats`, statistics of each field asked for over a range of days
+fn get_stats(req: Request<hyper::body::Incoming>, data: &RwLock<Dataset>, station: &str, units: UnitSystem) -> HandlerResult {
+    let allowed = &["dates", "from", "to", "values", "units", "ops", "hdd_base", "cdd_base", "gdd_base", "gdd_cap", "fill"];
+    let DaysQuery { query, to_units, map, .. } = days_query(&req, allowed, data, station, units, false)?;
+    let (points, derived) = query_values(&query, &query_bases(&query, to_units)?)?;
+    let ops = query_ops(&query)?;
+    let only_derived = points.is_empty() && !derived.is_empty();
+    // One object per field, in the same order as the fields of /q, with the ops in the order they were asked for
+    // followed by the derived values
+    let mut fields: IndexMap<&str, IndexMap<&str, StatValue>> = IndexMap::new();
+    let selected = DataPoint::ALL
+        .into_iter()
+        .filter(|point| !only_derived && (points.is_empty() || points.contains(point)))
+        .map(|point| (point.name(), Field::Point(point)));
+    for (name, field) in selected.chain(derived.iter().map(|value| (value.name(), Field::Derived(*value)))) {
+        let results = ops.iter().filter_map(|(name, op)| Some((*name, op.run(&map, field)?))).collect();
+        fields.insert(name, results);
+    }
+    let body = serde_json::to_string(&fields).expect("stats are always serializable to JSON");
+    Ok(res_with_body(body, StatusCode::OK))
+}
 
-            return Ok(res_with_body("{\"success\": \"Data successfully updated\"}", StatusCode::OK));
-        }
-        &Method::DELETE => {
-            if uri.path() != "/q" {
-                return Ok(res_with_body("{\"error\": \"path does not exist\"}", StatusCode::NOT_FOUND));
-            }
-            let query = match uri.query() {
-                Some(query) => query,
-                None => {
-                    return Ok(res_with_body("{\"error\": \"date query required\"}", StatusCode::BAD_REQUEST));
-                }
-            };
-            if !query.starts_with("dates=") {
-                return Ok(res_with_body("{\"error\": \"date query required\"}", StatusCode::BAD_REQUEST));
-            }
-            let date_str = query.strip_prefix("dates=").unwrap();
-            let dates: Vec<&str> = date_str.split("%20").collect();
-            let mut dates_to_delete: Vec<Date> = Vec::with_capacity(dates.len());
-            for date_str in dates {
-                let date = match Date::from_string(date_str) {
-                    Ok(date) => date,
-                    Err(_) => {
-                        return Ok(res_with_body("{\"error\": \"invalid date format\"}", StatusCode::BAD_REQUEST));
-                    }
-                };
-                dates_to_delete.push(date);
-            }
-            let mut data = data.write().unwrap();
-            for date in dates_to_delete {
-                if !data.contains_key(&date) {
-                    return Ok(res_with_body("{\"error\": \"date does not exist\"}", StatusCode::BAD_REQUEST));
-                }
-                data.shift_remove_full(&date);
+/// `GET /events`, the runs of days meeting the threshold of each kind of event asked for
+fn get_events(req: Request<hyper::body::Incoming>, data: &RwLock<Dataset>, station: &str, units: UnitSystem) -> HandlerResult {
+    let allowed = &["dates", "from", "to", "units", "kinds", "wet", "heat", "frost", "wind", "heat_wave_days", "fill"];
+    let DaysQuery { query, to_units, map, .. } = days_query(&req, allowed, data, station, units, false)?;
+    let (kinds, thresholds) = (query_kinds(&query)?, query_thresholds(&query, to_units)?);
+    // One object per kind, in the order they were asked for
+    let events: IndexMap<&str, EventSummary> =
+        kinds.iter().map(|kind| (kind.name(), EventSummary::new(map.events(*kind, &thresholds)))).collect();
+    let body = serde_json::to_string(&events).expect("events are always serializable to JSON");
+    Ok(res_with_body(body, StatusCode::OK))
+}
+
+/// Reads the body of a request as text
+async fn body_text(req: Request<hyper::body::Incoming>) -> Result<String, ApiError> {
+    String::from_utf8(req.collect().await?.to_bytes().to_vec()).map_err(|_| ApiError::InvalidEncoding)
+}
+
+/// Reads a JSON list of days or of hourly observations, which have a time instead of a date. Every record has to be
+/// the same kind as the first, hours are summarized into days the same way hourly CSV is
+fn json_records(body: &str) -> Result<WeatherDataMap, ApiError> {
+    let records: Vec<serde_json::Value> = serde_json::from_str(body)?;
+    let is_hour = |record: &serde_json::Value| record.get("time").is_some() && record.get("date").is_none();
+    let hourly = records.first().is_some_and(is_hour);
+    if let Some(index) = records.iter().position(|record| is_hour(record) != hourly) {
+        return Err(ApiError::MixedRecords { index, hourly });
+    }
+    let records = serde_json::Value::Array(records);
+    if hourly {
+        let hours = HourlyData::from_records(Vec::deserialize(records)?)?;
+        Ok(HourlyData::daily(&hours, ResamplePolicy::default()))
+    } else {
+        Ok(parser::records::deserialize(records)?)
+    }
+}
+
+/// `POST /days` (and `/`), adding days that aren't there yet, or `POST /import` with a strategy for merging the days that are
+async fn post_days(req: Request<hyper::body::Incoming>, data: &RwLock<Dataset>, station: &str, units: UnitSystem, strategy: Option<MergeStrategy>) -> HandlerResult {
+    let is_csv = match content_type(req.headers()).as_deref() {
+        Some("application/json") => false,
+        Some("text/csv") => true,
+        _ => return Err(ApiError::UnsupportedMediaType(&["application/json", "text/csv"])),
+    };
+    let body = body_text(req).await?;
+
+    let to_add: WeatherDataMap = if is_csv {
+        WeatherDataMap::from_csv(body.as_bytes(), CsvOptions::default())?.data
+    } else if body.trim_start().starts_with('{') {
+        // An Open-Meteo response posted as downloaded, instead of an array of days
+        let import = parser::from_open_meteo(&body, ParseOptions::default())?;
+        import.report.data.convert_units(import.report.units.unwrap_or(units), units)
+    } else {
+        json_records(&body)?
+    };
+
+    let mut dataset = data.write().unwrap();
+    let data_write = &mut dataset.stations[station].data;
+    if let Some(strategy) = strategy {
+        let report = data_write.merge(&to_add, strategy).map_err(ApiError::MergeConflict)?;
+        *data_write = report.data;
+        let summary = ImportSummary {
+            added: report.added,
+            overlapping: report.overlapping,
+            strategy: report.strategy.name(),
+            conflicts: report.conflicts,
+        };
+        let body = serde_json::to_string(&summary).expect("import summaries are always serializable to JSON");
+        return Ok(res_with_body(body, StatusCode::OK));
+    }
+    if let Some(date) = to_add.keys().find(|date| data_write.contains_key(*date)) {
+        return Err(ApiError::DateExists(*date));
+    }
+    data_write.extend(to_add);
+    data_write.sort_keys();
+
+    Ok(res_with_body("{\"success\": \"data successfully added\"}", StatusCode::OK))
+}
+
+/// Reads the `dates=` parameter of `PUT /q` and `DELETE /q`, a list of dates separated by spaces or commas
+fn query_date_list(req: &Request<hyper::body::Incoming>) -> Result<Vec<Date>, QueryError> {
+    let query = Query::parse(req.uri().query().unwrap_or(""))?;
+    query.only(&["dates"])?;
+    let dates = query.list("dates");
+    if dates.is_empty() {
+        return Err(QueryError::missing("dates", "dates required"));
+    }
+    dates
+        .into_iter()
+        .map(|date| Date::from_string(date).map_err(|_| QueryError::new("dates", format!("invalid date '{}', expected YYYY-MM-DD", date))))
+        .collect()
+}
+
+/// `PUT /q?dates=`, changing the fields given for each date, one object per date in the same order
+///
+/// Unlike the `/days` routes the body is read as JSON whatever its content type, the way `/q` always has
+async fn put_dates(req: Request<hyper::body::Incoming>, data: &RwLock<Dataset>, station: &str) -> HandlerResult {
+    let dates_to_change = query_date_list(&req)?;
+    let body = body_text(req).await?;
+    let updates: Vec<WeatherDataUpdate> = serde_json::from_str(&body)?;
+    if updates.len() != dates_to_change.len() {
+        return Err(ApiError::CountMismatch { dates: dates_to_change.len(), updates: updates.len() });
+    }
+    let mut dataset = data.write().unwrap();
+    let data = &mut dataset.stations[station].data;
+    if let Some(date) = dates_to_change.iter().find(|date| !data.contains_key(*date)) {
+        return Err(ApiError::DateNotFound(*date));
+    }
+    for (date, update) in dates_to_change.iter().zip(updates) {
+        update.apply(data.get_mut(date).unwrap());
+    }
+
+    Ok(res_with_body("{\"success\": \"Data successfully updated\"}", StatusCode::OK))
+}
+
+/// `DELETE /q?dates=`, removing every date listed, or none of them if any isn't there
+fn delete_dates(req: Request<hyper::body::Incoming>, data: &RwLock<Dataset>, station: &str) -> HandlerResult {
+    let dates_to_delete = query_date_list(&req)?;
+    let mut dataset = data.write().unwrap();
+    let data = &mut dataset.stations[station].data;
+    if let Some(date) = dates_to_delete.iter().find(|date| !data.contains_key(*date)) {
+        return Err(ApiError::DateNotFound(*date));
+    }
+    for date in dates_to_delete {
+        data.shift_remove(&date);
+    }
+    Ok(res_with_body("{\"success\": \"Data successfully deleted\"}", StatusCode::OK))
+}
+
+/// `GET /days/{date}`, a single day as an object
+fn get_day(req: Request<hyper::body::Incoming>, data: &RwLock<Dataset>, station: &str, units: UnitSystem, date: Date) -> HandlerResult {
+    let query = Query::parse(req.uri().query().unwrap_or(""))?;
+    query.only(&["units"])?;
+    let to_units = query_units(&query, units)?;
+    let day = match data.read().unwrap().stations[station].data.get(&date) {
+        Some(day) => day.converted(units, to_units),
+        None => return Err(ApiError::DateNotFound(date)),
+    };
+    // Written the same way as the days of /days, without the array around it
+    let body = serde_json::to_string(&PointsView::new(&day, &DataPoint::ALL)).expect("weather data is always serializable to JSON");
+    Ok(res_with_body(body, StatusCode::OK))
+}
+
+/// `PUT /days/{date}`, setting every field of a day and adding it if it isn't there (fields left out are missing),
+/// or `PATCH /days/{date}`, changing only the fields given for a day that is there
+async fn put_day(req: Request<hyper::body::Incoming>, data: &RwLock<Dataset>, station: &str, date: Date, patch: bool) -> HandlerResult {
+    require_json(req.headers())?;
+    let body = body_text(req).await?;
+    let update: WeatherDataUpdate = serde_json::from_str(&body)?;
+    let mut dataset = data.write().unwrap();
+    let data = &mut dataset.stations[station].data;
+    if patch {
+        return match data.get_mut(&date) {
+            Some(day) => {
+                update.apply(day);
+                Ok(res_with_body("{\"success\": \"Data successfully updated\"}", StatusCode::OK))
             }
-            return Ok(res_with_body("{\"success\": \"Data successfully deleted\"}", StatusCode::OK));
-        }
-        &Method::OPTIONS => {
-            return Ok(res_with_body("{\"error\": \"path does not exist\"}", StatusCode::NOT_FOUND));
-        }
-        _ => {
-            return Ok(res_with_body("{\"error\": \"method not allowed\"}", StatusCode::METHOD_NOT_ALLOWED));
-        }
+            None => Err(ApiError::DateNotFound(date)),
+        };
     }
+    let mut day = WeatherData::empty(date);
+    update.apply(&mut day);
+    if data.insert(date, day).is_some() {
+        return Ok(res_with_body("{\"success\": \"Data successfully replaced\"}", StatusCode::OK));
+    }
+    data.sort_keys();
+    Ok(res_with_body("{\"success\": \"data successfully added\"}", StatusCode::CREATED))
 }
 
-fn full<T: Into<Bytes>>(buf: T) -> BoxBody<Bytes, hyper::Error> {
-    Full::new(buf.into())
-        .map_err(|never| match never {})
-        .boxed()
+/// `DELETE /days/{date}`
+fn delete_day(data: &RwLock<Dataset>, station: &str, date: Date) -> HandlerResult {
+    match data.write().unwrap().stations[station].data.shift_remove(&date) {
+        Some(_) => Ok(res_with_body("{\"success\": \"Data successfully deleted\"}", StatusCode::OK)),
+        None => Err(ApiError::DateNotFound(date)),
+    }
 }
 
-fn empty() -> BoxBody<Bytes, hyper::Error> {
-    Empty::<Bytes>::new()
+fn full<T: Into<Bytes>>(buf: T) -> BoxBody<Bytes, hyper::Error> {
+    Full::new(buf.into())
         .map_err(|never| match never {})
         .boxed()
 }
@@ -600,13 +1269,20 @@ async fn shutdown_signal() {
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    let data = startup();
+    let config = match Config::load() {
+        Ok(config) => Arc::new(config),
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    };
+    let data = startup(&config);
     let is_quit = Arc::new(Mutex::new(false));
-    let heartbeat_thread = tokio::spawn(heartbeat(data.clone(), is_quit.clone()));
+    let heartbeat_thread = tokio::spawn(heartbeat(data.clone(), is_quit.clone(), config.clone()));
     data.clear_poison();
 
-    let addr = SocketAddr::from(([127, 0, 0, 1], 3000));
-    let listener = TcpListener::bind(&addr).await?;
+    let listener = TcpListener::bind(&config.addr).await?;
+    log(&config.log_file, format!("Listening on {}", config.addr).as_str());
     let http = http1::Builder::new();
     let graceful = hyper_util::server::graceful::GracefulShutdown::new();
     let mut signal = std::pin::pin!(shutdown_signal());
@@ -616,18 +1292,20 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
             Ok((stream, _addr)) = listener.accept() => {
                 let io = TokioIo::new(stream);
                 let data_ref = data.clone();
-                let conn = http.serve_connection(io, service_fn(move |req| {handle_req(req, data_ref.clone())}));
+                let config_ref = config.clone();
+                let conn = http.serve_connection(io, service_fn(move |req| {route(req, data_ref.clone(), config_ref.clone())}));
                 // watch this connection
                 let fut = graceful.watch(conn);
+                let log_file = config.log_file.clone();
                 tokio::spawn(async move {
                     if let Err(e) = fut.await {
-                        log(format!("Error serving connection: {:?}", e).as_str());
+                        log(&log_file, format!("Error serving connection: {:?}", e).as_str());
                     }
                 });
             },
 
             _ = &mut signal => {
-                log("Graceful shutdown signal received");
+                log(&config.log_file, "Graceful shutdown signal received");
                 // stop the accept loop
                 break;
             }
@@ -640,10 +1318,10 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
             match heartbeat_thread.await{
                 Ok(_) => {},
                 Err(e) => {
-                    log(format!("Error with shutting down heartbeat thread: {:?}", e).as_str());
+                    log(&config.log_file, format!("Error with shutting down heartbeat thread: {:?}", e).as_str());
                 }
             };
-            log("Server shutdown completed without errors");
+            log(&config.log_file, "Server shutdown completed without errors");
 
         },
         // If the graceful shutdown times out, print an error message
@@ -652,11 +1330,159 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
             match heartbeat_thread.await{
                 Ok(_) => {},
                 Err(e) => {
-                    log(format!("Error with shutting down heartbeat thread: {:?}", e).as_str());
+                    log(&config.log_file, format!("Error with shutting down heartbeat thread: {:?}", e).as_str());
                 }
             };
-            log("Server timed out wait for all connections to close");
+            log(&config.log_file, "Server timed out wait for all connections to close");
         }
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resources_from_paths() {
+        assert_eq!(Resource::from_path("/"), Some(Resource::Root));
+        assert_eq!(Resource::from_path(""), Some(Resource::Root));
+        assert_eq!(Resource::from_path("/days"), Some(Resource::Days));
+        assert_eq!(Resource::from_path("/days/"), Some(Resource::Days));
+        assert_eq!(Resource::from_path("/days/2024-04-24"), Some(Resource::Day("2024-04-24")));
+        assert_eq!(Resource::from_path("/q"), Some(Resource::Query));
+        assert_eq!(Resource::from_path("/stats"), Some(Resource::Stats));
+        assert_eq!(Resource::from_path("/events"), Some(Resource::Events));
+        assert_eq!(Resource::from_path("/import"), Some(Resource::Import));
+        assert_eq!(Resource::from_path("/days/2024-04-24/extra"), None);
+        assert_eq!(Resource::from_path("/nope"), None);
+    }
+
+    #[test]
+    fn method_not_allowed_lists_the_methods() {
+        let e = ApiError::MethodNotAllowed(Resource::Day("2024-04-24").methods());
+        let res = error_response(&e, false);
+        assert_eq!(res.status(), StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(res.headers()["Allow"], "GET, PUT, PATCH, DELETE");
+        let res = error_response(&ApiError::MethodNotAllowed(Resource::Import.methods()), false);
+        assert_eq!(res.headers()["Allow"], "POST");
+        // Other errors don't get one
+        assert!(!error_response(&ApiError::NotFound, false).headers().contains_key("Allow"));
+    }
+
+    /// Two days of data with a derived column, read from the column format
+    fn table_data() -> WeatherDataMap {
+        WeatherData::from_data(
+            "date: 2024-04-24 2024-04-25\nweather_code: 3 61\ntemperature_max: 61.9 52.6\ntemperature_min: 47.1 44.2\n\
+             precipitation_sum: 0 0.22\nwind_speed_max: 9.3 10.1\nprecipitation_probability_max: 45 100"
+                .to_string(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn days_table_adds_derived_columns() {
+        let map = table_data();
+        let columns = derived_columns(&map, &map, &[Derived::HeatingDegreeDays { base: 65.0 }], None);
+        let table = DaysTable { map: &map, points: vec![DataPoint::TemperatureMax], columns, smoothed: None, rolling_points: vec![], flags: None };
+        assert_eq!(
+            table.json(),
+            "[{\"date\":\"2024-04-24\",\"temperature_max\":61.9,\"heating_degree_days\":10.5,\"heating_degree_days_cumulative\":10.5},\
+             {\"date\":\"2024-04-25\",\"temperature_max\":52.6,\"heating_degree_days\":16.599998,\"heating_degree_days_cumulative\":27.099998}]"
+        );
+        assert_eq!(
+            table.csv(),
+            "date,temperature_max,heating_degree_days,heating_degree_days_cumulative\n2024-04-24,61.9,10.5,10.5\n2024-04-25,52.6,16.599998,27.099998\n"
+        );
+    }
+
+    #[test]
+    fn days_table_with_only_derived_columns() {
+        let map = table_data();
+        let columns = derived_columns(&map, &map, &[Derived::MeanTemperature], None);
+        let table = DaysTable { map: &map, points: vec![], columns, smoothed: None, rolling_points: vec![], flags: None };
+        assert_eq!(table.json(), "[{\"date\":\"2024-04-24\",\"temperature_mean\":54.5},{\"date\":\"2024-04-25\",\"temperature_mean\":48.4}]");
+        assert_eq!(table.csv(), "date,temperature_mean\n2024-04-24,54.5\n2024-04-25,48.4\n");
+    }
+
+    #[test]
+    fn days_table_adds_smoothed_fields() {
+        let map = table_data();
+        let smoothed = map.rolling(2);
+        let table = DaysTable { map: &map, points: vec![DataPoint::TemperatureMax], columns: vec![], smoothed: Some(smoothed), rolling_points: vec![DataPoint::TemperatureMax], flags: None };
+        assert_eq!(
+            table.json(),
+            "[{\"date\":\"2024-04-24\",\"temperature_max\":61.9,\"rolling\":{\"temperature_max\":61.9}},\
+             {\"date\":\"2024-04-25\",\"temperature_max\":52.6,\"rolling\":{\"temperature_max\":57.25}}]"
+        );
+        assert_eq!(table.csv(), "date,temperature_max,temperature_max_rolling\n2024-04-24,61.9,61.9\n2024-04-25,52.6,57.25\n");
+    }
+
+    #[test]
+    fn days_table_adds_quality_flags() {
+        let mut map = table_data();
+        map.values_mut().nth(1).unwrap().set_value(DataPoint::PrecipitationSum, Some(-0.5));
+        let flags = map.check_quality(UnitSystem::Imperial, None);
+        let table = DaysTable { map: &map, points: vec![], columns: vec![], smoothed: None, rolling_points: vec![], flags: Some(flags) };
+        assert_eq!(
+            table.json(),
+            "[{\"date\":\"2024-04-24\",\"quality\":[]},\
+             {\"date\":\"2024-04-25\",\"quality\":[{\"field\":\"precipitation_sum\",\"value\":-0.5,\"kind\":\"implausible\",\"reason\":\"negative\"}]}]"
+        );
+        assert_eq!(table.csv(), "date,quality\n2024-04-24,\n2024-04-25,precipitation_sum:negative\n");
+    }
+
+    #[test]
+    fn json_bodies_by_media_type() {
+        let headers = |content_type: Option<&'static str>| {
+            let mut headers = HeaderMap::new();
+            if let Some(content_type) = content_type {
+                headers.insert("content-type", HeaderValue::from_static(content_type));
+            }
+            headers
+        };
+        assert!(require_json(&headers(Some("application/json"))).is_ok());
+        assert!(require_json(&headers(Some("Application/JSON; charset=utf-8"))).is_ok());
+        assert!(require_json(&headers(Some("text/csv"))).is_err());
+        assert!(require_json(&headers(None)).is_err());
+    }
+
+    #[test]
+    fn json_records_are_all_days_or_all_hours() {
+        let days = json_records(r#"[{"date": "2024-04-24", "temperature_max": 61.9}, {"date": "2024-04-25", "temperature_max": 52.6}]"#).unwrap();
+        assert_eq!(days.len(), 2);
+        let hours = json_records(r#"[{"time": "2024-04-24T00:00", "temperature": 50}, {"time": "2024-04-24T01:00", "temperature": 52}]"#).unwrap();
+        assert_eq!(hours.len(), 1);
+        assert!(json_records("[]").unwrap().is_empty());
+
+        let e = json_records(r#"[{"date": "2024-04-24"}, {"date": "2024-04-25"}, {"time": "2024-04-26T00:00"}]"#).unwrap_err();
+        assert!(matches!(e, ApiError::MixedRecords { index: 2, hourly: false }));
+        assert_eq!(e.status(), StatusCode::BAD_REQUEST);
+        let e = json_records(r#"[{"time": "2024-04-24T00:00"}, {"date": "2024-04-25", "time": "2024-04-25T00:00"}]"#).unwrap_err();
+        assert!(matches!(e, ApiError::MixedRecords { index: 1, hourly: true }));
+    }
+
+    #[test]
+    fn station_ids_are_percent_decoded() {
+        assert_eq!(station_id("lawrence").unwrap(), "lawrence");
+        assert_eq!(station_id("Lawrence%20KS").unwrap(), "Lawrence KS");
+        assert_eq!(station_id("ku+1").unwrap(), "ku+1");
+        assert_eq!(station_id("caf%C3%A9").unwrap(), "café");
+        assert!(matches!(station_id("ku%2"), Err(ApiError::InvalidPath)));
+        assert!(matches!(station_id("ku%zz"), Err(ApiError::InvalidPath)));
+        assert!(matches!(station_id("%FF"), Err(ApiError::InvalidPath)));
+    }
+
+    #[test]
+    fn derived_values_by_short_or_column_name() {
+        let bases = DegreeDayBases::for_units(UnitSystem::Imperial);
+        let values = |query: &str| query_values(&Query::parse(query).unwrap(), &bases);
+        let (points, derived) = values("values=temp_max,hdd,temperature_mean").unwrap();
+        assert_eq!(points, HashSet::from([DataPoint::TemperatureMax]));
+        assert_eq!(derived, vec![Derived::HeatingDegreeDays { base: 65.0 }, Derived::MeanTemperature]);
+        let (_, derived) = values("values=heating_degree_days,hdd,temp_range,temperature_range,cooling_degree_days,growing_degree_days,gdd").unwrap();
+        let names: Vec<&str> = derived.iter().map(|derived| derived.name()).collect();
+        assert_eq!(names, vec!["heating_degree_days", "temperature_range", "cooling_degree_days", "growing_degree_days"]);
+        assert!(values("values=heating").is_err());
+    }
+}
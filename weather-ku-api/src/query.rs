@@ -0,0 +1,163 @@
+use indexmap::IndexMap;
+
+/// The parameters of a query string, percent-decoded, keeping every value given for each one in the order they came in
+pub struct Query {
+    params: IndexMap<String, Vec<String>>,
+}
+
+/// A query parameter that can't be read, sent back as a 400 naming the parameter
+#[derive(Debug)]
+pub struct QueryError {
+    /// `invalid_parameter`, `missing_parameter` or `unknown_parameter`
+    pub code: &'static str,
+    pub parameter: String,
+    pub message: String,
+}
+
+impl QueryError {
+    /// A parameter that's there but can't be read
+    pub fn new(parameter: &str, message: impl Into<String>) -> QueryError {
+        QueryError { code: "invalid_parameter", parameter: parameter.to_string(), message: message.into() }
+    }
+
+    /// A parameter the request can't do without
+    pub fn missing(parameter: &str, message: impl Into<String>) -> QueryError {
+        QueryError { code: "missing_parameter", ..QueryError::new(parameter, message) }
+    }
+}
+
+/// Value of a hexadecimal digit, None for anything else (including the signs `from_str_radix` would take)
+fn hex_digit(byte: u8) -> Option<u8> {
+    match byte {
+        b'0'..=b'9' => Some(byte - b'0'),
+        b'a'..=b'f' => Some(byte - b'a' + 10),
+        b'A'..=b'F' => Some(byte - b'A' + 10),
+        _ => None,
+    }
+}
+
+/// Decodes `+` as a space and `%XX` as the byte it stands for, None if an escape is cut short or malformed or the bytes
+/// aren't UTF-8
+pub(crate) fn percent_decode(text: &str) -> Option<String> {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => decoded.push(b' '),
+            b'%' => {
+                let high = hex_digit(*bytes.get(i + 1)?)?;
+                let low = hex_digit(*bytes.get(i + 2)?)?;
+                decoded.push(high << 4 | low);
+                i += 2;
+            }
+            byte => decoded.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8(decoded).ok()
+}
+
+impl Query {
+    /// Reads a query string (without the `?`), a parameter without `=` has an empty value
+    pub fn parse(query: &str) -> Result<Query, QueryError> {
+        let mut params: IndexMap<String, Vec<String>> = IndexMap::new();
+        for part in query.split('&').filter(|part| !part.is_empty()) {
+            let (key, value) = part.split_once('=').unwrap_or((part, ""));
+            let key = match percent_decode(key) {
+                Some(key) => key,
+                None => return Err(QueryError::new(key, "parameter name is not valid percent-encoded UTF-8")),
+            };
+            match percent_decode(value) {
+                Some(value) => params.entry(key).or_default().push(value),
+                None => return Err(QueryError::new(&key, format!("{} is not valid percent-encoded UTF-8", key))),
+            }
+        }
+        Ok(Query { params })
+    }
+
+    /// Fails on the first parameter that isn't one of `allowed`
+    pub fn only(&self, allowed: &[&str]) -> Result<(), QueryError> {
+        match self.params.keys().find(|key| !allowed.contains(&key.as_str())) {
+            Some(key) => Err(QueryError {
+                code: "unknown_parameter",
+                ..QueryError::new(key, format!("invalid query parameter '{}' (only {} allowed)", key, allowed.join(", ")))
+            }),
+            None => Ok(()),
+        }
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.params.contains_key(name)
+    }
+
+    /// The value of a parameter, the last one if it was given more than once
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.params.get(name)?.last().map(String::as_str)
+    }
+
+    /// Every item of a list parameter, from all the times it was given, split on commas and whitespace with empty items left out
+    pub fn list(&self, name: &str) -> Vec<&str> {
+        self.params
+            .get(name)
+            .into_iter()
+            .flatten()
+            .flat_map(|value| value.split(|c: char| c == ',' || c.is_whitespace()))
+            .filter(|item| !item.is_empty())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_spaces_and_escapes() {
+        assert_eq!(percent_decode("2024-04-24+2024-04-25").as_deref(), Some("2024-04-24 2024-04-25"));
+        assert_eq!(percent_decode("2024-04-24%202024-04-25").as_deref(), Some("2024-04-24 2024-04-25"));
+        assert_eq!(percent_decode("%C3%A9%2c").as_deref(), Some("é,"));
+    }
+
+    #[test]
+    fn rejects_malformed_escapes() {
+        assert_eq!(percent_decode("%4"), None);
+        assert_eq!(percent_decode("%"), None);
+        assert_eq!(percent_decode("%+1"), None);
+        assert_eq!(percent_decode("%-1"), None);
+        assert_eq!(percent_decode("%zz"), None);
+    }
+
+    #[test]
+    fn rejects_invalid_utf8() {
+        assert_eq!(percent_decode("%FF"), None);
+        assert_eq!(percent_decode("%C3"), None);
+    }
+
+    #[test]
+    fn parse_names_the_parameter_that_fails() {
+        let e = Query::parse("units=metric&dates=%zz").err().unwrap();
+        assert_eq!(e.code, "invalid_parameter");
+        assert_eq!(e.parameter, "dates");
+    }
+
+    #[test]
+    fn repeated_keys_and_missing_values() {
+        let query = Query::parse("values=temp_max&values=temp_min,hdd&units=metric&units=imperial&qc&&").unwrap();
+        assert_eq!(query.list("values"), vec!["temp_max", "temp_min", "hdd"]);
+        assert_eq!(query.get("units"), Some("imperial"));
+        assert_eq!(query.get("qc"), Some(""));
+        assert!(query.contains("qc"));
+        assert_eq!(query.get("dates"), None);
+        assert!(query.list("dates").is_empty());
+    }
+
+    #[test]
+    fn only_reports_unknown_keys() {
+        let query = Query::parse("dates=2024-04-24&dates=2024-04-25&bogus=1").unwrap();
+        assert!(query.only(&["dates", "bogus"]).is_ok());
+        let e = query.only(&["dates"]).unwrap_err();
+        assert_eq!(e.code, "unknown_parameter");
+        assert_eq!(e.parameter, "bogus");
+    }
+}
@@ -1,6 +1,11 @@
 use indexmap::IndexMap;
-use inquire::{Confirm, Editor, InquireError, Select};
-use parser::{Date, ParseError, WeatherData};
+use inquire::{Confirm, CustomType, Editor, InquireError, Select};
+use parser::stats::Selector;
+use parser::{
+    is_csv_path, read_open_meteo, stats, Conflict, CsvOptions, DataOps, Dataset, DatasetReport, Date, DegreeDayBases, Derived, EventKind,
+    Implausible, Issue, MergeStrategy, OutlierTest, ParseDiagnostic, ParseMode, ParseOptions, Period, ResamplePolicy, Station, Thresholds,
+    UnitSystem, WeatherCode, WeatherData, WeatherDataMap,
+};
 use std::process;
 
 mod pathfinder;
@@ -13,54 +18,157 @@ enum DataPoint {
     TotalPrecipitation,
     HighestPrecipitationChance,
     MaximumWindSpeed,
+    MeanTemperature,
+    TemperatureRange,
+    HeatingDegreeDays,
+    CoolingDegreeDays,
+    GrowingDegreeDays,
+}
+impl std::fmt::Display for DataPoint{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result{
+        let name = match self{
+            DataPoint::WeatherCode => "Weather Code",
+            DataPoint::HighTemperature => "High Temperature",
+            DataPoint::LowTemperature => "Low Temperature",
+            DataPoint::TotalPrecipitation => "Total Precipitation",
+            DataPoint::HighestPrecipitationChance => "Highest Precipitation Chance",
+            DataPoint::MaximumWindSpeed => "Maximum Wind Speed",
+            DataPoint::MeanTemperature => "Mean Temperature",
+            DataPoint::TemperatureRange => "Temperature Range",
+            DataPoint::HeatingDegreeDays => "Heating Degree Days",
+            DataPoint::CoolingDegreeDays => "Cooling Degree Days",
+            DataPoint::GrowingDegreeDays => "Growing Degree Days",
+        };
+        write!(f, "{}", name)
+    }
 }
 impl DataPoint{
-    fn to_string(&self) -> String{
+    const ALL: [DataPoint; 11] = [
+        DataPoint::WeatherCode,
+        DataPoint::HighTemperature,
+        DataPoint::LowTemperature,
+        DataPoint::TotalPrecipitation,
+        DataPoint::HighestPrecipitationChance,
+        DataPoint::MaximumWindSpeed,
+        DataPoint::MeanTemperature,
+        DataPoint::TemperatureRange,
+        DataPoint::HeatingDegreeDays,
+        DataPoint::CoolingDegreeDays,
+        DataPoint::GrowingDegreeDays,
+    ];
+    /// The field of the parser's weather data this data point reads from,
+    /// derived data points are measured in the units of the high temperature
+    fn field(self) -> parser::DataPoint{
         match self{
-            DataPoint::WeatherCode => "Weather Code".to_string(),
-            DataPoint::HighTemperature => "High Temperature".to_string(),
-            DataPoint::LowTemperature => "Low Temperature".to_string(),
-            DataPoint::TotalPrecipitation => "Total Precipitation".to_string(),
-            DataPoint::HighestPrecipitationChance => "Highest Precipitation Chance".to_string(),
-            DataPoint::MaximumWindSpeed => "Maximum Wind Speed".to_string(),
+            DataPoint::WeatherCode => parser::DataPoint::WeatherCode,
+            DataPoint::HighTemperature => parser::DataPoint::TemperatureMax,
+            DataPoint::LowTemperature => parser::DataPoint::TemperatureMin,
+            DataPoint::TotalPrecipitation => parser::DataPoint::PrecipitationSum,
+            DataPoint::HighestPrecipitationChance => parser::DataPoint::PrecipitationProbabilityMax,
+            DataPoint::MaximumWindSpeed => parser::DataPoint::WindSpeedMax,
+            DataPoint::MeanTemperature
+            | DataPoint::TemperatureRange
+            | DataPoint::HeatingDegreeDays
+            | DataPoint::CoolingDegreeDays
+            | DataPoint::GrowingDegreeDays => parser::DataPoint::TemperatureMax,
         }
     }
-}
-// OPERATIONS \\
-#[inline]
-fn avg(set: Vec<f32>) -> f32 {
-    let mut sum = 0.0;
-    let mut len = 0;
-    for val in set {
-        sum += val;
-        len += 1;
-    }
-    sum / len as f32
+    /// The data point that shows a field of the parser's weather data
+    fn from_field(field: parser::DataPoint) -> DataPoint {
+        match field {
+            parser::DataPoint::TemperatureMax => DataPoint::HighTemperature,
+            parser::DataPoint::TemperatureMin => DataPoint::LowTemperature,
+            parser::DataPoint::PrecipitationSum => DataPoint::TotalPrecipitation,
+            parser::DataPoint::PrecipitationProbabilityMax => DataPoint::HighestPrecipitationChance,
+            parser::DataPoint::WindSpeedMax => DataPoint::MaximumWindSpeed,
+            parser::DataPoint::WeatherCode | parser::DataPoint::Date => DataPoint::WeatherCode,
+        }
+    }
+    /// The value derived from the temperatures this data point shows, with the usual degree day bases for the given units
+    fn derived(self, recorded: UnitSystem) -> Option<Derived> {
+        let bases = DegreeDayBases::for_units(recorded);
+        match self {
+            DataPoint::MeanTemperature => Some(Derived::MeanTemperature),
+            DataPoint::TemperatureRange => Some(Derived::DiurnalRange),
+            DataPoint::HeatingDegreeDays => Some(Derived::heating_degree_days(&bases)),
+            DataPoint::CoolingDegreeDays => Some(Derived::cooling_degree_days(&bases)),
+            DataPoint::GrowingDegreeDays => Some(Derived::growing_degree_days(&bases)),
+            _ => None,
+        }
+    }
+    /// What to read from each day for this data point, in the units the data is recorded in
+    fn reading(self, recorded: UnitSystem) -> Reading {
+        match self.derived(recorded) {
+            Some(derived) => Reading::Derived(derived),
+            None => Reading::Field(self.field()),
+        }
+    }
+    /// Temperature ranges and degree days are differences, so they're shown like a spread
+    fn is_difference(self) -> bool {
+        self.derived(UnitSystem::default()).is_some_and(|derived| derived.is_difference())
+    }
 }
 
-#[inline]
-fn min(set: Vec<f32>) -> f32 {
-    let mut min = set[0];
-    for val in set {
-        if val < min {
-            min = val;
+/// What a data point reads from each day, a field of the weather data or a value derived from its temperatures
+#[derive(Clone, Copy)]
+enum Reading {
+    Field(parser::DataPoint),
+    Derived(Derived),
+}
+impl Selector for Reading {
+    fn select(&self, data: &WeatherData) -> Option<f32> {
+        match self {
+            Reading::Field(field) => field.select(data),
+            Reading::Derived(derived) => derived.select(data),
         }
     }
-    min
+}
+/// Units the loaded data is recorded in, and the units results are shown in
+#[derive(Clone, Copy)]
+struct Units {
+    recorded: UnitSystem,
+    shown: UnitSystem,
 }
 
-#[inline]
-fn max(set: Vec<f32>) -> f32 {
-    let mut max = set[0];
-    for val in set {
-        if val > max {
-            max = val;
-        }
+/// Result of an operation, formatted in whichever units are being shown
+type Message = Box<dyn Fn(Units) -> String>;
+
+/// Formats a value with its unit, converting it to the units being shown
+/// Weather codes are shown with their description instead
+fn show(value: f32, point: DataPoint, units: Units) -> String {
+    if let (DataPoint::WeatherCode, Some(code)) = (point, WeatherCode::from_value(value)) {
+        return format!("{} ({})", code, code.description());
+    }
+    if point.is_difference() {
+        return show_spread(value, point, units);
+    }
+    let field = point.field();
+    let unit = units.shown.unit(field);
+    let value = if units.shown == units.recorded {
+        value.to_string()
+    } else {
+        // Converted values are rounded so they don't show float noise
+        format!("{:.2}", units.recorded.convert(value, field, units.shown))
+    };
+    if unit.is_empty() {
+        value
+    } else {
+        format!("{} {}", value, unit)
     }
-    max
 }
 
-// END OPERATIONS \\
+/// Formats a spread of values (like a standard deviation) with its unit, which only scales between units
+fn show_spread(spread: f32, point: DataPoint, units: Units) -> String {
+    let field = point.field();
+    let spread = if units.shown == units.recorded {
+        spread.to_string()
+    } else {
+        let scale = units.recorded.convert(1.0, field, units.shown) - units.recorded.convert(0.0, field, units.shown);
+        format!("{:.2}", spread * scale)
+    };
+    format!("{} {}", spread, units.shown.unit(field))
+}
+
 fn exit_dialog(menu: fn()) {
     let exit = Confirm::new("Are you sure you want to exit?").prompt();
     match exit {
@@ -71,38 +179,133 @@ fn exit_dialog(menu: fn()) {
 }
 // INPUT METHODS \\
 fn data_from_file(path: String) {
-    let data = match std::fs::read_to_string(path) {
-        Ok(data) => data,
+    match read_file(&path) {
+        Ok(report) => load_report(report),
         Err(e) => {
             println!("Error: {}", e);
             start_menu();
-            return;
         }
-    };
+    }
+}
 
-    let weather_data = match WeatherData::from_data(data) {
-        Ok(data) => data,
-        Err(e) => {
-            handle_parse_err(e);
-            return;
-        }
-    };
-    get_options(weather_data);
-    
+/// Reads a file leniently in the format its extension says: CSV, a saved Open-Meteo response or the column text format,
+/// which is the only one that can hold more than one station
+/// The outer error is for files that can't be opened at all
+fn read_file(path: &str) -> Result<Result<DatasetReport, ParseDiagnostic>, std::io::Error> {
+    if is_csv_path(path) {
+        let file = std::fs::File::open(path)?;
+        let options = CsvOptions { mode: ParseMode::Lenient, ..CsvOptions::for_path(path) };
+        return Ok(WeatherDataMap::from_csv(file, options).map(DatasetReport::from));
+    }
+    if path.to_ascii_lowercase().ends_with(".json") {
+        // Saved Open-Meteo responses, the units they were downloaded in are kept as is
+        let import = read_open_meteo(path, ParseOptions { mode: ParseMode::Lenient });
+        return Ok(import.map(|import| DatasetReport::from(import.report)));
+    }
+    let data = std::fs::read_to_string(path)?;
+    Ok(Dataset::from_data_with(data, ParseOptions { mode: ParseMode::Lenient }))
 }
 
-//FIXME
 fn data_from_manual() {
     let editor_data = Editor::new("Enter data here:")
         .with_help_message("Save and close the file to submit!")
         .prompt();
     match editor_data {
-        Ok(data) => {
-            (data.as_str());
+        Ok(data) => parse_data(data),
+        Err(_) => start_menu(),
+    }
+}
+
+/// Parses data in the column text format leniently
+fn parse_data(data: String) {
+    load_report(Dataset::from_data_with(data, ParseOptions { mode: ParseMode::Lenient }));
+}
+
+/// Lets the user pick which station's data to work with, without asking if there's only one
+fn select_station(dataset: Dataset) -> Option<Station> {
+    let mut stations = dataset.stations;
+    if stations.is_empty() {
+        println!("Error! No stations could be read from the data");
+        start_menu();
+        return None;
+    }
+    if stations.len() == 1 {
+        return stations.pop().map(|(_, station)| station);
+    }
+    let options: Vec<String> = stations
+        .values()
+        .map(|station| {
+            let mut option = station.id.clone();
+            if let Some(name) = &station.name {
+                option.push_str(&format!(" - {}", name));
+            }
+            if let (Some(latitude), Some(longitude)) = (station.latitude, station.longitude) {
+                option.push_str(&format!(" ({}, {})", latitude, longitude));
+            }
+            option.push_str(&format!(", {} day(s)", station.data.len()));
+            option
+        })
+        .collect();
+    match Select::new("Select a station:", options.clone()).prompt() {
+        Ok(option) => {
+            let index = options.iter().position(|label| *label == option)?;
+            stations.swap_remove_index(index).map(|(_, station)| station)
+        }
+        Err(InquireError::OperationCanceled) | Err(InquireError::OperationInterrupted) => {
+            exit_dialog(start_menu);
+            None
+        }
+        Err(_) => {
+            println!("Error occured, please try again.");
+            start_menu();
+            None
+        }
+    }
+}
+
+/// Lists any problems found while parsing and lets the user continue with the days that were read
+fn load_report(report: Result<DatasetReport, ParseDiagnostic>) {
+    let report = match report {
+        Ok(report) => report,
+        Err(e) => {
+            handle_parse_err(e);
+            return;
+        }
+    };
+    let station = match select_station(report.dataset) {
+        Some(station) => station,
+        None => return,
+    };
+    if report.problems.is_empty() {
+        get_options(station.data, loaded_units(station.units));
+        return;
+    }
+
+    println!("Found {} problem(s) in the data:", report.problems.len());
+    for problem in report.problems.iter() {
+        println!("  {}", problem);
+    }
+    if station.data.is_empty() {
+        println!("Error! No complete days could be read from the data");
+        start_menu();
+        return;
+    }
+    let prompt = format!("Continue with the {} day(s) that were read successfully?", station.data.len());
+    match Confirm::new(prompt.as_str()).with_default(true).prompt() {
+        Ok(true) => get_options(station.data, loaded_units(station.units)),
+        Ok(false) => start_menu(),
+        Err(InquireError::OperationCanceled) | Err(InquireError::OperationInterrupted) => {
+            exit_dialog(start_menu);
         }
         Err(_) => start_menu(),
     }
 }
+
+/// Data that doesn't say what units it's in is taken to be imperial, like the sample data
+fn loaded_units(units: Option<UnitSystem>) -> Units {
+    let recorded = units.unwrap_or_default();
+    Units { recorded, shown: recorded }
+}
 //END INPUT METHODS\\
 
 fn start_menu() {
@@ -123,25 +326,29 @@ fn start_menu() {
     }
 }
 
-fn data_ops(data: IndexMap<Date, WeatherData>, point: DataPoint) {
+fn data_ops(data: WeatherDataMap, units: Units, point: DataPoint) {
     let range = date_range(&data);
-    let mut set = Vec::with_capacity(range.len());
-    for data in range.values() {
-        match point {
-            DataPoint::WeatherCode => {set.push(data.weather_code as f32);}
-            DataPoint::HighTemperature => set.push(data.temp_max),
-            DataPoint::LowTemperature => set.push(data.temp_min),
-            DataPoint::TotalPrecipitation => set.push(data.precip_sum),
-            DataPoint::HighestPrecipitationChance => set.push(data.precip_prob_max),
-            DataPoint::MaximumWindSpeed => set.push(data.max_wind),
-        }
-    }
-    let options: Vec<&str>;
-    if range.len() == 1 || point == DataPoint::WeatherCode {
-        options = vec!["Single Point"];
+    let field = match degree_day_base(point, units) {
+        Some(field) => field,
+        None => return,
+    };
+    // Missing values are left out of every operation, but counted so the user knows about them
+    let count = stats::count(&range, field);
+    let skipped = range.len() - count;
+    let skipped_note = if skipped > 0 {
+        format!(" ({} day(s) with missing values skipped)", skipped)
     } else {
-        options = vec!["Single Point", "Average", "Minimum", "Maximum", "Compare"];
-    }
+        String::new()
+    };
+    let options: Vec<&str> = if range.len() == 1 {
+        vec!["Single Point"]
+    } else if point == DataPoint::WeatherCode {
+        vec!["Single Point", "Most Common"]
+    } else if matches!(field, Reading::Derived(derived) if derived.is_degree_days()) {
+        vec!["Single Point", "Total", "Average", "Median", "Standard Deviation", "Minimum", "Maximum", "Compare", "Events"]
+    } else {
+        vec!["Single Point", "Average", "Median", "Standard Deviation", "Minimum", "Maximum", "Compare", "Events"]
+    };
     let op = match Select::new("Select an operation to perform: ", options).prompt() {
         Ok(op) => op,
         Err(InquireError::OperationCanceled) | Err(InquireError::OperationInterrupted) => {
@@ -154,23 +361,51 @@ fn data_ops(data: IndexMap<Date, WeatherData>, point: DataPoint) {
             return;
         }
     };
-    let message = match op {
-        "Single Point" => {single_point_select(range, point)}
+    let message: Message = match op {
+        "Single Point" => {single_point_select(range, point, field)}
+        "Events" => {events_select(range, units)}
+        "Total" | "Average" | "Median" | "Standard Deviation" | "Minimum" | "Maximum" | "Most Common" if count == 0 => {
+            let (first, last) = (*range.first().unwrap().0, *range.last().unwrap().0);
+            Box::new(move |_| format!("No {} values from {} to {}, all {} day(s) are missing", point, first, last, skipped))
+        }
+        "Total" => {
+            let total = stats::sum(&range, field).unwrap();
+            let (first, last) = (*range.first().unwrap().0, *range.last().unwrap().0);
+            Box::new(move |units| format!("Total {} from {} to {}: {}{}", point, first, last, show(total, point, units), skipped_note))
+        }
         "Average" => {
-            let avg = avg(set);
-            format!("Average {} from {} to {}: {}", point.to_string(), range.first().unwrap().0.to_string(), range.last().unwrap().0.to_string(), avg)
+            let avg = stats::mean(&range, field).unwrap();
+            let (first, last) = (*range.first().unwrap().0, *range.last().unwrap().0);
+            Box::new(move |units| format!("Average {} from {} to {}: {}{}", point, first, last, show(avg, point, units), skipped_note))
+        }
+        "Median" => {
+            let median = stats::median(&range, field).unwrap();
+            let (first, last) = (*range.first().unwrap().0, *range.last().unwrap().0);
+            Box::new(move |units| format!("Median {} from {} to {}: {}{}", point, first, last, show(median, point, units), skipped_note))
+        }
+        "Standard Deviation" => {
+            let stddev = stats::stddev(&range, field).unwrap();
+            let (first, last) = (*range.first().unwrap().0, *range.last().unwrap().0);
+            Box::new(move |units| format!("Standard deviation of {} from {} to {}: {}{}", point, first, last, show_spread(stddev, point, units), skipped_note))
         }
         "Minimum" => {
-            let min: f32 = min(set);
-            format!("Average {} from {} to {}: {}", point.to_string(), range.first().unwrap().0.to_string(), range.last().unwrap().0.to_string(), min)
+            let min = stats::argmin(&range, field).unwrap();
+            let (first, last) = (*range.first().unwrap().0, *range.last().unwrap().0);
+            Box::new(move |units| format!("Minimum {} from {} to {}: {} on {}{}", point, first, last, show(min.value, point, units), min.date, skipped_note))
         }
         "Maximum" => {
-            let max = max(set);
-            format!("Average {} from {} to {}: {}", point.to_string(), range.first().unwrap().0.to_string(), range.last().unwrap().0.to_string(), max)
+            let max = stats::argmax(&range, field).unwrap();
+            let (first, last) = (*range.first().unwrap().0, *range.last().unwrap().0);
+            Box::new(move |units| format!("Maximum {} from {} to {}: {} on {}{}", point, first, last, show(max.value, point, units), max.date, skipped_note))
+        }
+        "Most Common" => {
+            let mode = stats::mode_weather_code(&range).unwrap();
+            let days = range.values().filter(|data| data.weather_code == Some(mode)).count();
+            let (first, last) = (*range.first().unwrap().0, *range.last().unwrap().0);
+            Box::new(move |_| format!("Most common {} from {} to {}: {} ({}) on {} day(s){}", point, first, last, mode, mode.description(), days, skipped_note))
         }
         "Compare" => {
-            let options = vec!["Weather Code", "High Temperature", "Low Temperature", "Total Precipitation", "Highest Precipitation Chance", "Maximum Wind Speed"];
-            let compare = match Select::new("Select a data point to compare: ", options).prompt() {
+            let compare = match Select::new("Select a data point to compare: ", DataPoint::ALL.to_vec()).prompt() {
                 Ok(compare) => compare,
                 Err(InquireError::OperationCanceled) | Err(InquireError::OperationInterrupted) => {
                     exit_dialog(start_menu);
@@ -182,10 +417,7 @@ fn data_ops(data: IndexMap<Date, WeatherData>, point: DataPoint) {
                     return;
                 }
             };
-            let mut comp_vec = Vec::with_capacity(range.len());
-            for (date, _) in range.iter() {
-                comp_vec.push(date.to_string());
-            }
+            let comp_vec: Vec<Date> = range.keys().copied().collect();
             let mut date_first = match Select::new("Select first date to compare", comp_vec.clone()).prompt() {
                 Ok(date) => date,
                 Err(InquireError::OperationCanceled) | Err(InquireError::OperationInterrupted) => {
@@ -210,77 +442,95 @@ fn data_ops(data: IndexMap<Date, WeatherData>, point: DataPoint) {
                     return;
                 }
             };
-            let data_first = range.get(&Date::from_string(&date_first).unwrap()).unwrap();
-            let data_second = range.get(&Date::from_string(&date_second).unwrap()).unwrap();
+            let data_first = range.get(&date_first).unwrap();
+            let data_second = range.get(&date_second).unwrap();
 
-            let mut comp1: f32 = 0.0;
-            let mut comp2: f32 = 0.0;
-        
-            match compare{
-                "Weather Code" => {
-                    comp1 = data_first.weather_code as f32;
-                    comp2 = data_second.weather_code as f32;
+            // Keeps the base picked for this data point when comparing it
+            let compare_field = if compare == point { field } else { compare.reading(units.recorded) };
+            let (mut comp1, mut comp2) = match (compare_field.select(data_first), compare_field.select(data_second)) {
+                (Some(comp1), Some(comp2)) => (comp1, comp2),
+                (None, _) => {
+                    return final_menu(data, units, point, Box::new(move |_| format!("Can't compare, {} is missing for {}", compare, date_first)));
                 }
-                "High Temperature" => {
-                    comp1 = data_first.temp_max;
-                    comp2 = data_second.temp_max;
+                (_, None) => {
+                    return final_menu(data, units, point, Box::new(move |_| format!("Can't compare, {} is missing for {}", compare, date_second)));
                 }
-                "Low Temperature" => {
-                    comp1 = data_first.temp_min;
-                    comp2 = data_second.temp_min;
-                }
-                "Total Precipitation" => {
-                    comp1 = data_first.precip_sum;
-                    comp2 = data_second.precip_sum;
-                }
-                "Highest Precipitation Chance" => {
-                    comp1 = data_first.precip_prob_max;
-                    comp2 = data_second.precip_prob_max;
-                }
-                "Maximum Wind Speed" => {
-                    comp1 = data_first.max_wind;
-                    comp2 = data_second.max_wind;
-                }
-                _ => {
-                    println!("Invalid option! Please try again");
-                    data_ops(data, point);
-                    return;
-                }
-            }
+            };
             if comp2 > comp1{
-                let temp = comp1;
-                comp1 = comp2;
-                comp2 = temp;
-
-                let temp = date_first;
-                date_first = date_second;
-                date_second = temp;
-            }
-            if compare == "Weather Code"{
-                format!("{} at {} ({}) is larger than {} at {} ({})", compare, date_first, comp1 as u8, compare, date_second, comp2 as u8);
-
+                std::mem::swap(&mut comp1, &mut comp2);
+                std::mem::swap(&mut date_first, &mut date_second);
             }
-            format!("{} at {} ({}) is larger than {} at {} ({})", compare, date_first, comp1, compare, date_second, comp2)
+            Box::new(move |units| {
+                format!("{} at {} ({}) is larger than {} at {} ({})", compare, date_first, show(comp1, compare, units), compare, date_second, show(comp2, compare, units))
+            })
 
 
         }
         
         _ => {
             println!("Invalid option! Please try again");
-            data_ops(data, point);
+            data_ops(data, units, point);
             return;
         }
     };
-    let final_menu = Select::new(message.as_str(), vec!["Try Different Operation", "Select New Datapoint", "Return To Main Menu", "Exit"]).prompt();
-    match final_menu {
+    final_menu(data, units, point, message);
+}
+
+/// Asks for the base temperature of degree days, in the units the data is recorded in
+/// Other data points are read as is, None if the prompt was left
+fn degree_day_base(point: DataPoint, units: Units) -> Option<Reading> {
+    let prompt = |default: f32| {
+        let message = format!("Base temperature ({}):", units.recorded.unit(parser::DataPoint::TemperatureMax));
+        CustomType::<f32>::new(message.as_str()).with_default(default).prompt()
+    };
+    let bases = DegreeDayBases::for_units(units.recorded);
+    let base = match point {
+        DataPoint::HeatingDegreeDays => prompt(bases.heating),
+        DataPoint::CoolingDegreeDays => prompt(bases.cooling),
+        DataPoint::GrowingDegreeDays => prompt(bases.growing),
+        _ => return Some(point.reading(units.recorded)),
+    };
+    let base = match base {
+        Ok(base) => base,
+        Err(InquireError::OperationCanceled) | Err(InquireError::OperationInterrupted) => {
+            exit_dialog(start_menu);
+            return None;
+        }
+        Err(_) => {
+            println!("Error occured, please try again.");
+            start_menu();
+            return None;
+        }
+    };
+    let bases = DegreeDayBases { heating: base, cooling: base, growing: base, ..bases };
+    let derived = match point {
+        DataPoint::HeatingDegreeDays => Derived::heating_degree_days(&bases),
+        DataPoint::CoolingDegreeDays => Derived::cooling_degree_days(&bases),
+        _ => Derived::growing_degree_days(&bases),
+    };
+    Some(Reading::Derived(derived))
+}
+
+/// Shows the result of an operation and asks what to do next
+fn final_menu(data: WeatherDataMap, units: Units, point: DataPoint, message: Message) {
+    let toggle = match units.shown {
+        UnitSystem::Metric => "Show In Imperial Units",
+        UnitSystem::Imperial => "Show In Metric Units",
+    };
+    let select = Select::new(message(units).as_str(), vec!["Try Different Operation", "Select New Datapoint", toggle, "Return To Main Menu", "Exit"]).prompt();
+    match select {
         Ok(option) => match option {
-            "Try Different Operation" => data_ops(data, point),
-            "Select New Datapoint" => get_options(data),
+            "Try Different Operation" => data_ops(data, units, point),
+            "Select New Datapoint" => get_options(data, units),
+            "Show In Imperial Units" | "Show In Metric Units" => {
+                let units = Units { shown: units.shown.other(), ..units };
+                final_menu(data, units, point, message);
+            }
             "Return To Main Menu" => start_menu(),
             "Exit" => exit_dialog(start_menu),
             _ => {
                 println!("Invalid option! Please try again");
-                data_ops(data, point);
+                data_ops(data, units, point);
             }
         },
         Err(InquireError::OperationCanceled) | Err(InquireError::OperationInterrupted) => {
@@ -290,43 +540,104 @@ fn data_ops(data: IndexMap<Date, WeatherData>, point: DataPoint) {
     }
 }
 
-fn single_point_select(range: IndexMap<Date, &WeatherData>, point: DataPoint) -> String {
-    let point_str = point.to_string();
-    let options = range.keys().map(|date| date.to_string()).collect();
+fn single_point_select(range: WeatherDataMap, point: DataPoint, field: Reading) -> Message {
+    let options = range.keys().copied().collect();
     let date = match Select::new("Select a date to sample: ", options).prompt() {
         Ok(date) => date,
         Err(InquireError::OperationCanceled) | Err(InquireError::OperationInterrupted) => {
             exit_dialog(start_menu);
-            return "".to_string();
+            return Box::new(|_| String::new());
         }
         Err(_) => {
             println!("Error occured, please try again.");
             start_menu();
-            return "".to_string();
+            return Box::new(|_| String::new());
         }
     };
-    let data = range.get(&Date::from_string(&date).unwrap()).unwrap();
-    let data = match point {
-        DataPoint::WeatherCode => data.weather_code.to_string(),
-        DataPoint::HighTemperature => data.temp_max.to_string(),
-        DataPoint::LowTemperature => data.temp_min.to_string(),
-        DataPoint::TotalPrecipitation => data.precip_sum.to_string(),
-        DataPoint::HighestPrecipitationChance => data.precip_prob_max.to_string(),
-        DataPoint::MaximumWindSpeed => data.max_wind.to_string(),
+    let day = range.get(&date).unwrap();
+    let value = field.select(day);
+    let filled = if day.synthetic { " (filled in)" } else { "" };
+    // Degree days are also added up from the start of the range
+    let total = match field {
+        Reading::Derived(derived) if derived.is_degree_days() => stats::cumulative(&range, field)[&date],
+        _ => None,
     };
-    return format!("{} for {}: {}", point_str, date, data);
+    let first = *range.first().unwrap().0;
+    Box::new(move |units| {
+        let value = match value {
+            Some(value) => show(value, point, units),
+            None => "missing".to_string(),
+        };
+        match total {
+            Some(total) if date != first => format!("{} for {}{}: {} ({} since {})", point, date, filled, value, show(total, point, units), first),
+            _ => format!("{} for {}{}: {}", point, date, filled, value),
+        }
+    })
 }
 
-fn get_options(data: IndexMap<Date, WeatherData>) {
-    
-    let options = vec![
-        "Weather Code",
-        "High Temperature",
-        "Low Temperature",
-        "Total Precipitation",
-        "Highest Precipitation Chance",
-        "Maximum Wind Speed",
-    ];
+/// Lists the dry spells, rain streaks, heat waves, frost days or high wind days in the range, with the usual thresholds
+fn events_select(range: WeatherDataMap, units: Units) -> Message {
+    let options = vec!["Dry Spells", "Rain Streaks", "Heat Waves", "Frost Days", "High Wind Days"];
+    let (name, kind) = match Select::new("Select the events to find: ", options).prompt() {
+        Ok(name @ "Dry Spells") => (name, EventKind::DrySpell),
+        Ok(name @ "Rain Streaks") => (name, EventKind::RainStreak),
+        Ok(name @ "Heat Waves") => (name, EventKind::HeatWave),
+        Ok(name @ "Frost Days") => (name, EventKind::Frost),
+        Ok(name) => (name, EventKind::HighWind),
+        Err(InquireError::OperationCanceled) | Err(InquireError::OperationInterrupted) => {
+            exit_dialog(start_menu);
+            return Box::new(|_| String::new());
+        }
+        Err(_) => {
+            println!("Error occured, please try again.");
+            start_menu();
+            return Box::new(|_| String::new());
+        }
+    };
+    let thresholds = Thresholds::for_units(units.recorded);
+    let events = range.events(kind, &thresholds);
+    let (first, last) = (*range.first().unwrap().0, *range.last().unwrap().0);
+    Box::new(move |units| {
+        let threshold = match kind {
+            EventKind::DrySpell => format!("less than {} of precipitation", show(thresholds.wet, DataPoint::TotalPrecipitation, units)),
+            EventKind::RainStreak => format!("at least {} of precipitation", show(thresholds.wet, DataPoint::TotalPrecipitation, units)),
+            EventKind::HeatWave => format!(
+                "high of at least {} for {} day(s) or more",
+                show(thresholds.heat, DataPoint::HighTemperature, units),
+                thresholds.heat_wave_days
+            ),
+            EventKind::Frost => format!("low of at most {}", show(thresholds.frost, DataPoint::LowTemperature, units)),
+            EventKind::HighWind => format!("wind of at least {}", show(thresholds.wind, DataPoint::MaximumWindSpeed, units)),
+        };
+        let longest = match events.iter().copied().reduce(|longest, event| if event.days > longest.days { event } else { longest }) {
+            Some(longest) => longest,
+            None => return format!("No {} from {} to {} ({})", name, first, last, threshold),
+        };
+        let mut message = format!(
+            "{} from {} to {} ({}): {} event(s), {} day(s) in total, longest {} day(s) from {} to {}",
+            name,
+            first,
+            last,
+            threshold,
+            events.len(),
+            events.iter().map(|event| event.days).sum::<u32>(),
+            longest.days,
+            longest.start,
+            longest.end
+        );
+        for event in events.iter() {
+            message.push_str(&format!("\n  {} to {}: {} day(s)", event.start, event.end, event.days));
+        }
+        message
+    })
+}
+
+fn get_options(data: WeatherDataMap, units: Units) {
+    let mut options: Vec<String> = DataPoint::ALL.iter().map(|point| point.to_string()).collect();
+    options.push("Summaries".to_string());
+    options.push("Check data quality".to_string());
+    options.push("Find and fill gaps".to_string());
+    options.push("Load additional file".to_string());
     let select = match Select::new("Select a data point to sample:", options).prompt() {
         Ok(option) => option,
         Err(InquireError::OperationCanceled) | Err(InquireError::OperationInterrupted) => {
@@ -339,25 +650,213 @@ fn get_options(data: IndexMap<Date, WeatherData>) {
             return;
         }
     };
-    match select {
-        "Weather Code" => {data_ops(data, DataPoint::WeatherCode);}
-        "High Temperature" => {data_ops(data, DataPoint::HighTemperature);}
-        "Low Temperature" => {data_ops(data, DataPoint::LowTemperature);}
-        "Total Precipitation" => {data_ops(data, DataPoint::TotalPrecipitation);}
-        "Highest Precipitation Chance" => {data_ops(data, DataPoint::HighestPrecipitationChance);}
-        "Maximum Wind Speed" => {data_ops(data, DataPoint::MaximumWindSpeed);}
-        _ => {
+    if select == "Summaries" {
+        summaries(data, units);
+        return;
+    }
+    if select == "Check data quality" {
+        check_quality(data, units);
+        return;
+    }
+    if select == "Find and fill gaps" {
+        fill_gaps(data, units);
+        return;
+    }
+    if select == "Load additional file" {
+        load_additional(data, units, pathfinder::file_dialog("."));
+        return;
+    }
+    match DataPoint::ALL.into_iter().find(|point| point.to_string() == select) {
+        Some(point) => data_ops(data, units, point),
+        None => {
             println!("Invalid option! Please try again");
-            get_options(data);
+            get_options(data, units);
+        }
+    }
+}
+
+/// Prints a weekly, monthly or yearly summary of all the data, one line per period
+fn summaries(data: WeatherDataMap, units: Units) {
+    let period = match Select::new("Summarize by: ", vec!["Week", "Month", "Year"]).prompt() {
+        Ok("Week") => Period::Week,
+        Ok("Month") => Period::Month,
+        Ok(_) => Period::Year,
+        Err(InquireError::OperationCanceled) | Err(InquireError::OperationInterrupted) => {
+            exit_dialog(start_menu);
+            return;
+        }
+        Err(_) => {
+            println!("Error occured, please try again.");
+            start_menu();
+            return;
+        }
+    };
+    for (start, summary) in data.resample(period, ResamplePolicy::SkipMissing).iter() {
+        let value = |point: DataPoint| match summary.value(point.field()) {
+            Some(value) => show(value, point, units),
+            None => "missing".to_string(),
+        };
+        println!(
+            "{}: average high {}, lowest low {}, total precipitation {}, highest wind speed {}, mostly {}",
+            period.label(start),
+            value(DataPoint::HighTemperature),
+            value(DataPoint::LowTemperature),
+            value(DataPoint::TotalPrecipitation),
+            value(DataPoint::MaximumWindSpeed),
+            value(DataPoint::WeatherCode),
+        );
+    }
+    get_options(data, units);
+}
+
+/// Prints every impossible value in the data, and the outliers found with the chosen test
+fn check_quality(data: WeatherDataMap, units: Units) {
+    let options = vec!["Z-Score", "Interquartile Range", "Impossible Values Only"];
+    let test = match Select::new("Test for outliers with: ", options).prompt() {
+        Ok("Z-Score") => OutlierTest::from_name("z_score"),
+        Ok("Interquartile Range") => OutlierTest::from_name("iqr"),
+        Ok(_) => None,
+        Err(InquireError::OperationCanceled) | Err(InquireError::OperationInterrupted) => {
+            exit_dialog(start_menu);
+            return;
+        }
+        Err(_) => {
+            println!("Error occured, please try again.");
+            start_menu();
+            return;
+        }
+    };
+    let flags = data.check_quality(units.recorded, test);
+    if flags.is_empty() {
+        println!("No problems found in the {} day(s) of data", data.len());
+    }
+    for (date, day) in flags.iter() {
+        for flag in day {
+            let point = DataPoint::from_field(flag.field);
+            let problem = match flag.issue {
+                Issue::Implausible { reason: Implausible::Negative } => "is negative".to_string(),
+                Issue::Implausible { reason: Implausible::TooLow } => "is lower than possible".to_string(),
+                Issue::Implausible { reason: Implausible::TooHigh } => "is higher than possible".to_string(),
+                Issue::Implausible { reason: Implausible::AboveTemperatureMax } => "is above the high temperature".to_string(),
+                Issue::Outlier { test, score } => {
+                    let scale = if test == "iqr" { "interquartile ranges" } else { "standard deviations" };
+                    let side = if score < 0.0 { "below" } else { "above" };
+                    format!("is an outlier ({:.2} {} {} the rest)", score.abs(), scale, side)
+                }
+            };
+            println!("{}: {} {} {}", date, point, show(flag.value, point, units), problem);
+        }
+    }
+    if !flags.is_empty() {
+        println!("{} value(s) flagged on {} day(s)", flags.values().map(Vec::len).sum::<usize>(), flags.len());
+    }
+    get_options(data, units);
+}
+
+/// Lists the days missing from the data and offers to fill them in, the filled days are marked as such wherever they're shown
+fn fill_gaps(data: WeatherDataMap, units: Units) {
+    let gaps = data.gaps();
+    if gaps.is_empty() {
+        println!("No gaps, every day from the first date to the last is there");
+        get_options(data, units);
+        return;
+    }
+    for gap in gaps.iter() {
+        if gap.days == 1 {
+            println!("Missing {}", gap.start);
+        } else {
+            println!("Missing {} to {} ({} days)", gap.start, gap.end, gap.days);
+        }
+    }
+    let missing: u32 = gaps.iter().map(|gap| gap.days).sum();
+    let prompt = format!("Fill in the {} missing day(s)? Temperatures and wind are interpolated, precipitation is taken as zero", missing);
+    match Confirm::new(prompt.as_str()).with_default(true).prompt() {
+        Ok(true) => {
+            let filled = data.fill_gaps();
+            get_options(filled, units);
+        }
+        Ok(false) => get_options(data, units),
+        Err(InquireError::OperationCanceled) | Err(InquireError::OperationInterrupted) => {
+            exit_dialog(start_menu);
         }
+        Err(_) => start_menu(),
     }
 }
 
-fn date_range(data: &IndexMap<Date, WeatherData>) -> IndexMap<Date, &WeatherData> {
-    let mut dates_to_display: Vec<String> = Vec::with_capacity(data.len());
-    for node in data.values() {
-        dates_to_display.push(node.date.to_string());
+/// Merges the days of another file into the data, asking how to settle the days both have different values for
+fn load_additional(data: WeatherDataMap, units: Units, path: String) {
+    let report = match read_file(&path) {
+        Ok(Ok(report)) => report,
+        Ok(Err(e)) => {
+            println!("Error! {}", e);
+            get_options(data, units);
+            return;
+        }
+        Err(e) => {
+            println!("Error: {}", e);
+            get_options(data, units);
+            return;
+        }
+    };
+    for problem in report.problems.iter() {
+        println!("  {}", problem);
+    }
+    let station = match select_station(report.dataset) {
+        Some(station) => station,
+        None => return,
+    };
+    if station.data.is_empty() {
+        println!("Error! No complete days could be read from the file");
+        get_options(data, units);
+        return;
+    }
+    // Compared in the units the loaded data is recorded in
+    let other = station.data.convert_units(loaded_units(station.units).recorded, units.recorded);
+
+    let options = vec!["Prefer Current Data", "Prefer New File", "Average", "Fail On Conflict"];
+    let strategy = match Select::new("Settle days both have different values for by: ", options).prompt() {
+        Ok("Prefer Current Data") => MergeStrategy::PreferLeft,
+        Ok("Prefer New File") => MergeStrategy::PreferRight,
+        Ok("Average") => MergeStrategy::Average,
+        Ok(_) => MergeStrategy::FailOnConflict,
+        Err(InquireError::OperationCanceled) | Err(InquireError::OperationInterrupted) => {
+            exit_dialog(start_menu);
+            return;
+        }
+        Err(_) => {
+            println!("Error occured, please try again.");
+            start_menu();
+            return;
+        }
+    };
+    let print_conflicts = |conflicts: &[Conflict]| {
+        for conflict in conflicts {
+            for field in conflict.fields.iter() {
+                let point = DataPoint::from_field(field.field);
+                println!("  {}: {} is {} now, {} in the new file", conflict.date, point, show(field.left, point, units), show(field.right, point, units));
+            }
+        }
+    };
+    match data.merge(&other, strategy) {
+        Ok(report) => {
+            println!("Added {} new day(s), {} day(s) were already there", report.added, report.overlapping);
+            if !report.conflicts.is_empty() {
+                println!("{} day(s) had different values:", report.conflicts.len());
+                print_conflicts(&report.conflicts);
+            }
+            get_options(report.data, units);
+        }
+        Err(conflicts) => {
+            println!("{} day(s) have different values, nothing was merged:", conflicts.len());
+            print_conflicts(&conflicts);
+            get_options(data, units);
+        }
     }
+}
+
+fn date_range(data: &WeatherDataMap) -> WeatherDataMap {
+    let mut dates_to_display: Vec<Date> = data.keys().copied().collect();
+    dates_to_display.sort();
     let begin_date = match Select::new("Begin date: ", dates_to_display.clone()).prompt() {
         Ok(date) => date,
         Err(InquireError::OperationCanceled) | Err(InquireError::OperationInterrupted) => {
@@ -372,19 +871,10 @@ fn date_range(data: &IndexMap<Date, WeatherData>) -> IndexMap<Date, &WeatherData
     };
 
     // Ensures that only selectable dates are displayed
-    for i in 0..dates_to_display.len() {
-        if dates_to_display[i] == begin_date {
-            dates_to_display.drain(0..i);
-            break;
-        }
-    }
+    dates_to_display.retain(|date| *date >= begin_date);
 
     let end_date = match Select::new(
-        format!(
-            "Begin date: {} | End date: ",
-            begin_date.clone().to_string()
-        )
-        .as_str(),
+        format!("Begin date: {} | End date: ", begin_date).as_str(),
         dates_to_display,
     )
     .prompt()
@@ -401,61 +891,24 @@ fn date_range(data: &IndexMap<Date, WeatherData>) -> IndexMap<Date, &WeatherData
         }
     };
 
-    let start_point = data
-        .get_index_of(&Date::from_string(&begin_date).unwrap())
-        .unwrap();
-    let end_point = data
-        .get_index_of(&Date::from_string(&end_date).unwrap())
-        .unwrap();
-    let range = data.get_range(start_point..end_point + 1).unwrap();
-    let range: IndexMap<Date, &WeatherData> = range
-        .into_iter()
-        .map(|(date, data)| (date.clone(), data))
-        .collect();
+    let range = data.take_range(&begin_date, &end_date);
+    let span = begin_date.days_between(&end_date) + 1;
+    if (range.len() as i64) < span {
+        println!(
+            "Note: {} of the {} days from {} to {} have no data, they can be filled in with \"Find and fill gaps\"",
+            span - range.len() as i64,
+            span,
+            begin_date,
+            end_date
+        );
+    }
     range
 }
 
 #[inline]
-fn handle_parse_err(error: ParseError) {
-    print!("Error! ");
-    match error {
-        ParseError::InvalidDate(date) => {
-            println!("Invalid date: {}", date);
-            start_menu();
-        }
-        ParseError::InvalidWeatherCode(code) => {
-            println!("Invalid weather code: {}", code);
-            start_menu();
-        }
-        ParseError::InvalidTemperature(temp) => {
-            println!("Invalid temperature: {}", temp);
-            start_menu();
-        }
-        ParseError::InvalidWind(wind) => {
-            println!("Invalid wind: {}", wind);
-            start_menu();
-        }
-        ParseError::InvalidPrecipitationProbability(prob) => {
-            println!("Invalid precipitation probability: {}", prob);
-            start_menu();
-        }
-        ParseError::InvalidPrecipitation(sum) => {
-            println!("Invalid precipitation: {}", sum);
-            start_menu();
-        }
-        ParseError::InvalidLine(line) => {
-            println!("Invalid line: {}", line);
-            start_menu();
-        }
-        ParseError::TooManyValues => {
-            println!("Inconsistent number of values!");
-            start_menu();
-        }
-        ParseError::DuplicateDate(date) => {
-            println!("Duplicate date: {}", date.to_string());
-            start_menu();
-        }
-    }
+fn handle_parse_err(error: ParseDiagnostic) {
+    println!("Error! {}", error);
+    start_menu();
 }
 
 fn main() {
@@ -34,7 +34,7 @@ fn file_path_add(path_to_add: &str) -> FileOption{
         file_map.remove(k);
     });
 
-    let file_names: Vec<String> = file_map.keys().map(|k| k.clone()).collect();
+    let file_names: Vec<String> = file_map.keys().cloned().collect();
 
     let msg = format!("Select a file from {}", path_to_add);
     let msg = msg.as_str();
@@ -26,7 +26,7 @@ function DateDisplay({ data }: { data: WeatherData[] }){
             {data.map((entry, index) => (
             <tr key={index}>
               <th scope='row'>{entry.date}</th>
-              <td>{entry.weather_code}</td>
+              <td>{entry.weather_code} {entry.weather_description && `(${entry.weather_description})`}</td>
               <td>{entry.temperature_max}</td>
               <td>{entry.temperature_min}</td>
               <td>{entry.precipitation_sum}</td>
@@ -9,12 +9,13 @@ export enum Options {
 
 export interface WeatherData {
     date: String;
-    weather_code: number;
-    temperature_max: number
-    temperature_min: number
-    precipitation_sum: number
-    wind_speed_max: number
-    precipitation_probability_max: number
+    weather_code: number | null;
+    weather_description: string | null;
+    temperature_max: number | null
+    temperature_min: number | null
+    precipitation_sum: number | null
+    wind_speed_max: number | null
+    precipitation_probability_max: number | null
 }
 
 const addr = 'http://localhost:3000';